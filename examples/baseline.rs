@@ -0,0 +1,211 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Measures the baseline published in `ion_log::bench`, counting allocations with a global
+//! allocator.
+//!
+//! ```text
+//! cargo run --release --features bench --example baseline [RECORDS]
+//! ```
+/*===============================================================================================*/
+
+// Crate imports
+extern crate ion_log;
+
+// Module imports
+use ion_log::*;
+use ion_log::bench::{self, Report, Scenario};
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::env;
+use std::fs;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+
+// The allocations made since the process started, including reallocations.
+static ALLOCATIONS: AtomicU64 = AtomicU64::new (0);
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/*===============================================================================================*/
+/*------COUNTING ALLOCATOR STRUCT----------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Counts allocations, leaving the work to the system allocator.
+struct CountingAllocator;
+
+/*===============================================================================================*/
+/*------COUNTING ALLOCATOR TRAIT IMPLEMENTATIONS-------------------------------------------------*/
+/*===============================================================================================*/
+
+unsafe impl GlobalAlloc for CountingAllocator {
+
+    unsafe fn alloc (&self, layout: Layout) -> *mut u8 {
+
+        ALLOCATIONS.fetch_add (1, Ordering::Relaxed);
+        unsafe {System.alloc (layout)}
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    unsafe fn dealloc (&self, pointer: *mut u8, layout: Layout) {
+        unsafe {System.dealloc (pointer, layout)}
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    unsafe fn realloc (&self, pointer: *mut u8, layout: Layout, size: usize) -> *mut u8 {
+
+        ALLOCATIONS.fetch_add (1, Ordering::Relaxed);
+        unsafe {System.realloc (pointer, layout, size)}
+    }
+}
+
+/*===============================================================================================*/
+/*------FUNCTIONS--------------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+fn main () {
+
+    let records = env::args ().nth (1).and_then (|records| records.parse ().ok ()).unwrap_or (100_000);
+    let path = env::temp_dir ().join (format! ("ion_log_baseline_{}.log", std::process::id ()));
+    let path = path.to_string_lossy ().into_owned ();
+
+    let mut pool = config (&path, true, LogFormat::Json);
+    pool.format_threads = 4;
+
+    let mut shedding = config (&path, true, LogFormat::Text);
+    shedding.overflow_policy = OverflowPolicy::Drop;
+    shedding.load_shedding   = Some (LoadShedding {trace: 0.5, debug: 0.5, info: 0.75});
+
+    let runs = [("sync, text",                    config (&path, false, LogFormat::Text),   &[1, 4, 16][..]),
+                ("sync, JSON",                    config (&path, false, LogFormat::Json),   &[4]),
+                ("sync, binary",                  config (&path, false, LogFormat::Binary), &[4]),
+                ("async, text",                   config (&path, true, LogFormat::Text),    &[1, 4, 16]),
+                ("async, JSON",                   config (&path, true, LogFormat::Json),    &[4]),
+                ("async, JSON, 4 format threads", pool,                                     &[4]),
+                ("async, binary",                 config (&path, true, LogFormat::Binary),  &[4]),
+                ("async, text, load shedding",    shedding,                                 &[4, 16])];
+
+    println! ("{} cores, {} records per run, best of 5 runs", cores (), records);
+    println! ();
+    println! ("| Config                        | Threads | Records/s | p50   | p99   | Allocations per record | Dropped |");
+    println! ("|-------------------------------|---------|-----------|-------|-------|------------------------|---------|");
+
+    for &(name, ref config, threads) in runs.iter () {
+
+        for &threads in threads {
+
+            let (report, allocations, dropped) = measure (config, threads, records);
+
+            println! ("| {:<29} | {:<7} | {:<9} | {:<5} | {:<5} | {:<22} | {:<7} |",
+                      name,
+                      threads,
+                      thousands (report.records_per_sec as u64),
+                      duration (report.p50.as_nanos ()),
+                      duration (report.p99.as_nanos ()),
+                      format! ("{:.2}", allocations as f64 / records as f64),
+                      format! ("{:.0}%", dropped as f64 * 100.0 / records as f64));
+        }
+    }
+
+    ion_log::release ();
+    drop (fs::remove_file (&path));
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Returns a config writing to the file only.
+fn config (path: &str, async_output: bool, log_format: LogFormat) -> LogConfig {
+
+    LogConfig {
+
+        log_to_io:       false,
+        log_to_file:     true,
+        log_output_path: path.to_string (),
+        log_format,
+        async_output,
+        ..LogConfig::new ()
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Runs the scenario five times after a warm up, returning the fastest run with its
+// allocations and the records it dropped.
+fn measure (config: &LogConfig, threads: usize, records: usize) -> (Report, u64, usize) {
+
+    bench::run (&Scenario {records: records / 5, threads, ..Scenario::new (config.clone ())}).unwrap ();
+
+    let mut best: Option<(Report, u64, usize)> = None;
+
+    for _ in 0..5 {
+
+        let allocations = ALLOCATIONS.load (Ordering::Relaxed);
+        let overflows = ion_log::overflow_count ();
+        let report = bench::run (&Scenario {records, threads, ..Scenario::new (config.clone ())}).unwrap ();
+        let allocations = ALLOCATIONS.load (Ordering::Relaxed) - allocations;
+        let dropped = ion_log::overflow_count () - overflows;
+
+        if best.as_ref ().is_none_or (|(best, _, _)| report.records_per_sec > best.records_per_sec) {
+            best = Some ((report, allocations, dropped));
+        }
+    }
+
+    best.unwrap ()
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+fn cores () -> usize {
+    thread::available_parallelism ().map_or (1, |cores| cores.get ())
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Formats a number with comma separated thousands.
+fn thousands (number: u64) -> String {
+
+    let digits = number.to_string ();
+    let mut formatted = String::new ();
+
+    for (i, digit) in digits.chars ().enumerate () {
+
+        if i > 0 && (digits.len () - i).is_multiple_of (3) {
+            formatted.push (',');
+        }
+
+        formatted.push (digit);
+    }
+
+    formatted
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Formats nanoseconds in the largest unit under a thousand.
+fn duration (nanos: u128) -> String {
+
+    match nanos {
+
+        0..=999          => format! ("{}ns", nanos),
+        1_000..=9_999    => format! ("{:.1}µs", nanos as f64 / 1_000.0),
+        10_000..=999_999 => format! ("{}µs", nanos / 1_000),
+        _                => format! ("{:.1}ms", nanos as f64 / 1_000_000.0),
+    }
+}
@@ -0,0 +1,371 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! The control socket set with `LogConfig::admin_socket`, for adjusting a running logger.
+/*===============================================================================================*/
+
+// Module imports
+use health::SinkStatus;
+
+use std::io;
+#[cfg (unix)]
+use std::io::ErrorKind;
+use std::io::{BufRead, BufReader};
+use std::io::prelude::{Read, Write};
+use std::net::{Ipv4Addr, TcpListener};
+#[cfg (unix)]
+use std::fs;
+#[cfg (unix)]
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+#[cfg (unix)]
+use std::os::unix::net::UnixListener;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// How often the server checks whether it should stop, while waiting for connections or input.
+const POLL_INTERVAL: Duration = Duration::from_millis (50);
+
+const HELP: &str = "commands: level <level>, filter add <directives>, filter clear, flush, rotate, stats, help";
+
+/*===============================================================================================*/
+/*------ADMIN ENDPOINT ENUM----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Where the admin control socket listens, set with `LogConfig::admin_socket`.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// # #[cfg (unix)]
+/// # fn main () {
+/// use std::os::unix::fs::PermissionsExt;
+///
+/// let path = std::env::temp_dir ().join (format! ("ion_log_admin_{}.sock", std::process::id ()));
+///
+/// let mut config = LogConfig::new ();
+/// config.admin_socket = Some (AdminEndpoint::Unix (path.to_string_lossy ().into_owned ()));
+/// ion_log::init (&config).unwrap ();
+///
+/// let mode = std::fs::metadata (&path).unwrap ().permissions ().mode ();
+/// assert_eq! (mode & 0o777, 0o600);
+/// # ion_log::release ();
+/// # }
+/// # #[cfg (not (unix))]
+/// # fn main () {}
+/// ```
+#[derive (Clone, Debug, PartialEq, Eq)]
+pub enum AdminEndpoint {
+
+    /// A Unix domain socket at the path, which only its owner can connect to. A stale socket
+    /// file left by an earlier run is replaced, and the file is removed at `release`.
+    /// Starting fails if anything other than a socket is at the path.
+    #[cfg (unix)]
+    Unix (String),
+    /// A TCP port on `127.0.0.1`. Every local user can connect to it, so on a shared host
+    /// prefer a Unix socket.
+    Tcp (u16),
+}
+
+/*===============================================================================================*/
+/*------ADMIN SERVER STRUCT----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// The thread serving the admin endpoint.
+pub struct AdminServer {
+
+    // Private
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+enum Listener {
+
+    Tcp (TcpListener),
+    #[cfg (unix)]
+    Unix (UnixListener, String),
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+trait Connection: Read + Write {}
+
+impl<T: Read + Write> Connection for T {}
+
+/*===============================================================================================*/
+/*------ADMIN SERVER PUBLIC METHODS--------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl AdminServer {
+
+    pub fn start (endpoint: &AdminEndpoint) -> io::Result<Self> {
+
+        let listener = match *endpoint {
+
+            AdminEndpoint::Tcp (port) => Listener::Tcp (TcpListener::bind ((Ipv4Addr::LOCALHOST, port))?),
+
+            #[cfg (unix)]
+            AdminEndpoint::Unix (ref path) => {
+
+                remove_stale_socket (path)?;
+
+                let listener = UnixListener::bind (path)?;
+
+                // Anyone who can connect can change the logging, so only the owner may.
+                if let Err (e) = fs::set_permissions (path, fs::Permissions::from_mode (0o600)) {
+
+                    drop (fs::remove_file (path));
+                    return Err (e);
+                }
+
+                Listener::Unix (listener, path.clone ())
+            },
+        };
+
+        match listener {
+
+            Listener::Tcp (ref listener) => listener.set_nonblocking (true)?,
+            #[cfg (unix)]
+            Listener::Unix (ref listener, _) => listener.set_nonblocking (true)?,
+        }
+
+        let stop = Arc::new (AtomicBool::new (false));
+        let thread_stop = stop.clone ();
+        let thread = thread::Builder::new ().name ("ion_log_admin".to_string ())
+                                            .spawn (move || listener.serve (&thread_stop))?;
+
+        Ok (AdminServer {stop, thread})
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Stops the server and waits for it to finish the current command.
+    pub fn stop (self) {
+
+        self.stop.store (true, Ordering::Relaxed);
+        drop (self.thread.join ());
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Stops the server without waiting, for when the caller might be the server itself.
+    pub fn stop_later (self) {
+        self.stop.store (true, Ordering::Relaxed);
+    }
+}
+
+/*===============================================================================================*/
+/*------LISTENER PRIVATE METHODS-----------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Listener {
+
+    // Serves one connection at a time until told to stop.
+    fn serve (self, stop: &AtomicBool) {
+
+        while !stop.load (Ordering::Relaxed) {
+
+            match self.accept () {
+
+                Ok (Some (connection)) => drop (serve_connection (connection, stop)),
+                Ok (None) | Err (_) => thread::sleep (POLL_INTERVAL),
+            }
+        }
+
+        #[cfg (unix)]
+        {
+            if let Listener::Unix (_, ref path) = self {
+                drop (fs::remove_file (path));
+            }
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Returns the next connection, or `None` if there isn't one waiting.
+    fn accept (&self) -> io::Result<Option<Box<dyn Connection>>> {
+
+        let result: io::Result<Box<dyn Connection>> = match *self {
+
+            Listener::Tcp (ref listener) => listener.accept ().and_then (|(stream, _)| {
+
+                stream.set_nonblocking (false)?;
+                stream.set_read_timeout (Some (POLL_INTERVAL))?;
+                Ok (Box::new (stream) as Box<dyn Connection>)
+            }),
+
+            #[cfg (unix)]
+            Listener::Unix (ref listener, _) => listener.accept ().and_then (|(stream, _)| {
+
+                stream.set_nonblocking (false)?;
+                stream.set_read_timeout (Some (POLL_INTERVAL))?;
+                Ok (Box::new (stream) as Box<dyn Connection>)
+            }),
+        };
+
+        match result {
+
+            Ok (connection) => Ok (Some (connection)),
+            Err (ref e) if e.kind () == io::ErrorKind::WouldBlock => Ok (None),
+            Err (e) => Err (e),
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Answers commands until the client disconnects or the server is told to stop.
+fn serve_connection (connection: Box<dyn Connection>, stop: &AtomicBool) -> io::Result<()> {
+
+    let mut reader = BufReader::new (connection);
+    let mut line = String::new ();
+
+    while !stop.load (Ordering::Relaxed) {
+
+        match reader.read_line (&mut line) {
+
+            Ok (0) => break,
+            Ok (_) => {
+
+                let reply = match run (line.trim ()) {
+
+                    Ok (output) => format! ("{}ok\n", output),
+                    Err (reason) => format! ("error: {}\n", reason),
+                };
+
+                reader.get_mut ().write_all (reply.as_bytes ())?;
+                line.clear ();
+            },
+
+            // A timeout keeps what was read of the line, and gives a chance to check `stop`.
+            Err (ref e) if e.kind () == io::ErrorKind::WouldBlock || e.kind () == io::ErrorKind::TimedOut => (),
+            Err (e) => return Err (e),
+        }
+    }
+
+    Ok (())
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Runs a command, returning its output lines.
+fn run (command: &str) -> Result<String, String> {
+
+    let mut words = command.splitn (2, ' ');
+    let name = words.next ().unwrap_or ("");
+    let argument = words.next ().unwrap_or ("").trim ();
+
+    match name {
+
+        "" => Ok (String::new ()),
+        "help" => Ok (format! ("{}\n", HELP)),
+        "flush" => ::flush ().map (|_| String::new ()).map_err (|e| e.to_string ()),
+        "rotate" => ::rotate ().map (|_| String::new ()).map_err (|e| e.to_string ()),
+        "stats" => stats (),
+
+        "level" => {
+
+            let level = argument.parse ().map_err (|_| format! ("invalid level `{}`", argument))?;
+            update (|config| {
+
+                config.max_log_level = level;
+                Ok (())
+            })
+        },
+
+        "filter" => {
+
+            let mut words = argument.splitn (2, ' ');
+
+            match (words.next (), words.next ()) {
+
+                (Some ("add"), Some (directives)) => update (|config| config.parse_filters (directives).map_err (|e| e.to_string ())),
+                (Some ("clear"), None) => update (|config| {
+
+                    config.module_filters.clear ();
+                    Ok (())
+                }),
+
+                _ => Err ("expected `filter add <directives>` or `filter clear`".to_string ()),
+            }
+        },
+
+        _ => Err (format! ("unknown command `{}`; {}", name, HELP)),
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Changes the running logger's config.
+fn update<F: FnOnce (&mut ::LogConfig) -> Result<(), String>> (change: F) -> Result<String, String> {
+
+    let mut config = ::current_config ().ok_or ("the logger isn't running")?;
+
+    change (&mut config)?;
+    ::reconfigure (&config).map_err (|e| e.to_string ())?;
+    Ok (String::new ())
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+fn stats () -> Result<String, String> {
+
+    let config = ::current_config ().ok_or ("the logger isn't running")?;
+    let filters: Vec<_> = config.module_filters.iter ()
+                                               .map (|filter| format! ("{}={}", filter.module, filter.max_log_level))
+                                               .collect ();
+
+    let mut output = format! ("session {}\nlevel {}\nfilters {}\noverflows {}\n",
+                              ::session_id ().unwrap_or_default (),
+                              config.max_log_level,
+                              filters.join (","),
+                              ::overflow_count ());
+
+    for sink in ::health () {
+
+        let status = match sink.status {
+
+            SinkStatus::Ok => "ok".to_string (),
+            SinkStatus::Degraded {ref last_error} => format! ("degraded ({})", last_error),
+            SinkStatus::Down {ref last_error, ..} => format! ("down ({})", last_error),
+        };
+
+        output.push_str (&format! ("sink {} {}\n", sink.name, status));
+    }
+
+    Ok (output)
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Removes a socket file left at the path, refusing to touch anything else that is there.
+#[cfg (unix)]
+fn remove_stale_socket (path: &str) -> io::Result<()> {
+
+    match fs::symlink_metadata (path) {
+
+        Ok (ref metadata) if metadata.file_type ().is_socket () => fs::remove_file (path),
+        Ok (_) => Err (io::Error::new (ErrorKind::AlreadyExists, format! ("{} exists and isn't a socket", path))),
+        Err (ref error) if error.kind () == ErrorKind::NotFound => Ok (()),
+        Err (error) => Err (error),
+    }
+}
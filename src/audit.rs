@@ -0,0 +1,86 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! The audit log, a separate file for security relevant events.
+//!
+//! Audit records skip every filter and queue. Each one is appended and synced to disk before
+//! `audit!` returns, and a record that can't be written is reported to the caller rather
+//! than dropped.
+/*===============================================================================================*/
+
+// Module imports
+use format;
+
+use std::fmt::Write as FmtWrite;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::prelude::Write;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/*===============================================================================================*/
+/*------AUDIT LOG STRUCT-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+pub struct AuditLog {
+
+    // Private
+    path: String,
+    file: Mutex<File>,
+}
+
+/*===============================================================================================*/
+/*------AUDIT LOG PUBLIC METHODS-----------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl AuditLog {
+
+    // Opens the audit log for appending, so earlier records are never truncated.
+    pub fn open (path: &str) -> io::Result<Self> {
+
+        Ok (AuditLog {
+
+            path: path.to_string (),
+            file: Mutex::new (OpenOptions::new ().create (true).append (true).open (path)?),
+        })
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    pub fn path (&self) -> &str {
+        &self.path
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Writes a record as `<timestamp> <session ID> [module - line] AUDIT: message`,
+    // then waits for it to reach the disk.
+    pub fn write (&self, timestamp: SystemTime, module_path: &str, line: u32, message: &str, session_id: &str) -> io::Result<()> {
+
+        let mut text = String::new ();
+
+        format::timestamp (&mut text, timestamp);
+        text.push (' ');
+        text.push_str (session_id);
+        writeln! (text, " [{} - {}] AUDIT: {}", module_path, line, message).unwrap ();
+
+        let mut file = self.file.lock ().unwrap ();
+
+        file.write_all (text.as_bytes ())?;
+        file.sync_data ()
+    }
+}
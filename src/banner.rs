@@ -0,0 +1,131 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! The records written at the start and end of a logging session.
+/*===============================================================================================*/
+
+// Module imports
+use format;
+use {LogConfig, LogFormat};
+
+use std::env::consts;
+use std::fmt::Write;
+use std::time::SystemTime;
+
+/*===============================================================================================*/
+/*------SESSION BANNER STRUCT--------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Describes the application in the records written at `init` and `release`.
+///
+/// Build information has to come from the application crate, so fill it in with `env!`.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let mut banner = SessionBanner::new (env! ("CARGO_PKG_NAME"), env! ("CARGO_PKG_VERSION"));
+/// banner.git_hash = option_env! ("GIT_HASH").map (String::from);
+///
+/// let mut config = LogConfig::new ();
+/// config.session_banner = Some (banner);
+/// ```
+#[derive (Clone, Debug, PartialEq, Eq)]
+pub struct SessionBanner {
+
+    // Public
+    /// The application name.
+    pub app_name: String,
+    /// The application version.
+    pub version:  String,
+    /// The commit the application was built from, if known.
+    pub git_hash: Option<String>,
+}
+
+/*===============================================================================================*/
+/*------SESSION BANNER PUBLIC METHODS------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl SessionBanner {
+
+    /// Returns a new instance of `SessionBanner`, without a git hash.
+    pub fn new (app_name: &str, version: &str) -> Self {
+
+        SessionBanner {
+
+            app_name: app_name.to_string (),
+            version:  version.to_string (),
+            git_hash: None,
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// The message for the first record of a session, such as
+// `session start: game 1.2.0 (4f2a9c1) on linux x86_64 at 2016-05-01T12:00:00.000000Z; level=info, terminal, file=game.log (text)`.
+pub fn header (banner: &SessionBanner, config: &LogConfig, started: SystemTime) -> String {
+
+    let mut message = format! ("session start: {} {}", banner.app_name, banner.version);
+
+    if let Some (ref git_hash) = banner.git_hash {
+        write! (message, " ({})", git_hash).unwrap ();
+    }
+
+    write! (message, " on {} {} at ", consts::OS, consts::ARCH).unwrap ();
+    format::timestamp (&mut message, started);
+    write! (message, "; level={}", config.max_log_level.to_string ().to_lowercase ()).unwrap ();
+
+    if !config.module_filters.is_empty () {
+        write! (message, ", {} module filters", config.module_filters.len ()).unwrap ();
+    }
+
+    if config.log_to_io {
+        message.push_str (", terminal");
+    }
+
+    if config.log_to_file {
+
+        let log_format = match config.log_format {
+
+            LogFormat::Text        => "text",
+            LogFormat::Json        => "json",
+            LogFormat::Binary      => "binary",
+            LogFormat::MessagePack => "msgpack",
+            LogFormat::Cbor        => "cbor",
+        };
+
+        let path = if config.log_file_handle.is_some () && config.log_output_path.is_empty () {"<handle>"} else {&config.log_output_path};
+        write! (message, ", file={} ({})", path, log_format).unwrap ();
+    }
+
+    if config.async_output {
+        write! (message, ", async ({} records)", config.async_capacity).unwrap ();
+    }
+
+    message
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// The message for the last record of a session, such as `session end: game 1.2.0 after 3600s`.
+pub fn footer (banner: &SessionBanner, started: SystemTime, now: SystemTime) -> String {
+
+    let uptime = now.duration_since (started).unwrap_or_default ();
+    format! ("session end: {} {} after {}s", banner.app_name, banner.version, uptime.as_secs ())
+}
@@ -0,0 +1,231 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! A harness for measuring logging throughput and call latency with a given config, enabled
+//! by the `bench` feature.
+//!
+//! # Example
+//! ```
+//! # use ion_log::*;
+//! use ion_log::bench::{self, Scenario};
+//!
+//! let mut config = LogConfig::new ();
+//! config.log_to_io = false;
+//! config.async_output = true;
+//!
+//! let report = bench::run (&Scenario {records: 10_000, ..Scenario::new (config)}).unwrap ();
+//! println! ("{:.0} records/s, p99 {:?}", report.records_per_sec, report.p99);
+//! # ion_log::release ();
+//! ```
+//!
+//! # Baseline
+//! Measured with `cargo run --release --features bench --example baseline`, which counts
+//! allocations with a global allocator. That was on a VM with one Xeon core, so threads take
+//! turns rather than running at once: the multi-thread rows show what contention costs on a
+//! single core, and say nothing about scaling across cores. Each line is the best of five
+//! runs of 100,000 records with the default scenario of 64 byte messages, writing to a file
+//! without terminal output. Dropped is the share of records counted in `overflow_count`.
+//!
+//! | Config                        | Threads | Records/s | p50   | p99   | Allocations per record | Dropped |
+//! |-------------------------------|---------|-----------|-------|-------|------------------------|---------|
+//! | sync, text                    | 1       | 1,798,000 | 348ns | 4.7µs | 0                      | 0%      |
+//! | sync, text                    | 4       | 2,358,000 | 293ns | 3.9µs | 0                      | 0%      |
+//! | sync, text                    | 16      | 1,560,000 | 455ns | 5.8µs | 0                      | 0%      |
+//! | sync, JSON                    | 4       | 437,000   | 1.9µs | 8.8µs | 1                      | 0%      |
+//! | sync, binary                  | 4       | 1,814,000 | 411ns | 3.8µs | 0                      | 0%      |
+//! | async, text                   | 1       | 1,105,000 | 354ns | 7.3µs | 1                      | 0%      |
+//! | async, text                   | 4       | 1,327,000 | 358ns | 710ns | 1                      | 0%      |
+//! | async, text                   | 16      | 1,264,000 | 332ns | 763ns | 1                      | 0%      |
+//! | async, JSON                   | 4       | 430,000   | 488ns | 1.1µs | 2                      | 0%      |
+//! | async, JSON, 4 format threads | 4       | 537,000   | 352ns | 760ns | 3.04                   | 0%      |
+//! | async, binary                 | 4       | 1,042,000 | 399ns | 890ns | 1                      | 0%      |
+//! | async, text, load shedding    | 4       | 3,829,000 | 135ns | 6.7µs | 0.06                   | 94%     |
+//! | async, text, load shedding    | 16      | 3,483,000 | 161ns | 605ns | 0.05                   | 95%     |
+//!
+//! Each producing thread queues into a buffer of its own, so async text holds its throughput
+//! from one thread to sixteen, with a p99 under a microsecond. Before, every producer sent
+//! through one shared channel, and the same runs managed 321,000 records a second with a p99
+//! of 77µs at four threads, and 168,000 with a p99 of 1.0ms at sixteen. Producers still meet
+//! on the logger's read lock and the queue's depth count, which only a multi-core host will
+//! show the cost of.
+//! The format pool raises async JSON throughput by a quarter, since formatting overlaps the
+//! previous batch's write. With the `Drop` overflow policy and load shedding of trace and
+//! debug at half and info at three quarters full, producers keep sub-microsecond latency.
+//!
+//! Async records only allocate for messages over 63 bytes, which the default scenario's
+//! are. With 32 byte messages, async text and binary make no allocations per record, where
+//! they made three before targets were interned.
+//!
+//! Runs on this VM vary by up to a third, so these numbers are a baseline for comparing
+//! changes on the same machine, not a promise for any other. Run the example on the
+//! target hardware for multi-core numbers.
+/*===============================================================================================*/
+
+// Module imports
+use LogConfig;
+
+use std::io;
+use std::iter;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/*===============================================================================================*/
+/*------SCENARIO STRUCT--------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// A config to measure, and the load to put on it.
+#[derive (Clone, Debug)]
+pub struct Scenario {
+
+    // Public
+    /// The config the logger runs with.
+    pub config:         LogConfig,
+    /// The number of records to log, split between the threads.
+    pub records:        usize,
+    /// The number of threads logging at once.
+    pub threads:        usize,
+    /// The length of each message, in bytes.
+    pub message_length: usize,
+}
+
+/*===============================================================================================*/
+/*------REPORT STRUCT----------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// The results of running a `Scenario`.
+#[derive (Clone, Debug, PartialEq)]
+pub struct Report {
+
+    // Public
+    /// The number of records logged.
+    pub records:         usize,
+    /// The time from the first call until every record was flushed.
+    pub elapsed:         Duration,
+    /// The records logged per second, over `elapsed`.
+    pub records_per_sec: f64,
+    /// The median time a logging call took.
+    pub p50:             Duration,
+    /// The 99th percentile of the time a logging call took.
+    pub p99:             Duration,
+    /// The longest a logging call took.
+    pub max:             Duration,
+}
+
+/*===============================================================================================*/
+/*------SCENARIO PUBLIC METHODS------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Scenario {
+
+    /// Returns a scenario logging 100,000 records of 64 bytes from one thread.
+    pub fn new (config: LogConfig) -> Self {
+
+        Scenario {
+
+            config,
+            records:        100_000,
+            threads:        1,
+            message_length: 64,
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Logs the scenario's records at the info level, and measures how long it takes.
+///
+/// The logger is initialized with the scenario's config, or reconfigured with it if it's
+/// already running. The time until every record is flushed is included in `Report::elapsed`,
+/// so asynchronous configs are measured end to end.
+///
+/// # Errors
+/// Fails if the logger can't be initialized or reconfigured, or can't flush.
+pub fn run (scenario: &Scenario) -> io::Result<Report> {
+
+    if ::current_config ().is_some () {
+        ::reconfigure (&scenario.config)?;
+    }
+
+    else if !::try_init (&scenario.config)? {
+        return Err (io::Error::other ("another logger is already installed"));
+    }
+
+    let threads = scenario.threads.max (1);
+    let message: String = iter::repeat_n ('x', scenario.message_length).collect ();
+    let start = Instant::now ();
+
+    let workers: Vec<_> = (0..threads).map (|thread| {
+
+        // Spread the remainder over the first threads.
+        let records = scenario.records / threads + usize::from (thread < scenario.records % threads);
+        let message = message.clone ();
+
+        thread::spawn (move || log_records (records, &message))
+    }).collect ();
+
+    let mut latencies: Vec<Duration> = workers.into_iter ()
+                                              .flat_map (|worker| worker.join ().unwrap ())
+                                              .collect ();
+
+    ::flush ()?;
+
+    let elapsed = start.elapsed ();
+
+    latencies.sort ();
+
+    Ok (Report {
+
+        records:         latencies.len (),
+        elapsed,
+        records_per_sec: latencies.len () as f64 / elapsed.as_secs_f64 (),
+        p50:             percentile (&latencies, 50),
+        p99:             percentile (&latencies, 99),
+        max:             latencies.last ().cloned ().unwrap_or_default (),
+    })
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Logs the records, returning how long each call took.
+fn log_records (records: usize, message: &str) -> Vec<Duration> {
+
+    let logger = ::scoped ("ion_log::bench");
+
+    (0..records).map (|record| {
+
+        let start = Instant::now ();
+
+        logger.info (format_args! ("{} {}", record, message));
+        start.elapsed ()
+    }).collect ()
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Returns the latency below which the given percent of the sorted latencies fall.
+fn percentile (latencies: &[Duration], percent: usize) -> Duration {
+
+    match latencies.len () {
+
+        0 => Duration::default (),
+        length => latencies[(length * percent / 100).min (length - 1)],
+    }
+}
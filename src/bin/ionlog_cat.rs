@@ -0,0 +1,268 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! `ionlog-cat` prints ionLog binary log files.
+//!
+//! ```text
+//! ionlog-cat [-f] [--json] [--no-colour] [--level LEVEL] [--target PREFIX]
+//!            [--since TIME] [--until TIME] FILE
+//! ```
+/*===============================================================================================*/
+
+// Crate imports
+extern crate ansi_term;
+extern crate ion_log;
+extern crate log;
+
+// Module imports
+use ansi_term::Colour::{Green, Blue, Purple, Yellow, Red};
+use ion_log::reader::{self, Index, Reader, Record};
+
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter};
+use std::io::prelude::{Read, Write};
+use std::process;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const USAGE: &str = "usage: ionlog-cat [-f] [--json] [--no-colour] [--level LEVEL] [--target PREFIX] \
+                     [--since TIME] [--until TIME] FILE";
+
+/*===============================================================================================*/
+/*------OPTIONS STRUCT---------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+struct Options {
+
+    // Private
+    path: String,
+    follow: bool,
+    json: bool,
+    coloured_output: bool,
+    max_log_level: log::LogLevelFilter,
+    target: Option<String>,
+    since: Option<SystemTime>,
+    until: Option<SystemTime>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Wraps a file being followed, waiting for more data instead of reporting the end of it.
+struct FollowInput<R: Read> {
+
+    // Private
+    input: R,
+}
+
+/*===============================================================================================*/
+/*------FOLLOW INPUT TRAIT IMPLEMENTATIONS-------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl<R: Read> Read for FollowInput<R> {
+
+    fn read (&mut self, buf: &mut [u8]) -> io::Result<usize> {
+
+        loop {
+
+            let count = self.input.read (buf)?;
+
+            if count > 0 || buf.is_empty () {
+                return Ok (count);
+            }
+
+            thread::sleep (Duration::from_millis (250));
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------OPTIONS PRIVATE METHODS------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Options {
+
+    fn parse<I: Iterator<Item = String>> (mut args: I) -> Result<Self, String> {
+
+        let mut options = Options {
+
+            path: String::new (),
+            follow: false,
+            json: false,
+            coloured_output: true,
+            max_log_level: log::LogLevelFilter::Trace,
+            target: None,
+            since: None,
+            until: None,
+        };
+
+        while let Some (arg) = args.next () {
+
+            match arg.as_str () {
+
+                "-f" | "--follow" => options.follow = true,
+                "--json"          => options.json = true,
+                "--no-colour"     => options.coloured_output = false,
+                "--level"  => options.max_log_level = Options::value (&mut args, &arg)?.parse ()
+                                                      .map_err (|_| "invalid level".to_string ())?,
+                "--target" => options.target = Some (Options::value (&mut args, &arg)?),
+                "--since"  => options.since  = Some (Options::time (&mut args, &arg)?),
+                "--until"  => options.until  = Some (Options::time (&mut args, &arg)?),
+                "-h" | "--help" => return Err (String::new ()),
+                _ if arg.starts_with ('-') => return Err (format! ("unknown option {}", arg)),
+                _ if options.path.is_empty () => options.path = arg,
+                _ => return Err ("only one file can be given".to_string ()),
+            }
+        }
+
+        if options.path.is_empty () {
+            return Err ("no file given".to_string ());
+        }
+
+        Ok (options)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn value<I: Iterator<Item = String>> (args: &mut I, option: &str) -> Result<String, String> {
+        args.next ().ok_or_else (|| format! ("{} needs a value", option))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn time<I: Iterator<Item = String>> (args: &mut I, option: &str) -> Result<SystemTime, String> {
+
+        let value = Options::value (args, option)?;
+        reader::parse_timestamp (&value).ok_or_else (|| format! ("invalid time {}", value))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn matches (&self, record: &Record) -> bool {
+
+        record.level <= self.max_log_level &&
+        self.target.as_ref ().is_none_or (|target| record.target.starts_with (target.as_str ())) &&
+        self.since.is_none_or (|since| record.timestamp >= since) &&
+        self.until.is_none_or (|until| record.timestamp <= until)
+    }
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+fn print_records<I: Iterator<Item = io::Result<Record>>> (records: I, options: &Options) -> io::Result<()> {
+
+    let stdout = io::stdout ();
+    let mut out = BufWriter::new (stdout.lock ());
+
+    for record in records {
+
+        let record = record?;
+
+        if !options.matches (&record) {
+            continue;
+        }
+
+        if options.json {
+            writeln! (out, "{}", record.to_json ())?;
+        }
+
+        else if options.coloured_output {
+
+            let colour = match record.level {
+
+                log::LogLevel::Trace => Green,
+                log::LogLevel::Debug => Blue,
+                log::LogLevel::Info  => Purple,
+                log::LogLevel::Warn  => Yellow,
+                log::LogLevel::Error => Red
+            };
+
+            writeln! (out, "{}", colour.paint (record.to_text ().trim_end_matches ('\n')))?;
+        }
+
+        else {
+            write! (out, "{}", record.to_text ())?;
+        }
+
+        // Records trickle in slowly when following, so show each one straight away.
+        if options.follow {
+            out.flush ()?;
+        }
+    }
+
+    out.flush ()
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+fn run (options: &Options) -> io::Result<()> {
+
+    let file = BufReader::new (File::open (&options.path)?);
+
+    if options.follow {
+        return print_records (Reader::new (FollowInput {input: file})?, options);
+    }
+
+    let mut reader = Reader::new (file)?;
+
+    // Without a usable index, this reads from the start instead.
+    if let Some (since) = options.since {
+
+        if let Ok (index) = Index::open (&options.path) {
+            reader.seek (&index, since)?;
+        }
+    }
+
+    // Stop reading once past the end of the range, rather than at the end of the file.
+    match options.until {
+
+        Some (until) => print_records (reader.range (options.since.unwrap_or (UNIX_EPOCH), until), options),
+        None => print_records (reader, options),
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+fn main () {
+
+    let options = match Options::parse (env::args ().skip (1)) {
+
+        Ok (options) => options,
+        Err (message) => {
+
+            if !message.is_empty () {
+                eprintln! ("ionlog-cat: {}", message);
+            }
+
+            eprintln! ("{}", USAGE);
+            process::exit (2);
+        }
+    };
+
+    if let Err (e) = run (&options) {
+
+        // Stop quietly when piped into something like `head`.
+        if e.kind () != io::ErrorKind::BrokenPipe {
+
+            eprintln! ("ionlog-cat: {}: {}", options.path, e);
+            process::exit (1);
+        }
+    }
+}
@@ -0,0 +1,393 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! `ionlog-tail` follows one or more ionLog binary log files, merging their records by time.
+//!
+//! ```text
+//! ionlog-tail [-n COUNT] [--json] [--no-colour] [--filter DIRECTIVES] FILE...
+//! ```
+//!
+//! The last `COUNT` records already in the files are shown first, then new records as they
+//! are written. `--filter` takes the same directives as `LogConfig::parse_filters`, such as
+//! `info,my_app::net=trace`.
+/*===============================================================================================*/
+
+// Crate imports
+extern crate ansi_term;
+extern crate ion_log;
+extern crate log;
+
+// Module imports
+use ansi_term::Colour::{Green, Blue, Purple, Yellow, Red};
+use ion_log::LogConfig;
+use ion_log::reader::{Reader, Record};
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter};
+use std::io::prelude::{Read, Write};
+use std::process;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const USAGE: &str = "usage: ionlog-tail [-n COUNT] [--json] [--no-colour] [--filter DIRECTIVES] FILE...";
+
+// How long new records are held back, so records from files polled later can be put before them.
+const MERGE_WINDOW: Duration = Duration::from_millis (500);
+
+/*===============================================================================================*/
+/*------OPTIONS STRUCT---------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+struct Options {
+
+    // Private
+    paths: Vec<String>,
+    count: usize,
+    json: bool,
+    coloured_output: bool,
+    filter: LogConfig,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// What the thread following each file sends back.
+enum Message {
+
+    Record   (Box<Record>),
+    CaughtUp (usize),
+    Failed   (usize, io::Error),
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Wraps a file being followed, waiting for more data instead of reporting the end of it.
+// The first time the end is reached, it reports that the file's existing records have been read.
+struct FollowInput<R: Read> {
+
+    // Private
+    input: R,
+    index: usize,
+    caught_up: Option<Sender<Message>>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// A record waiting to be printed, ordered so the oldest is at the top of a `BinaryHeap`.
+struct Pending {
+
+    // Private
+    sequence: u64,
+    record: Record,
+}
+
+/*===============================================================================================*/
+/*------FOLLOW INPUT TRAIT IMPLEMENTATIONS-------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl<R: Read> Read for FollowInput<R> {
+
+    fn read (&mut self, buf: &mut [u8]) -> io::Result<usize> {
+
+        loop {
+
+            let count = self.input.read (buf)?;
+
+            if count > 0 || buf.is_empty () {
+                return Ok (count);
+            }
+
+            if let Some (caught_up) = self.caught_up.take () {
+                drop (caught_up.send (Message::CaughtUp (self.index)));
+            }
+
+            thread::sleep (Duration::from_millis (250));
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------PENDING TRAIT IMPLEMENTATIONS------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Ord for Pending {
+
+    // Reversed, as `BinaryHeap` pops the greatest. Records with the same time keep their order.
+    fn cmp (&self, other: &Self) -> Ordering {
+
+        (other.record.timestamp, other.sequence).cmp (&(self.record.timestamp, self.sequence))
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl PartialOrd for Pending {
+
+    fn partial_cmp (&self, other: &Self) -> Option<Ordering> {
+        Some (self.cmp (other))
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl PartialEq for Pending {
+
+    fn eq (&self, other: &Self) -> bool {
+        self.cmp (other) == Ordering::Equal
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl Eq for Pending {}
+
+/*===============================================================================================*/
+/*------OPTIONS PRIVATE METHODS------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Options {
+
+    fn parse<I: Iterator<Item = String>> (mut args: I) -> Result<Self, String> {
+
+        let mut options = Options {
+
+            paths: Vec::new (),
+            count: 10,
+            json: false,
+            coloured_output: true,
+            filter: LogConfig::new (),
+        };
+
+        while let Some (arg) = args.next () {
+
+            match arg.as_str () {
+
+                "--json"      => options.json = true,
+                "--no-colour" => options.coloured_output = false,
+                "-n" | "--lines" => options.count = Options::value (&mut args, &arg)?.parse ()
+                                                    .map_err (|_| "invalid count".to_string ())?,
+                "--filter" => options.filter.parse_filters (&Options::value (&mut args, &arg)?)
+                                            .map_err (|e| e.to_string ())?,
+                "-h" | "--help" => return Err (String::new ()),
+                _ if arg.starts_with ('-') => return Err (format! ("unknown option {}", arg)),
+                _ => options.paths.push (arg),
+            }
+        }
+
+        if options.paths.is_empty () {
+            return Err ("no file given".to_string ());
+        }
+
+        Ok (options)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn value<I: Iterator<Item = String>> (args: &mut I, option: &str) -> Result<String, String> {
+        args.next ().ok_or_else (|| format! ("{} needs a value", option))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn matches (&self, record: &Record) -> bool {
+        record.level <= self.filter.level_for (&record.target)
+    }
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Reads a file on its own thread, sending back each record.
+fn follow (index: usize, path: String, messages: Sender<Message>) {
+
+    thread::spawn (move || {
+
+        let result = File::open (&path).and_then (|file| {
+
+            let input = FollowInput {
+
+                input: BufReader::new (file),
+                index,
+                caught_up: Some (messages.clone ()),
+            };
+
+            for record in Reader::new (input)? {
+
+                if messages.send (Message::Record (Box::new (record?))).is_err () {
+                    break;
+                }
+            }
+
+            Ok (())
+        });
+
+        if let Err (e) = result {
+            drop (messages.send (Message::Failed (index, e)));
+        }
+    });
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+fn print_record<W: Write> (out: &mut W, record: &Record, options: &Options) -> io::Result<()> {
+
+    if options.json {
+        return writeln! (out, "{}", record.to_json ());
+    }
+
+    if !options.coloured_output {
+        return write! (out, "{}", record.to_text ());
+    }
+
+    let colour = match record.level {
+
+        log::LogLevel::Trace => Green,
+        log::LogLevel::Debug => Blue,
+        log::LogLevel::Info  => Purple,
+        log::LogLevel::Warn  => Yellow,
+        log::LogLevel::Error => Red
+    };
+
+    writeln! (out, "{}", colour.paint (record.to_text ().trim_end_matches ('\n')))
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+fn run (options: &Options) -> io::Result<()> {
+
+    let (sender, messages) = mpsc::channel ();
+
+    for (index, path) in options.paths.iter ().enumerate () {
+        follow (index, path.clone (), sender.clone ());
+    }
+
+    drop (sender);
+
+    let stdout = io::stdout ();
+    let mut out = BufWriter::new (stdout.lock ());
+    let mut pending = BinaryHeap::new ();
+    let mut sequence = 0;
+    let mut following = options.paths.len ();
+
+    // Collect what is already in the files, then show the newest records in order.
+    let mut caught_up = vec! [false; options.paths.len ()];
+
+    while caught_up.contains (&false) {
+
+        match messages.recv () {
+
+            Ok (Message::Record (record)) => if options.matches (&record) {
+                pending.push (Pending {sequence, record: *record});
+            },
+
+            Ok (Message::CaughtUp (index)) => caught_up[index] = true,
+            Ok (Message::Failed (index, e)) => {
+
+                eprintln! ("ionlog-tail: {}: {}", options.paths[index], e);
+                caught_up[index] = true;
+                following -= 1;
+            },
+
+            Err (_) => break,
+        }
+
+        sequence += 1;
+    }
+
+    let backlog = pending.into_sorted_vec ();
+
+    // The heap is ordered newest first, so the newest records are at the start.
+    for entry in backlog.iter ().take (options.count).rev () {
+        print_record (&mut out, &entry.record, options)?;
+    }
+
+    out.flush ()?;
+    pending = BinaryHeap::new ();
+
+    while following > 0 {
+
+        match messages.recv_timeout (MERGE_WINDOW / 2) {
+
+            Ok (Message::Record (record)) => if options.matches (&record) {
+                pending.push (Pending {sequence, record: *record});
+            },
+
+            Ok (Message::CaughtUp (_)) => (),
+            Ok (Message::Failed (index, e)) => {
+
+                eprintln! ("ionlog-tail: {}: {}", options.paths[index], e);
+                following -= 1;
+            },
+
+            Err (RecvTimeoutError::Timeout) => (),
+            Err (RecvTimeoutError::Disconnected) => break,
+        }
+
+        sequence += 1;
+
+        // Print everything old enough that nothing from another file should come before it.
+        let cutoff = SystemTime::now () - MERGE_WINDOW;
+
+        while pending.peek ().is_some_and (|entry| entry.record.timestamp <= cutoff) {
+
+            let entry = pending.pop ().unwrap ();
+            print_record (&mut out, &entry.record, options)?;
+        }
+
+        out.flush ()?;
+    }
+
+    for entry in pending.into_sorted_vec ().iter ().rev () {
+        print_record (&mut out, &entry.record, options)?;
+    }
+
+    out.flush ()
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+fn main () {
+
+    let options = match Options::parse (env::args ().skip (1)) {
+
+        Ok (options) => options,
+        Err (message) => {
+
+            if !message.is_empty () {
+                eprintln! ("ionlog-tail: {}", message);
+            }
+
+            eprintln! ("{}", USAGE);
+            process::exit (2);
+        }
+    };
+
+    if let Err (e) = run (&options) {
+
+        // Stop quietly when piped into something like `head`.
+        if e.kind () != io::ErrorKind::BrokenPipe {
+
+            eprintln! ("ionlog-tail: {}", e);
+            process::exit (1);
+        }
+    }
+}
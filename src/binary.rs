@@ -0,0 +1,360 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! The compact binary log format.
+//!
+//! A file starts with `MAGIC` and a version byte, followed by frames. Each frame is a tag
+//! byte, a varint payload length and the payload, so readers can skip unknown frames.
+//!
+//! * `FRAME_TARGET` - varint id, then the UTF-8 string it stands for.
+//! * `FRAME_RECORD` - level byte, varint timestamp (microseconds since the Unix epoch),
+//!   varint target id, varint module path id, varint line, then the UTF-8 message.
+//! * `FRAME_TRACE` - sent just before a record frame which has a trace context:
+//!   varint length and UTF-8 trace ID, then the UTF-8 span ID.
+//! * `FRAME_TAGS` - sent just before a record frame which has tags: the varint string id
+//!   of each tag.
+//! * `FRAME_SESSION` - sent once after the header: the UTF-8 session ID of the logger
+//!   that wrote the file.
+//! * `FRAME_APP` - sent after the session frame if the logger has an application name or
+//!   instance ID: varint length and UTF-8 application name, then the UTF-8 instance ID.
+//! * `FRAME_REORDER` - sent before the records of a logger with a flight recorder: varint
+//!   microseconds by which a record after it can follow records logged later than it.
+//! * `FRAME_SEVERITY` - sent just before a record frame logged with `notice!`, `critical!`
+//!   or `fatal!`: a byte, 1 for notice, 2 for critical and 3 for fatal.
+//!
+//! An index file starts with `INDEX_MAGIC` and the version byte instead. It holds the
+//! frames written before the first record of the log, and target frames for the whole string
+//! table, followed by:
+//!
+//! * `FRAME_MINUTE` - one per minute with records: varint timestamp of its first record,
+//!   varint file offset of that record's frames, varint number of strings defined before
+//!   it, then varint record counts from error to trace.
+//! * `FRAME_LENGTH` - varint length of the log file the index covers.
+/*===============================================================================================*/
+
+// Module imports
+use format;
+use {Entry, Severity};
+
+use std::collections::HashMap;
+use std::io;
+use std::io::prelude::Read;
+use std::time::Duration;
+
+pub const MAGIC:       &[u8] = b"IONLOG";
+pub const INDEX_MAGIC: &[u8] = b"IONIDX";
+pub const VERSION:     u8    = 1;
+
+pub const FRAME_TARGET:   u8 = 1;
+pub const FRAME_RECORD:   u8 = 2;
+pub const FRAME_TRACE:    u8 = 3;
+pub const FRAME_SESSION:  u8 = 4;
+pub const FRAME_TAGS:     u8 = 5;
+pub const FRAME_MINUTE:   u8 = 6;
+pub const FRAME_LENGTH:   u8 = 7;
+pub const FRAME_APP:      u8 = 8;
+pub const FRAME_REORDER:  u8 = 9;
+pub const FRAME_SEVERITY: u8 = 10;
+
+/*===============================================================================================*/
+/*------ENCODER STRUCT---------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Encodes entries for one file, interning target and module path strings.
+pub struct Encoder {
+
+    // Private
+    strings: HashMap<String, u64>,
+    written: usize,
+    payload: Vec<u8>,
+}
+
+/*===============================================================================================*/
+/*------ENCODER PUBLIC METHODS-------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Encoder {
+
+    pub fn new () -> Self {
+
+        Encoder {
+
+            strings: HashMap::new (),
+            written: 0,
+            payload: Vec::new (),
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Appends the file header.
+    pub fn header (out: &mut Vec<u8>) {
+
+        out.extend_from_slice (MAGIC);
+        out.push (VERSION);
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Appends the session frame, which applies to every record after it.
+    pub fn session (session_id: &str, out: &mut Vec<u8>) {
+        write_frame (out, FRAME_SESSION, session_id.as_bytes ());
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Appends the application frame, which also applies to every record after it.
+    pub fn app (app_name: &str, instance_id: &str, out: &mut Vec<u8>) {
+
+        let mut payload = Vec::new ();
+
+        write_varint (&mut payload, app_name.len () as u64);
+        payload.extend_from_slice (app_name.as_bytes ());
+        payload.extend_from_slice (instance_id.as_bytes ());
+
+        write_frame (out, FRAME_APP, &payload);
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Appends the reorder frame, for records which can be written up to `window` late.
+    pub fn reorder (window: Duration, out: &mut Vec<u8>) {
+
+        let mut payload = Vec::new ();
+
+        write_varint (&mut payload, window.as_micros () as u64);
+        write_frame (out, FRAME_REORDER, &payload);
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Appends the frames for an entry, defining any strings not seen before.
+    pub fn encode (&mut self, entry: &Entry, out: &mut Vec<u8>) {
+
+        let target = self.intern (entry.target, out);
+        let module_path = self.intern (entry.location.module_path (), out);
+
+        if let Some (trace_context) = entry.trace_context {
+
+            self.payload.clear ();
+            write_varint (&mut self.payload, trace_context.trace_id.len () as u64);
+            self.payload.extend_from_slice (trace_context.trace_id.as_bytes ());
+            self.payload.extend_from_slice (trace_context.span_id.as_bytes ());
+
+            write_frame (out, FRAME_TRACE, &self.payload);
+        }
+
+        if !entry.tags.is_empty () {
+
+            let tags: Vec<_> = entry.tags.iter ().map (|tag| self.intern (tag, out)).collect ();
+
+            self.payload.clear ();
+
+            for tag in tags {
+                write_varint (&mut self.payload, tag);
+            }
+
+            write_frame (out, FRAME_TAGS, &self.payload);
+        }
+
+        if let Some (severity) = format::tagged_severity (entry.tags) {
+
+            let code = match severity {
+
+                Severity::Notice   => 1,
+                Severity::Critical => 2,
+                Severity::Fatal    => 3,
+            };
+
+            write_frame (out, FRAME_SEVERITY, &[code]);
+        }
+
+        self.payload.clear ();
+        self.payload.push (entry.level as u8);
+        write_varint (&mut self.payload, format::timestamp_micros (entry.timestamp));
+        write_varint (&mut self.payload, target);
+        write_varint (&mut self.payload, module_path);
+        write_varint (&mut self.payload, u64::from (entry.location.line ()));
+        self.payload.extend_from_slice (entry.message.as_bytes ());
+
+        write_frame (out, FRAME_RECORD, &self.payload);
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Returns the number of strings defined so far.
+    pub fn string_count (&self) -> usize {
+        self.strings.len ()
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Marks the strings defined so far as written to the file.
+    pub fn commit (&mut self) {
+        self.written = self.strings.len ();
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Forgets the strings defined since the last commit, whose frames were dropped, so they
+    // are defined again when next used.
+    pub fn discard (&mut self) {
+
+        let written = self.written as u64;
+        self.strings.retain (|_, &mut id| id < written);
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Appends target frames defining every string, in id order.
+    pub fn string_table (&self, out: &mut Vec<u8>) {
+
+        let mut strings: Vec<_> = self.strings.iter ().collect ();
+        strings.sort_by_key (|&(_, &id)| id);
+
+        for (string, &id) in strings {
+
+            let mut payload = Vec::new ();
+            write_varint (&mut payload, id);
+            payload.extend_from_slice (string.as_bytes ());
+
+            write_frame (out, FRAME_TARGET, &payload);
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------ENCODER PRIVATE METHODS------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Encoder {
+
+    fn intern (&mut self, string: &str, out: &mut Vec<u8>) -> u64 {
+
+        if let Some (&id) = self.strings.get (string) {
+            return id;
+        }
+
+        let id = self.strings.len () as u64;
+        self.strings.insert (string.to_string (), id);
+
+        self.payload.clear ();
+        write_varint (&mut self.payload, id);
+        self.payload.extend_from_slice (string.as_bytes ());
+
+        write_frame (out, FRAME_TARGET, &self.payload);
+        id
+    }
+}
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Appends an unsigned LEB128 varint.
+pub fn write_varint (out: &mut Vec<u8>, mut value: u64) {
+
+    while value >= 0x80 {
+
+        out.push ((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+
+    out.push (value as u8);
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Reads a varint from a payload, advancing `pos` past it.
+pub fn read_varint (input: &[u8], pos: &mut usize) -> io::Result<u64> {
+
+    let mut value = 0u64;
+    let mut shift = 0;
+
+    loop {
+
+        let byte = match input.get (*pos) {
+
+            Some (&byte) if shift < 64 => byte,
+            _ => return Err (invalid_data ("truncated or oversized varint")),
+        };
+
+        *pos += 1;
+        value |= u64::from (byte & 0x7f) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok (value);
+        }
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Reads the next frame, returning `None` at a clean end of input.
+pub fn read_frame<R: Read> (input: &mut R, payload: &mut Vec<u8>) -> io::Result<Option<u8>> {
+
+    let mut tag = [0u8];
+
+    if input.read (&mut tag)? == 0 {
+        return Ok (None);
+    }
+
+    let mut length = 0u64;
+    let mut shift = 0;
+
+    loop {
+
+        let mut byte = [0u8];
+        input.read_exact (&mut byte)?;
+
+        if shift >= 64 {
+            return Err (invalid_data ("oversized frame length"));
+        }
+
+        length |= u64::from (byte[0] & 0x7f) << shift;
+        shift += 7;
+
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+
+    payload.clear ();
+    input.by_ref ().take (length).read_to_end (payload)?;
+
+    if payload.len () as u64 != length {
+        return Err (io::Error::new (io::ErrorKind::UnexpectedEof, "truncated frame"));
+    }
+
+    Ok (Some (tag[0]))
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+pub fn invalid_data (message: &str) -> io::Error {
+    io::Error::new (io::ErrorKind::InvalidData, message.to_string ())
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+pub fn write_frame (out: &mut Vec<u8>, tag: u8, payload: &[u8]) {
+
+    out.push (tag);
+    write_varint (out, payload.len () as u64);
+    out.extend_from_slice (payload);
+}
@@ -0,0 +1,279 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! The CBOR log format, and a decoder for reading it back.
+/*===============================================================================================*/
+
+// Module imports
+#[cfg (feature = "binary")]
+use binary::invalid_data;
+use format::{self, JsonRecord};
+#[cfg (feature = "binary")]
+use json::Value;
+use JSON_SCHEMA;
+
+#[cfg (feature = "binary")]
+use std::io;
+#[cfg (feature = "binary")]
+use std::io::prelude::Read;
+
+// The major types used by the log format.
+const UINT:  u8 = 0;
+const TEXT:  u8 = 3;
+const ARRAY: u8 = 4;
+const MAP:   u8 = 5;
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Appends a record as a map with the fields of the JSON layout. Records follow each other
+// with nothing between them, making the file a CBOR sequence as described by RFC 8742. The
+// timestamp is microseconds since the Unix epoch rather than a string.
+pub fn record<T: AsRef<str>> (out: &mut Vec<u8>, record: &JsonRecord<T>) {
+
+    let optional = [("trace_id",    record.trace_id),
+                    ("span_id",     record.span_id),
+                    ("session_id",  record.session_id),
+                    ("app_name",    record.app_name),
+                    ("instance_id", record.instance_id)];
+
+    let fields = 8 + optional.iter ().filter (|(_, value)| value.is_some ()).count () +
+                 if record.tags.is_empty () {0} else {1};
+
+    head (out, MAP, fields as u64);
+    text (out, "schema");
+    head (out, UINT, u64::from (JSON_SCHEMA));
+    text (out, "timestamp");
+    head (out, UINT, format::timestamp_micros (record.timestamp));
+    text (out, "level");
+    text (out, &record.level.to_string ());
+    text (out, "target");
+    text (out, record.target);
+    text (out, "module_path");
+    text (out, record.module_path);
+    text (out, "line");
+    head (out, UINT, u64::from (record.line));
+    text (out, "message");
+    text (out, record.message);
+
+    for (name, value) in optional.iter () {
+
+        if let Some (value) = *value {
+
+            text (out, name);
+            text (out, value);
+        }
+    }
+
+    text (out, "severity");
+    text (out, format::severity_name (record.level, record.severity));
+
+    if !record.tags.is_empty () {
+
+        text (out, "tags");
+        head (out, ARRAY, record.tags.len () as u64);
+
+        for tag in record.tags {
+            text (out, tag.as_ref ());
+        }
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Reads the next item of a CBOR sequence, returning `None` at a clean end of input.
+// Byte strings and indefinite lengths aren't used by the log format, so aren't accepted.
+// Tags are skipped, leaving the value they tag.
+#[cfg (feature = "binary")]
+pub fn decode<R: Read> (input: &mut R) -> io::Result<Option<Value>> {
+
+    let mut initial = [0u8];
+
+    if input.read (&mut initial)? == 0 {
+        return Ok (None);
+    }
+
+    value (input, initial[0]).map (Some)
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Writes the head of an item: its major type and argument, in the fewest bytes.
+fn head (out: &mut Vec<u8>, major: u8, argument: u64) {
+
+    let major = major << 5;
+
+    match argument {
+
+        0 ..= 23 => out.push (major | argument as u8),
+        24 ..= 0xff => out.extend_from_slice (&[major | 24, argument as u8]),
+        0x100 ..= 0xffff => {
+
+            out.push (major | 25);
+            out.extend_from_slice (&(argument as u16).to_be_bytes ());
+        },
+
+        0x1_0000 ..= 0xffff_ffff => {
+
+            out.push (major | 26);
+            out.extend_from_slice (&(argument as u32).to_be_bytes ());
+        },
+
+        _ => {
+
+            out.push (major | 27);
+            out.extend_from_slice (&argument.to_be_bytes ());
+        },
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+fn text (out: &mut Vec<u8>, value: &str) {
+
+    head (out, TEXT, value.len () as u64);
+    out.extend_from_slice (value.as_bytes ());
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Reads the item starting with the initial byte.
+#[cfg (feature = "binary")]
+fn value<R: Read> (input: &mut R, initial: u8) -> io::Result<Value> {
+
+    let major = initial >> 5;
+    let info = initial & 0x1f;
+
+    // Floats and simple values keep their meaning in the additional info.
+    if major == 7 {
+
+        return match info {
+
+            20 => Ok (Value::Bool (false)),
+            21 => Ok (Value::Bool (true)),
+            22 | 23 => Ok (Value::Null),
+            25 => Ok (Value::Number (half_float (uint (input, 2)? as u16))),
+            26 => Ok (Value::Number (f64::from (f32::from_bits (uint (input, 4)? as u32)))),
+            27 => Ok (Value::Number (f64::from_bits (uint (input, 8)?))),
+            _ => Err (invalid_data ("unsupported CBOR simple value")),
+        };
+    }
+
+    let argument = match info {
+
+        0 ..= 23 => u64::from (info),
+        24 ..= 27 => uint (input, 1 << (info - 24))?,
+        _ => return Err (invalid_data ("unsupported CBOR length")),
+    };
+
+    match major {
+
+        0 => Ok (Value::Number (argument as f64)),
+        1 => Ok (Value::Number (-1.0 - argument as f64)),
+
+        3 => {
+
+            let mut bytes = Vec::new ();
+            input.by_ref ().take (argument).read_to_end (&mut bytes)?;
+
+            if bytes.len () as u64 != argument {
+                return Err (io::Error::new (io::ErrorKind::UnexpectedEof, "truncated CBOR string"));
+            }
+
+            String::from_utf8 (bytes).map (Value::String).map_err (|_| invalid_data ("invalid CBOR string"))
+        },
+
+        // The lengths come from the input, so the vectors grow as items are read rather than
+        // being allocated up front.
+        4 => {
+
+            let mut items = Vec::new ();
+
+            for _ in 0..argument {
+                items.push (next (input)?);
+            }
+
+            Ok (Value::Array (items))
+        },
+
+        5 => {
+
+            let mut members = Vec::new ();
+
+            for _ in 0..argument {
+
+                let name = match next (input)? {
+
+                    Value::String (name) => name,
+                    _ => return Err (invalid_data ("CBOR map key isn't a string")),
+                };
+
+                members.push ((name, next (input)?));
+            }
+
+            Ok (Value::Object (members))
+        },
+
+        6 => next (input),
+        _ => Err (invalid_data ("unsupported CBOR byte string")),
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Reads an item that must be there, such as an array element.
+#[cfg (feature = "binary")]
+fn next<R: Read> (input: &mut R) -> io::Result<Value> {
+
+    let mut initial = [0u8];
+
+    input.read_exact (&mut initial)?;
+    value (input, initial[0])
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Reads a big endian unsigned integer of the given number of bytes.
+#[cfg (feature = "binary")]
+fn uint<R: Read> (input: &mut R, size: usize) -> io::Result<u64> {
+
+    let mut bytes = [0u8; 8];
+
+    input.read_exact (&mut bytes[8 - size..])?;
+    Ok (u64::from_be_bytes (bytes))
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+#[cfg (feature = "binary")]
+fn half_float (bits: u16) -> f64 {
+
+    let sign = if bits & 0x8000 != 0 {-1.0} else {1.0};
+    let exponent = i32::from ((bits >> 10) & 0x1f);
+    let mantissa = f64::from (bits & 0x3ff);
+
+    sign * match exponent {
+
+        0 => mantissa * 2f64.powi (-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (mantissa + 1024.0) * 2f64.powi (exponent - 25),
+    }
+}
@@ -0,0 +1,191 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Command line flags for configuring the logger, for use with `clap`.
+//!
+//! Requires the `clap` feature.
+//!
+//! # Example
+//! ```
+//! # extern crate clap;
+//! # extern crate ion_log;
+//! use clap::{Args, Command, FromArgMatches};
+//! use ion_log::clap::LogArgs;
+//!
+//! # fn main () {
+//! let matches = LogArgs::augment_args (Command::new ("game"))
+//!                   .get_matches_from (vec! ["game", "--log-level", "debug", "--no-color"]);
+//!
+//! let config = LogArgs::from_arg_matches (&matches).unwrap ().to_config ();
+//! assert_eq! (config.max_log_level, ion_log::LogLevel::Debug);
+//! assert! (!config.coloured_output);
+//! # }
+//! ```
+/*===============================================================================================*/
+
+// Module imports
+use clap_crate::{Arg, ArgAction, ArgMatches, Args, Command, Error, FromArgMatches};
+use clap_crate::builder::PossibleValuesParser;
+use {LogConfig, LogFormat, LogLevel};
+
+/*===============================================================================================*/
+/*------LOG ARGS STRUCT--------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// The `--log-level`, `--log-file`, `--no-color` and `--log-format` flags.
+///
+/// Add them to a command with `Args::augment_args`, or with `#[command(flatten)]`
+/// in a derived parser.
+#[derive (Clone, Debug, Default, PartialEq, Eq)]
+pub struct LogArgs {
+
+    // Public
+    /// The maximum log level, from `--log-level`.
+    pub log_level:  Option<LogLevel>,
+    /// The log output file path, from `--log-file`.
+    pub log_file:   Option<String>,
+    /// Whether `--no-color` was given.
+    pub no_color:   bool,
+    /// The log file format, from `--log-format`.
+    pub log_format: Option<LogFormat>,
+}
+
+/*===============================================================================================*/
+/*------LOG ARGS TRAIT IMPLEMENTATIONS-----------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Args for LogArgs {
+
+    fn augment_args (cmd: Command) -> Command {
+
+        cmd.arg (Arg::new ("log-level")
+                     .long ("log-level")
+                     .value_name ("LEVEL")
+                     .value_parser (PossibleValuesParser::new (["off", "error", "warn", "info", "debug", "trace"]))
+                     .help ("Sets the maximum log level"))
+           .arg (Arg::new ("log-file")
+                     .long ("log-file")
+                     .value_name ("PATH")
+                     .help ("Writes the log to a file"))
+           .arg (Arg::new ("no-color")
+                     .long ("no-color")
+                     .action (ArgAction::SetTrue)
+                     .help ("Disables coloured terminal output"))
+           .arg (Arg::new ("log-format")
+                     .long ("log-format")
+                     .value_name ("FORMAT")
+                     .value_parser (PossibleValuesParser::new (["text", "json", "binary", "msgpack", "cbor"]))
+                     .help ("Sets the log file format"))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn augment_args_for_update (cmd: Command) -> Command {
+        LogArgs::augment_args (cmd)
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl FromArgMatches for LogArgs {
+
+    fn from_arg_matches (matches: &ArgMatches) -> Result<Self, Error> {
+
+        let mut args = LogArgs::default ();
+        args.update_from_arg_matches (matches)?;
+        Ok (args)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn update_from_arg_matches (&mut self, matches: &ArgMatches) -> Result<(), Error> {
+
+        // The value parsers only accept valid names, so these conversions can't fail.
+        if let Some (level) = matches.get_one::<String> ("log-level") {
+            self.log_level = level.parse ().ok ();
+        }
+
+        if let Some (path) = matches.get_one::<String> ("log-file") {
+            self.log_file = Some (path.clone ());
+        }
+
+        if matches.get_flag ("no-color") {
+            self.no_color = true;
+        }
+
+        if let Some (format) = matches.get_one::<String> ("log-format") {
+            self.log_format = Some (match format.as_str () {
+
+                "json"    => LogFormat::Json,
+                "binary"  => LogFormat::Binary,
+                "msgpack" => LogFormat::MessagePack,
+                "cbor"    => LogFormat::Cbor,
+                _         => LogFormat::Text,
+            });
+        }
+
+        Ok (())
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl From<LogArgs> for LogConfig {
+
+    fn from (args: LogArgs) -> Self {
+        args.to_config ()
+    }
+}
+
+/*===============================================================================================*/
+/*------LOG ARGS PUBLIC METHODS------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl LogArgs {
+
+    /// Returns a default `LogConfig` with the flags applied.
+    pub fn to_config (&self) -> LogConfig {
+
+        let mut config = LogConfig::new ();
+        self.apply (&mut config);
+        config
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Applies the flags that were given to an existing config.
+    pub fn apply (&self, config: &mut LogConfig) {
+
+        if let Some (level) = self.log_level {
+            config.max_log_level = level;
+        }
+
+        if let Some (ref path) = self.log_file {
+
+            config.log_to_file     = true;
+            config.log_output_path = path.clone ();
+        }
+
+        if self.no_color {
+            config.coloured_output = false;
+        }
+
+        if let Some (format) = self.log_format {
+            config.log_format = format;
+        }
+    }
+}
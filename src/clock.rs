@@ -0,0 +1,136 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Clocks that timestamp records.
+/*===============================================================================================*/
+
+// Module imports
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/*===============================================================================================*/
+/*------CLOCK TRAIT------------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// A source of the current time, set with `LogConfig::clock`.
+///
+/// The logger asks it for record timestamps, the session banner uptime, audit record times,
+/// when a sink went down and when daily log rotation is next due.
+pub trait Clock: fmt::Debug + Send + Sync {
+
+    /// Returns the current time.
+    fn now (&self) -> SystemTime;
+}
+
+/*===============================================================================================*/
+/*------SYSTEM CLOCK STRUCT----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// The system clock, used by default.
+#[derive (Copy, Clone, Debug, Default)]
+pub struct SystemClock;
+
+/*===============================================================================================*/
+/*------MOCK CLOCK STRUCT------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// A clock which only moves when told to, so tests get the same timestamps on every run.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate log;
+/// # extern crate ion_log;
+/// # use ion_log::*;
+/// # use ion_log::test::ChannelSink;
+/// # use std::sync::Arc;
+/// # use std::time::{Duration, UNIX_EPOCH};
+/// # fn main () {
+/// let clock = Arc::new (MockClock::new (UNIX_EPOCH + Duration::from_secs (1_470_054_600)));
+/// let sink = ChannelSink::new ();
+/// let records = sink.subscribe ();
+///
+/// let mut config = LogConfig::new ();
+/// config.log_to_io    = false;
+/// config.channel_sink = Some (sink);
+/// config.clock        = clock.clone ();
+///
+/// ion_log::init (&config).unwrap ();
+/// info! ("First frame");
+/// clock.advance (Duration::from_millis (16));
+/// info! ("Second frame");
+/// ion_log::release ();
+///
+/// let records: Vec<_> = records.try_iter ().collect ();
+/// assert_eq! (records[1].timestamp.duration_since (records[0].timestamp).unwrap (),
+///             Duration::from_millis (16));
+/// # }
+/// ```
+#[derive (Debug)]
+pub struct MockClock {
+
+    // Private
+    time: Mutex<SystemTime>,
+}
+
+/*===============================================================================================*/
+/*------SYSTEM CLOCK TRAIT IMPLEMENTATIONS-------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Clock for SystemClock {
+
+    fn now (&self) -> SystemTime {
+        SystemTime::now ()
+    }
+}
+
+/*===============================================================================================*/
+/*------MOCK CLOCK TRAIT IMPLEMENTATIONS---------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Clock for MockClock {
+
+    fn now (&self) -> SystemTime {
+        *self.time.lock ().unwrap ()
+    }
+}
+
+/*===============================================================================================*/
+/*------MOCK CLOCK PUBLIC METHODS----------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl MockClock {
+
+    /// Returns a clock stopped at the given time.
+    pub fn new (time: SystemTime) -> Self {
+        MockClock {time: Mutex::new (time)}
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Moves the clock to the given time, which may be earlier than the current one.
+    pub fn set (&self, time: SystemTime) {
+        *self.time.lock ().unwrap () = time;
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Moves the clock forward.
+    pub fn advance (&self, duration: Duration) {
+        *self.time.lock ().unwrap () += duration;
+    }
+}
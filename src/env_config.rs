@@ -0,0 +1,216 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Overrides for a `LogConfig` from `ION_LOG_*` environment variables.
+/*===============================================================================================*/
+
+// Module imports
+use filter;
+use {FileEncoding, LocationStyle, LogConfig, LogFormat};
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/*===============================================================================================*/
+/*------PARSE ENV ERROR STRUCT-------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// The error returned when an `ION_LOG*` environment variable has an invalid value.
+#[derive (Clone, Debug, PartialEq, Eq)]
+pub struct ParseEnvError {
+
+    // Private
+    variable: String,
+    value: String,
+}
+
+/*===============================================================================================*/
+/*------PARSE ENV ERROR TRAIT IMPLEMENTATIONS----------------------------------------------------*/
+/*===============================================================================================*/
+
+impl fmt::Display for ParseEnvError {
+
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write! (f, "invalid value `{}` for {}", self.value, self.variable)
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl Error for ParseEnvError {}
+
+/*===============================================================================================*/
+/*------PARSE ENV ERROR PUBLIC METHODS-----------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl ParseEnvError {
+
+    /// Returns the name of the variable with the invalid value.
+    pub fn variable (&self) -> &str {
+        &self.variable
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Returns the invalid value.
+    pub fn value (&self) -> &str {
+        &self.value
+    }
+}
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Applies every `ION_LOG*` variable that is set, in the order `LogConfig::apply_env` lists them.
+pub fn apply (config: &mut LogConfig) -> Result<(), ParseEnvError> {
+
+    if let Some (directives) = var ("ION_LOG") {
+
+        let (default_level, mut filters) = filter::parse (&directives).map_err (|_| error ("ION_LOG", &directives))?;
+
+        if let Some (level) = default_level {
+            config.max_log_level = level;
+        }
+
+        config.module_filters.append (&mut filters);
+    }
+
+    if let Some (level) = var ("ION_LOG_LEVEL") {
+        config.max_log_level = parse ("ION_LOG_LEVEL", &level)?;
+    }
+
+    if let Some (path) = var ("ION_LOG_FILE") {
+
+        config.log_to_file = !path.is_empty ();
+        config.log_output_path = path;
+    }
+
+    if let Some (format) = var ("ION_LOG_FORMAT") {
+
+        config.log_format = match format.to_lowercase ().as_str () {
+
+            "text"    => LogFormat::Text,
+            "json"    => LogFormat::Json,
+            "binary"  => LogFormat::Binary,
+            "msgpack" => LogFormat::MessagePack,
+            "cbor"    => LogFormat::Cbor,
+            _         => return Err (error ("ION_LOG_FORMAT", &format)),
+        };
+    }
+
+    if let Some (encoding) = var ("ION_LOG_ENCODING") {
+
+        config.file_encoding = match encoding.to_lowercase ().as_str () {
+
+            "utf-8"     => FileEncoding::Utf8,
+            "utf-8-bom" => FileEncoding::Utf8Bom,
+            "utf-16le"  => FileEncoding::Utf16Le,
+            _           => return Err (error ("ION_LOG_ENCODING", &encoding)),
+        };
+    }
+
+    if let Some (index) = var ("ION_LOG_INDEX") {
+        config.log_file_index = flag ("ION_LOG_INDEX", &index)?;
+    }
+
+    if let Some (terminal) = var ("ION_LOG_TERMINAL") {
+        config.log_to_io = flag ("ION_LOG_TERMINAL", &terminal)?;
+    }
+
+    if let Some (format) = var ("ION_LOG_TERMINAL_FORMAT") {
+
+        config.terminal_json = match format.to_lowercase ().as_str () {
+
+            "text" => false,
+            "json" => true,
+            _      => return Err (error ("ION_LOG_TERMINAL_FORMAT", &format)),
+        };
+    }
+
+    if let Some (colour) = var ("ION_LOG_COLOR").or_else (|| var ("ION_LOG_COLOUR")) {
+        config.coloured_output = flag ("ION_LOG_COLOR", &colour)?;
+    }
+
+    if let Some (style) = var ("ION_LOG_LOCATION") {
+
+        config.location_style = match style.to_lowercase ().as_str () {
+
+            "module" => LocationStyle::Module,
+            "file"   => LocationStyle::File,
+            "hidden" => LocationStyle::Hidden,
+            _        => return Err (error ("ION_LOG_LOCATION", &style)),
+        };
+    }
+
+    if let Some (async_output) = var ("ION_LOG_ASYNC") {
+        config.async_output = flag ("ION_LOG_ASYNC", &async_output)?;
+    }
+
+    if let Some (capacity) = var ("ION_LOG_ASYNC_CAPACITY") {
+        config.async_capacity = parse ("ION_LOG_ASYNC_CAPACITY", &capacity)?;
+    }
+
+    if let Some (path) = var ("ION_LOG_AUDIT_FILE") {
+        config.audit_log_path = path;
+    }
+
+    if let Some (app_name) = var ("ION_LOG_APP_NAME") {
+        config.app_name = app_name;
+    }
+
+    if let Some (instance_id) = var ("ION_LOG_INSTANCE_ID") {
+        config.instance_id = instance_id;
+    }
+
+    Ok (())
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+fn var (name: &str) -> Option<String> {
+    env::var (name).ok ()
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+fn error (variable: &str, value: &str) -> ParseEnvError {
+    ParseEnvError {variable: variable.to_string (), value: value.to_string ()}
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+fn parse<T: FromStr> (variable: &str, value: &str) -> Result<T, ParseEnvError> {
+    value.trim ().parse ().map_err (|_| error (variable, value))
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Accepts the usual spellings of on and off, in any case.
+fn flag (variable: &str, value: &str) -> Result<bool, ParseEnvError> {
+
+    match value.trim ().to_lowercase ().as_str () {
+
+        "1" | "true" | "yes" | "on" | "always" => Ok (true),
+        "0" | "false" | "no" | "off" | "never" => Ok (false),
+        _ => Err (error (variable, value)),
+    }
+}
@@ -0,0 +1,92 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Detection of the environment a binary is running in.
+/*===============================================================================================*/
+
+// Module imports
+use std::env;
+use std::io;
+use std::io::IsTerminal;
+
+/*===============================================================================================*/
+/*------ENVIRONMENT ENUM-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// An environment with its own conventions for log output, used by `LogConfig::for_environment`.
+#[derive (Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Environment {
+
+    /// A Kubernetes pod, where a collector reads JSON from stdout.
+    Kubernetes,
+    /// A systemd service, whose stdout goes to the journal.
+    Systemd,
+    /// A continuous integration job.
+    Ci,
+    /// An interactive terminal.
+    Terminal,
+    /// Anything else, such as output piped to another program.
+    Other,
+}
+
+/*===============================================================================================*/
+/*------ENVIRONMENT PUBLIC METHODS---------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Environment {
+
+    /// Returns the environment the process is running in.
+    ///
+    /// These are checked in order:
+    ///
+    /// * `KUBERNETES_SERVICE_HOST` is set for Kubernetes.
+    /// * `JOURNAL_STREAM` or `INVOCATION_ID` is set for systemd.
+    /// * `CI` is set, and isn't `false` or `0`, for continuous integration.
+    /// * Stdout is a terminal for an interactive terminal.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// if Environment::detect () == Environment::Kubernetes {
+    ///     println! ("Running in a pod");
+    /// }
+    /// ```
+    pub fn detect () -> Self {
+
+        let is_set = |name| env::var_os (name).is_some_and (|value| !value.is_empty ());
+
+        if is_set ("KUBERNETES_SERVICE_HOST") {
+            Environment::Kubernetes
+        }
+
+        else if is_set ("JOURNAL_STREAM") || is_set ("INVOCATION_ID") {
+            Environment::Systemd
+        }
+
+        else if env::var ("CI").is_ok_and (|value| !value.is_empty () && value != "false" && value != "0") {
+            Environment::Ci
+        }
+
+        else if io::stdout ().is_terminal () {
+            Environment::Terminal
+        }
+
+        else {
+            Environment::Other
+        }
+    }
+}
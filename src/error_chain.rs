@@ -0,0 +1,252 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Formatting for errors and the chain of errors that caused them.
+/*===============================================================================================*/
+
+// Module imports
+#[cfg (feature = "anyhow")]
+use anyhow;
+#[cfg (feature = "eyre")]
+use eyre;
+use format;
+
+#[cfg (any (feature = "anyhow", feature = "eyre"))]
+use std::backtrace::Backtrace;
+#[cfg (feature = "anyhow")]
+use std::backtrace::BacktraceStatus;
+use std::error::Error;
+use std::fmt;
+
+/*===============================================================================================*/
+/*------ERROR CHAIN STRUCT-----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Formats an error followed by each of its sources, so wrapped errors stay readable.
+///
+/// Each cause is written on its own indented line.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate log;
+/// # extern crate ion_log;
+/// # use ion_log::ErrorChain;
+/// # use std::{error, fmt, io};
+/// #[derive (Debug)]
+/// struct LoadError (io::Error);
+///
+/// impl fmt::Display for LoadError {
+///     fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+///         write! (f, "failed to load the level")
+///     }
+/// }
+///
+/// impl error::Error for LoadError {
+///     fn source (&self) -> Option<&(dyn error::Error + 'static)> {
+///         Some (&self.0)
+///     }
+/// }
+///
+/// # fn main () {
+/// let err = LoadError (io::Error::new (io::ErrorKind::NotFound, "textures/stone.png not found"));
+///
+/// assert_eq! (ErrorChain (&err).to_string (),
+///             "failed to load the level\n  caused by: textures/stone.png not found");
+/// assert_eq! (ErrorChain (&err).to_json (),
+///             r#"["failed to load the level","textures/stone.png not found"]"#);
+///
+/// error! ("{}", ErrorChain (&err));
+/// # }
+/// ```
+#[derive (Copy, Clone, Debug)]
+pub struct ErrorChain<'a> (pub &'a (dyn Error + 'a));
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Formats an `anyhow::Error` or `eyre::Report` with its context chain and backtrace.
+///
+/// Requires the `anyhow` or `eyre` feature. The chain is written like `ErrorChain`, followed
+/// by the backtrace if one was captured. `eyre` backtraces belong to the installed handler,
+/// so only the chain is available for them.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate log;
+/// # extern crate anyhow;
+/// # extern crate ion_log;
+/// # use anyhow::Context;
+/// # use ion_log::ErrorReport;
+/// # fn main () {
+/// let err = std::fs::read ("missing.toml").context ("failed to read the settings").unwrap_err ();
+///
+/// error! ("{}", ErrorReport::from (&err));
+/// # }
+/// ```
+#[cfg (any (feature = "anyhow", feature = "eyre"))]
+#[derive (Copy, Clone, Debug)]
+pub struct ErrorReport<'a> {
+
+    // Private
+    chain: ErrorChain<'a>,
+    backtrace: Option<&'a Backtrace>,
+}
+
+/*===============================================================================================*/
+/*------ERROR CHAIN TRAIT IMPLEMENTATIONS--------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl<'a> fmt::Display for ErrorChain<'a> {
+
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        write! (f, "{}", self.0)?;
+
+        for cause in self.causes () {
+            write! (f, "\n  caused by: {}", cause)?;
+        }
+
+        Ok (())
+    }
+}
+
+/*===============================================================================================*/
+/*------ERROR REPORT TRAIT IMPLEMENTATIONS-------------------------------------------------------*/
+/*===============================================================================================*/
+
+#[cfg (any (feature = "anyhow", feature = "eyre"))]
+impl<'a> fmt::Display for ErrorReport<'a> {
+
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        write! (f, "{}", self.chain)?;
+
+        if let Some (backtrace) = self.backtrace {
+
+            f.write_str ("\n  backtrace:")?;
+
+            for line in backtrace.to_string ().lines () {
+                write! (f, "\n    {}", line)?;
+            }
+        }
+
+        Ok (())
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+#[cfg (feature = "anyhow")]
+impl<'a> From<&'a anyhow::Error> for ErrorReport<'a> {
+
+    fn from (err: &'a anyhow::Error) -> Self {
+
+        let backtrace = err.backtrace ();
+
+        ErrorReport {
+
+            chain: ErrorChain (err.as_ref ()),
+            backtrace: if backtrace.status () == BacktraceStatus::Captured {Some (backtrace)} else {None},
+        }
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+#[cfg (feature = "eyre")]
+impl<'a> From<&'a eyre::Report> for ErrorReport<'a> {
+
+    fn from (err: &'a eyre::Report) -> Self {
+
+        ErrorReport {
+
+            chain: ErrorChain (err.as_ref ()),
+            backtrace: None,
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------ERROR REPORT PUBLIC METHODS--------------------------------------------------------------*/
+/*===============================================================================================*/
+
+#[cfg (any (feature = "anyhow", feature = "eyre"))]
+impl<'a> ErrorReport<'a> {
+
+    /// Returns the context chain, without the backtrace.
+    pub fn chain (&self) -> ErrorChain<'a> {
+        self.chain
+    }
+}
+
+/*===============================================================================================*/
+/*------ERROR CHAIN PUBLIC METHODS---------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl<'a> ErrorChain<'a> {
+
+    /// Returns the error and its sources as a JSON array of messages, outermost first.
+    pub fn to_json (&self) -> String {
+
+        let mut json = String::from ("[");
+        format::json_string (&mut json, &self.0.to_string ());
+
+        for cause in self.causes () {
+
+            json.push (',');
+            format::json_string (&mut json, &cause.to_string ());
+        }
+
+        json.push (']');
+        json
+    }
+}
+
+/*===============================================================================================*/
+/*------ERROR CHAIN PRIVATE METHODS--------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl<'a> ErrorChain<'a> {
+
+    fn causes (&self) -> Causes<'a> {
+        Causes {next: self.0.source ()}
+    }
+}
+
+/*===============================================================================================*/
+/*------CAUSES STRUCT----------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Iterates over the sources of an error.
+struct Causes<'a> {
+
+    // Private
+    next: Option<&'a (dyn Error + 'static)>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl<'a> Iterator for Causes<'a> {
+
+    type Item = &'a (dyn Error + 'static);
+
+    fn next (&mut self) -> Option<Self::Item> {
+
+        let current = self.next?;
+        self.next = current.source ();
+        Some (current)
+    }
+}
@@ -0,0 +1,624 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! A small expression language for filtering records.
+//!
+//! ```text
+//! level >= warn || (target ~ "payment" && message ~ /timeout/)
+//! ```
+//!
+//! * `level` compares by severity against `error`, `warn`, `info`, `debug` or `trace`
+//!   with `==`, `!=`, `<`, `<=`, `>` and `>=`, so `level >= warn` keeps warnings and errors.
+//! * `target`, `module` and `message` compare with `==` and `!=` against a string, or
+//!   with `~` against a string (substring match) or a `/regex/`. Regexes need the `regex`
+//!   feature, which is on by default.
+//! * `tag` compares the same way against each of the record's tags. `tag == "net"` and
+//!   `tag ~ ...` hold if any tag matches, and `tag != "net"` if none is `net`.
+//! * Conditions combine with `&&`, `||`, `!` and parentheses.
+/*===============================================================================================*/
+
+// Module imports
+use log;
+#[cfg (feature = "regex")]
+use regex::Regex;
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/*===============================================================================================*/
+/*------FILTER EXPRESSION STRUCT-----------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// A parsed filter expression, which records must match to be logged.
+#[derive (Clone, Debug)]
+pub struct FilterExpression {
+
+    // Private
+    source: String,
+    root: Node,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// The error returned when a filter expression can't be parsed.
+#[derive (Clone, Debug, PartialEq, Eq)]
+pub struct ParseExpressionError {
+
+    // Private
+    position: usize,
+    message: String,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+#[derive (Clone, Debug)]
+enum Node {
+
+    Or    (Box<Node>, Box<Node>),
+    And   (Box<Node>, Box<Node>),
+    Not   (Box<Node>),
+    Level (Comparison, log::LogLevel),
+    Text  (Field, TextMatch),
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+#[derive (Copy, Clone, Debug, PartialEq, Eq)]
+enum Comparison {
+
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Matches,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+#[derive (Copy, Clone, Debug)]
+enum Field {
+
+    Target,
+    Module,
+    Message,
+    Tag,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+#[derive (Clone, Debug)]
+enum TextMatch {
+
+    Equal    (String),
+    NotEqual (String),
+    Contains (String),
+    #[cfg (feature = "regex")]
+    Regex    (Regex),
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// The parts of a record an expression can look at.
+struct Fields<'a> {
+
+    // Private
+    level: log::LogLevel,
+    target: &'a str,
+    module_path: &'a str,
+    message: &'a str,
+    tags: &'a [&'a str],
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+#[derive (Clone, Debug, PartialEq)]
+enum Token {
+
+    Word       (String),
+    String     (String),
+    Regex      (String),
+    Comparison (Comparison),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+struct Parser {
+
+    // Private
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+}
+
+/*===============================================================================================*/
+/*------FILTER EXPRESSION TRAIT IMPLEMENTATIONS--------------------------------------------------*/
+/*===============================================================================================*/
+
+impl FromStr for FilterExpression {
+
+    type Err = ParseExpressionError;
+
+    fn from_str (source: &str) -> Result<Self, Self::Err> {
+        FilterExpression::parse (source)
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl fmt::Display for FilterExpression {
+
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str (&self.source)
+    }
+}
+
+/*===============================================================================================*/
+/*------FILTER EXPRESSION PUBLIC METHODS---------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl FilterExpression {
+
+    /// Parses a filter expression.
+    ///
+    /// # Errors
+    /// Fails if the expression has a syntax error, an unknown field or level, or an invalid regex.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::FilterExpression;
+    /// let filter = FilterExpression::parse (r#"level >= warn || (target ~ "payment" && message ~ "timeout")"#).unwrap ();
+    ///
+    /// assert! ( filter.matches (log::LogLevel::Error, "my_app::db", "db::pool", "connection lost"));
+    /// assert! ( filter.matches (log::LogLevel::Info, "my_app::payment", "my_app::payment", "request timeout"));
+    /// assert! (!filter.matches (log::LogLevel::Info, "my_app::db", "my_app::db", "request timeout"));
+    /// ```
+    ///
+    /// With the `regex` feature, `~` also matches against a `/regex/`:
+    /// ```
+    /// # use ion_log::FilterExpression;
+    /// # #[cfg (feature = "regex")] {
+    /// let filter = FilterExpression::parse (r#"message ~ /timed? ?out/"#).unwrap ();
+    ///
+    /// assert! ( filter.matches (log::LogLevel::Info, "my_app", "my_app", "request timed out"));
+    /// assert! (!filter.matches (log::LogLevel::Info, "my_app", "my_app", "request failed"));
+    /// # }
+    /// ```
+    pub fn parse (source: &str) -> Result<Self, ParseExpressionError> {
+
+        let tokens = tokenize (source)?;
+        let mut parser = Parser {tokens, pos: 0, end: source.len ()};
+        let root = parser.parse_or ()?;
+
+        if let Some (&(position, _)) = parser.tokens.get (parser.pos) {
+            return Err (ParseExpressionError::new (position, "unexpected input after expression"));
+        }
+
+        Ok (FilterExpression {source: source.to_string (), root})
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Returns whether a record with the given fields, and no tags, matches the expression.
+    pub fn matches (&self, level: log::LogLevel, target: &str, module_path: &str, message: &str) -> bool {
+        self.matches_tagged (level, target, module_path, message, &[])
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Returns whether a record with the given fields and tags matches the expression.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::FilterExpression;
+    /// let filter = FilterExpression::parse (r#"tag == "net" && tag != "noisy""#).unwrap ();
+    ///
+    /// assert! ( filter.matches_tagged (log::LogLevel::Info, "game", "game", "packet", &["net"]));
+    /// assert! (!filter.matches_tagged (log::LogLevel::Info, "game", "game", "packet", &["net", "noisy"]));
+    /// assert! (!filter.matches (log::LogLevel::Info, "game", "game", "packet"));
+    /// ```
+    pub fn matches_tagged (&self, level: log::LogLevel, target: &str, module_path: &str, message: &str, tags: &[&str]) -> bool {
+        self.root.evaluate (&Fields {level, target, module_path, message, tags})
+    }
+
+}
+
+/*===============================================================================================*/
+/*------PARSE EXPRESSION ERROR TRAIT IMPLEMENTATIONS---------------------------------------------*/
+/*===============================================================================================*/
+
+impl fmt::Display for ParseExpressionError {
+
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write! (f, "{} at position {}", self.message, self.position)
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl Error for ParseExpressionError {}
+
+/*===============================================================================================*/
+/*------PARSE EXPRESSION ERROR PUBLIC METHODS----------------------------------------------------*/
+/*===============================================================================================*/
+
+impl ParseExpressionError {
+
+    /// Returns the byte offset in the expression where the error was found.
+    pub fn position (&self) -> usize {
+        self.position
+    }
+}
+
+/*===============================================================================================*/
+/*------PARSE EXPRESSION ERROR PRIVATE METHODS---------------------------------------------------*/
+/*===============================================================================================*/
+
+impl ParseExpressionError {
+
+    fn new (position: usize, message: &str) -> Self {
+
+        ParseExpressionError {
+
+            position,
+            message: message.to_string (),
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------NODE PRIVATE METHODS---------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Node {
+
+    fn evaluate (&self, fields: &Fields) -> bool {
+
+        let level = fields.level;
+
+        match *self {
+
+            Node::Or  (ref a, ref b) => a.evaluate (fields) || b.evaluate (fields),
+            Node::And (ref a, ref b) => a.evaluate (fields) && b.evaluate (fields),
+            Node::Not (ref a) => !a.evaluate (fields),
+
+            // Levels compare by severity, and the log crate orders the most severe first.
+            Node::Level (comparison, value) => match comparison {
+
+                Comparison::Equal        => level == value,
+                Comparison::NotEqual     => level != value,
+                Comparison::Less         => level >  value,
+                Comparison::LessEqual    => level >= value,
+                Comparison::Greater      => level <  value,
+                Comparison::GreaterEqual => level <= value,
+                Comparison::Matches      => false,
+            },
+
+            // `tag != value` means no tag is the value, rather than some tag isn't.
+            Node::Text (Field::Tag, TextMatch::NotEqual (ref value)) => !fields.tags.iter ().any (|tag| tag == value),
+            Node::Text (Field::Tag, ref text_match) => fields.tags.iter ().any (|tag| text_match.matches (tag)),
+
+            Node::Text (field, ref text_match) => {
+
+                let text = match field {
+
+                    Field::Target  => fields.target,
+                    Field::Module  => fields.module_path,
+                    Field::Message => fields.message,
+                    Field::Tag     => unreachable! (),
+                };
+
+                text_match.matches (text)
+            },
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------TEXT MATCH PRIVATE METHODS---------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl TextMatch {
+
+    fn matches (&self, text: &str) -> bool {
+
+        match *self {
+
+            TextMatch::Equal    (ref value) => text == value,
+            TextMatch::NotEqual (ref value) => text != value,
+            TextMatch::Contains (ref value) => text.contains (value.as_str ()),
+            #[cfg (feature = "regex")]
+            TextMatch::Regex    (ref regex) => regex.is_match (text),
+        }
+    }
+
+}
+
+/*===============================================================================================*/
+/*------PARSER PRIVATE METHODS-------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Parser {
+
+    fn parse_or (&mut self) -> Result<Node, ParseExpressionError> {
+
+        let mut node = self.parse_and ()?;
+
+        while self.next_if (&Token::Or) {
+            node = Node::Or (Box::new (node), Box::new (self.parse_and ()?));
+        }
+
+        Ok (node)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn parse_and (&mut self) -> Result<Node, ParseExpressionError> {
+
+        let mut node = self.parse_unary ()?;
+
+        while self.next_if (&Token::And) {
+            node = Node::And (Box::new (node), Box::new (self.parse_unary ()?));
+        }
+
+        Ok (node)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn parse_unary (&mut self) -> Result<Node, ParseExpressionError> {
+
+        let (position, token) = self.next ("expected a condition")?;
+
+        match token {
+
+            Token::Not  => Ok (Node::Not (Box::new (self.parse_unary ()?))),
+            Token::Open => {
+
+                let node = self.parse_or ()?;
+
+                match self.next ("expected `)`")? {
+
+                    (_, Token::Close) => Ok (node),
+                    (position, _) => Err (ParseExpressionError::new (position, "expected `)`")),
+                }
+            },
+
+            Token::Word (ref field) => self.parse_condition (position, field),
+            _ => Err (ParseExpressionError::new (position, "expected a condition")),
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn parse_condition (&mut self, position: usize, field: &str) -> Result<Node, ParseExpressionError> {
+
+        let comparison = match self.next ("expected a comparison")? {
+
+            (_, Token::Comparison (comparison)) => comparison,
+            (position, _) => return Err (ParseExpressionError::new (position, "expected a comparison")),
+        };
+
+        let (value_position, value) = self.next ("expected a value")?;
+
+        if field == "level" {
+
+            let level = match value {
+
+                Token::Word (ref word) => word.parse ().ok (),
+                _ => None,
+            };
+
+            return match level {
+
+                Some (level) if comparison != Comparison::Matches => Ok (Node::Level (comparison, level)),
+                Some (_) => Err (ParseExpressionError::new (value_position, "levels can't be matched with `~`")),
+                None => Err (ParseExpressionError::new (value_position, "expected a level")),
+            };
+        }
+
+        let field = match field {
+
+            "target"  => Field::Target,
+            "module"  => Field::Module,
+            "message" => Field::Message,
+            "tag"     => Field::Tag,
+            _ => return Err (ParseExpressionError::new (position, "unknown field")),
+        };
+
+        let text_match = match (comparison, value) {
+
+            (Comparison::Equal,    Token::String (value)) => TextMatch::Equal (value),
+            (Comparison::NotEqual, Token::String (value)) => TextMatch::NotEqual (value),
+            (Comparison::Matches,  Token::String (value)) => TextMatch::Contains (value),
+            #[cfg (feature = "regex")]
+            (Comparison::Matches,  Token::Regex  (value)) => match Regex::new (&value) {
+
+                Ok (regex) => TextMatch::Regex (regex),
+                Err (_) => return Err (ParseExpressionError::new (value_position, "invalid regex")),
+            },
+
+            #[cfg (not (feature = "regex"))]
+            (Comparison::Matches,  Token::Regex  (_)) => return Err (ParseExpressionError::new (value_position, "regex matching needs the `regex` feature")),
+
+            _ => return Err (ParseExpressionError::new (value_position, "expected a string, or a regex after `~`")),
+        };
+
+        Ok (Node::Text (field, text_match))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn next (&mut self, expected: &str) -> Result<(usize, Token), ParseExpressionError> {
+
+        match self.tokens.get (self.pos) {
+
+            Some (token) => {
+
+                self.pos += 1;
+                Ok (token.clone ())
+            },
+
+            None => Err (ParseExpressionError::new (self.end, expected)),
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn next_if (&mut self, token: &Token) -> bool {
+
+        match self.tokens.get (self.pos) {
+
+            Some ((_, next)) if next == token => {
+
+                self.pos += 1;
+                true
+            },
+
+            _ => false,
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+fn tokenize (source: &str) -> Result<Vec<(usize, Token)>, ParseExpressionError> {
+
+    let mut tokens = Vec::new ();
+    let mut chars = source.char_indices ().peekable ();
+
+    while let Some ((position, c)) = chars.next () {
+
+        let token = match c {
+
+            c if c.is_whitespace () => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '~' => Token::Comparison (Comparison::Matches),
+            '&' | '|' => {
+
+                match chars.next () {
+
+                    Some ((_, next)) if next == c => (),
+                    _ => return Err (ParseExpressionError::new (position, "expected `&&` or `||`")),
+                }
+
+                if c == '&' {Token::And} else {Token::Or}
+            },
+
+            '!' | '=' | '<' | '>' => {
+
+                let equals = match chars.peek () {
+
+                    Some (&(_, '=')) => {
+
+                        chars.next ();
+                        true
+                    },
+
+                    _ => false,
+                };
+
+                match (c, equals) {
+
+                    ('!', false) => Token::Not,
+                    ('!', true)  => Token::Comparison (Comparison::NotEqual),
+                    ('=', true)  => Token::Comparison (Comparison::Equal),
+                    ('<', false) => Token::Comparison (Comparison::Less),
+                    ('<', true)  => Token::Comparison (Comparison::LessEqual),
+                    ('>', false) => Token::Comparison (Comparison::Greater),
+                    ('>', true)  => Token::Comparison (Comparison::GreaterEqual),
+                    _ => return Err (ParseExpressionError::new (position, "expected `==`")),
+                }
+            },
+
+            '"' | '/' => {
+
+                let mut value = String::new ();
+                let mut closed = false;
+
+                while let Some ((_, next)) = chars.next () {
+
+                    if next == c {
+
+                        closed = true;
+                        break;
+                    }
+
+                    // A backslash escapes the delimiter. Other escapes are kept for the regex.
+                    if next == '\\' {
+
+                        match chars.next () {
+
+                            Some ((_, escaped)) if escaped == c => value.push (escaped),
+                            Some ((_, escaped)) if c == '"' && escaped == '\\' => value.push (escaped),
+                            Some ((_, escaped)) => {
+
+                                value.push ('\\');
+                                value.push (escaped);
+                            },
+
+                            None => break,
+                        }
+                    }
+
+                    else {
+                        value.push (next);
+                    }
+                }
+
+                if !closed {
+                    return Err (ParseExpressionError::new (position, "unterminated string or regex"));
+                }
+
+                if c == '"' {Token::String (value)} else {Token::Regex (value)}
+            },
+
+            c if c.is_alphanumeric () || c == '_' => {
+
+                let mut word = c.to_string ();
+
+                while let Some (&(_, next)) = chars.peek () {
+
+                    if !(next.is_alphanumeric () || next == '_') {
+                        break;
+                    }
+
+                    word.push (next);
+                    chars.next ();
+                }
+
+                Token::Word (word)
+            },
+
+            _ => return Err (ParseExpressionError::new (position, "unexpected character")),
+        };
+
+        tokens.push ((position, token));
+    }
+
+    Ok (tokens)
+}
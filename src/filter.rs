@@ -0,0 +1,222 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Per-module level filters and the directive syntax used to configure them.
+/*===============================================================================================*/
+
+// Module imports
+use LogLevel;
+
+use std::error::Error;
+use std::fmt;
+
+/*===============================================================================================*/
+/*------MODULE FILTER STRUCT---------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Overrides the maximum log level for a module and its children.
+#[derive (Clone, Debug, PartialEq, Eq)]
+pub struct ModuleFilter {
+
+    // Public
+    /// The module path, matched against the record target. `*` matches any characters.
+    pub module:        String,
+    /// The maximum log level for the module.
+    pub max_log_level: LogLevel,
+}
+
+/*===============================================================================================*/
+/*------PARSE FILTER ERROR STRUCT----------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// The error returned when a filter directive can't be parsed.
+#[derive (Clone, Debug, PartialEq, Eq)]
+pub struct ParseFilterError {
+
+    // Private
+    directive: String,
+}
+
+/*===============================================================================================*/
+/*------PARSE FILTER ERROR TRAIT IMPLEMENTATIONS-------------------------------------------------*/
+/*===============================================================================================*/
+
+impl fmt::Display for ParseFilterError {
+
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write! (f, "invalid filter directive `{}`", self.directive)
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl Error for ParseFilterError {}
+
+/*===============================================================================================*/
+/*------PARSE FILTER ERROR PUBLIC METHODS--------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl ParseFilterError {
+
+    /// Returns the directive that couldn't be parsed.
+    pub fn directive (&self) -> &str {
+        &self.directive
+    }
+}
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Parses a comma separated directive list, such as `info,sqlx=warn,my_app::gpu=trace`.
+// Returns the default level, if one was given, and the module filters.
+pub fn parse (directives: &str) -> Result<(Option<LogLevel>, Vec<ModuleFilter>), ParseFilterError> {
+
+    let mut default_level = None;
+    let mut filters = Vec::new ();
+
+    for directive in directives.split (',').map (str::trim).filter (|d| !d.is_empty ()) {
+
+        let error = || ParseFilterError {directive: directive.to_string ()};
+        let mut parts = directive.splitn (2, '=');
+        let name = parts.next ().unwrap ().trim ();
+
+        match parts.next () {
+
+            Some (level) => {
+
+                if name.is_empty () {
+                    return Err (error ());
+                }
+
+                filters.push (ModuleFilter {
+
+                    module:        name.to_string (),
+                    max_log_level: level.trim ().parse ().map_err (|_| error ())?,
+                });
+            },
+
+            // A bare word is either the default level, or a module to log everything from.
+            None => match name.parse () {
+
+                Ok (level) => default_level = Some (level),
+                Err (_) => filters.push (ModuleFilter {
+
+                    module:        name.to_string (),
+                    max_log_level: LogLevel::Trace,
+                }),
+            },
+        }
+    }
+
+    Ok ((default_level, filters))
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Returns the level of the most specific filter matching the target.
+pub fn level_for (filters: &[ModuleFilter], target: &str) -> Option<LogLevel> {
+    most_specific (filters, |filter| &filter.module, target).map (|filter| filter.max_log_level)
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Returns the item whose module pattern is the most specific match for the target.
+// Specificity is the length of the pattern, not counting wildcards.
+pub fn most_specific<'a, T, F: Fn (&T) -> &str> (items: &'a [T], module: F, target: &str) -> Option<&'a T> {
+
+    items.iter ()
+         .filter (|item| matches (module (item), target))
+         .max_by_key (|item| module (item).len () - module (item).matches ('*').count ())
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Whether the target matches any of the module patterns.
+pub fn matches_any (modules: &[String], target: &str) -> bool {
+    modules.iter ().any (|module| matches (module, target))
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Whether the module pattern is a `::` separated path of identifiers and wildcards.
+pub fn is_valid_module (module: &str) -> bool {
+
+    module.split ("::").all (|segment| {
+
+        !segment.is_empty () &&
+        segment.chars ().all (|c| c.is_alphanumeric () || c == '_' || c == '-' || c == '*')
+    })
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Whether the target is the module or one of its children.
+// The module may contain `*` wildcards, which match any run of characters.
+fn matches (module: &str, target: &str) -> bool {
+
+    if !module.contains ('*') {
+
+        return target.starts_with (module) &&
+               (target.len () == module.len () || target[module.len ()..].starts_with ("::"));
+    }
+
+    // Try the target itself, then each of its parent modules.
+    target.match_indices ("::")
+          .map (|(index, _)| &target[..index])
+          .chain (Some (target))
+          .any (|path| glob_matches (module.as_bytes (), path.as_bytes ()))
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+fn glob_matches (pattern: &[u8], text: &[u8]) -> bool {
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len () {
+
+        if p < pattern.len () && pattern[p] == b'*' {
+
+            backtrack = Some ((p, t));
+            p += 1;
+        }
+
+        else if p < pattern.len () && pattern[p] == text[t] {
+
+            p += 1;
+            t += 1;
+        }
+
+        // Let the last wildcard swallow one more character and try again.
+        else if let Some ((star, start)) = backtrack {
+
+            backtrack = Some ((star, start + 1));
+            p = star + 1;
+            t = start + 1;
+        }
+
+        else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter ().all (|&c| c == b'*')
+}
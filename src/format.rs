@@ -0,0 +1,528 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Formatting helpers shared by the output sinks and the reader.
+/*===============================================================================================*/
+
+// Module imports
+use log;
+
+use std::fmt::Write;
+#[cfg (feature = "binary")]
+use std::time::Duration;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The version of the JSON Lines layout, written as the `schema` field of every line.
+///
+/// Each line is an object with these fields, in this order:
+///
+/// | Field         | Type             | Notes                                          |
+/// |---------------|------------------|------------------------------------------------|
+/// | `schema`      | number           | This version.                                  |
+/// | `timestamp`   | string           | RFC 3339 in UTC, with microseconds.            |
+/// | `level`       | string           | `ERROR`, `WARN`, `INFO`, `DEBUG` or `TRACE`.   |
+/// | `target`      | string           |                                                |
+/// | `module_path` | string           |                                                |
+/// | `line`        | number           |                                                |
+/// | `message`     | string           |                                                |
+/// | `trace_id`    | string, optional | Left out when the record has no trace context. |
+/// | `span_id`     | string, optional | Left out when the record has no trace context. |
+/// | `session_id`  | string, optional |                                                |
+/// | `app_name`    | string, optional | Left out when not configured.                  |
+/// | `instance_id` | string, optional | Left out when not configured.                  |
+/// | `severity`    | string           | See below.                                     |
+/// | `tags`        | array of strings | Left out when empty.                           |
+///
+/// `severity` repeats `level` under the names log collectors such as Kubernetes and Cloud
+/// Logging look for, with `WARNING` for warn and `DEBUG` for trace. Records logged with
+/// `notice!`, `critical!` and `fatal!` have `NOTICE`, `CRITICAL` and `EMERGENCY` instead.
+///
+/// Terminal output written with `LogConfig::terminal_json` also has a `time` field before
+/// `severity`, which repeats `timestamp` under the name collectors look for.
+///
+/// New fields may be added without changing the version, so parsers should ignore fields
+/// they don't know. The version only goes up when an existing field is removed or changes
+/// meaning. `reader::Record::from_json` reads lines of this version or earlier.
+///
+/// `LogFormat::MessagePack` and `LogFormat::Cbor` write maps with the same fields and version, except that
+/// `timestamp` is an integer count of microseconds since the Unix epoch.
+pub const JSON_SCHEMA: u32 = 1;
+
+/*===============================================================================================*/
+/*------LEVEL NAME STRUCT------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Writes a level under another name in text output, for the records of a target.
+///
+/// Structured output keeps the standard names, so it can still be read back.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate log;
+/// # extern crate ion_log;
+/// # use ion_log::*;
+/// # fn main () {
+/// let sink = MockSink::new ();
+///
+/// let mut config = LogConfig::new ();
+/// config.log_to_io = false;
+/// config.mock_sink = Some (sink.clone ());
+/// config.level_names.push (LevelName {target: "*".to_string (), level: log::LogLevel::Warn, name: "WARNING".to_string ()});
+///
+/// ion_log::init (&config).unwrap ();
+/// warn! ("Disk nearly full");
+/// ion_log::release ();
+///
+/// assert! (sink.lines ()[0].ends_with ("WARNING: Disk nearly full"));
+/// # }
+/// ```
+#[derive (Clone, Debug, PartialEq, Eq)]
+pub struct LevelName {
+
+    // Public
+    /// The target, matched like a module filter. `*` matches any characters, so on its own
+    /// it renames the level for every target.
+    pub target: String,
+    /// The level to rename.
+    pub level:  log::LogLevel,
+    /// The name to write instead, such as `WARNING` or `FATAL`.
+    pub name:   String,
+}
+
+/*===============================================================================================*/
+/*------SEVERITY ENUM----------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// The severities syslog and SIEM pipelines use beyond the log crate's levels, logged with
+/// `notice!`, `critical!` and `fatal!`.
+///
+/// Each is logged at a standard level with a tag naming the severity, so it can be matched by
+/// filter expressions. Structured output also names it in the `severity` field, which
+/// `reader::Record::severity` reads back.
+#[derive (Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+
+    /// A normal but significant event, logged at info as `NOTICE`.
+    Notice,
+    /// A critical condition, logged at error as `CRITICAL`.
+    Critical,
+    /// A failure the process can't recover from, logged at error as `EMERGENCY`.
+    /// Logging it doesn't stop the process.
+    Fatal,
+}
+
+/*===============================================================================================*/
+/*------JSON RECORD STRUCT-----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// The fields of a record, borrowed for writing as JSON, MessagePack or CBOR.
+pub struct JsonRecord<'a, T: AsRef<str>> {
+
+    // Public
+    pub level:       log::LogLevel,
+    pub timestamp:   SystemTime,
+    pub target:      &'a str,
+    pub module_path: &'a str,
+    pub line:        u32,
+    pub message:     &'a str,
+    pub trace_id:    Option<&'a str>,
+    pub span_id:     Option<&'a str>,
+    pub session_id:  Option<&'a str>,
+    pub app_name:    Option<&'a str>,
+    pub instance_id: Option<&'a str>,
+    pub tags:        &'a [T],
+    pub severity:    Option<Severity>,
+    // Adds `time`, the name log collectors look for.
+    pub collector:   bool,
+}
+
+/*===============================================================================================*/
+/*------SEVERITY PUBLIC METHODS------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Severity {
+
+    /// Returns the level records of this severity are logged at.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate log;
+    /// # extern crate ion_log;
+    /// # use ion_log::*;
+    /// # fn main () {
+    /// assert_eq! (Severity::Notice.level (), log::LogLevel::Info);
+    /// assert_eq! (Severity::Fatal.level (), log::LogLevel::Error);
+    /// # }
+    /// ```
+    pub fn level (self) -> log::LogLevel {
+
+        match self {
+
+            Severity::Notice => log::LogLevel::Info,
+            Severity::Critical | Severity::Fatal => log::LogLevel::Error,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Returns the tag attached to records of this severity, such as `severity=critical`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// let expression = format! ("tag == \"{}\"", Severity::Critical.tag ());
+    /// let filter = FilterExpression::parse (&expression).unwrap ();
+    /// ```
+    pub fn tag (self) -> &'static str {
+
+        match self {
+
+            Severity::Notice   => "severity=notice",
+            Severity::Critical => "severity=critical",
+            Severity::Fatal    => "severity=fatal",
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Appends a record in the plain text layout, including the trailing newline.
+// Returns the length of the location part, `[module_path - line]`.
+pub fn text_line (out: &mut String, level: &str, module_path: &str, line: u32, message: &str) -> usize {
+
+    let start = out.len ();
+
+    write! (out, "[{} - {}]", module_path, line).unwrap ();
+
+    let location_length = out.len () - start;
+
+    writeln! (out, " {}: {}", level, message).unwrap ();
+    location_length
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Returns the standard name of the level, as displayed by the log crate.
+pub fn level_name (level: log::LogLevel) -> &'static str {
+
+    match level {
+
+        log::LogLevel::Error => "ERROR",
+        log::LogLevel::Warn  => "WARN",
+        log::LogLevel::Info  => "INFO",
+        log::LogLevel::Debug => "DEBUG",
+        log::LogLevel::Trace => "TRACE",
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Appends a record starting with `file:line:`, which IDE terminals turn into a link.
+// Returns the length of that location part.
+pub fn file_text_line (out: &mut String, level: &str, file: &str, line: u32, message: &str) -> usize {
+
+    let start = out.len ();
+
+    write! (out, "{}:{}:", file, line).unwrap ();
+
+    let location_length = out.len () - start;
+
+    writeln! (out, " {}: {}", level, message).unwrap ();
+    location_length
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Appends a record without a location, as `LEVEL: message`. Returns the location length, 0.
+pub fn bare_text_line (out: &mut String, level: &str, message: &str) -> usize {
+
+    writeln! (out, "{}: {}", level, message).unwrap ();
+    0
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Appends a record as a single line JSON object, without a trailing newline.
+// Optional fields are left out when they have no value.
+pub fn json_line<T: AsRef<str>> (out: &mut String, record: &JsonRecord<T>) {
+
+    write! (out, "{{\"schema\":{},\"timestamp\":\"", JSON_SCHEMA).unwrap ();
+    timestamp (out, record.timestamp);
+    out.push_str ("\",\"level\":");
+    json_string (out, &record.level.to_string ());
+    out.push_str (",\"target\":");
+    json_string (out, record.target);
+    out.push_str (",\"module_path\":");
+    json_string (out, record.module_path);
+    write! (out, ",\"line\":{},\"message\":", record.line).unwrap ();
+    json_string (out, record.message);
+
+    let optional = [("trace_id",    record.trace_id),
+                    ("span_id",     record.span_id),
+                    ("session_id",  record.session_id),
+                    ("app_name",    record.app_name),
+                    ("instance_id", record.instance_id)];
+
+    for (name, value) in optional.iter () {
+
+        if let Some (value) = *value {
+
+            write! (out, ",\"{}\":", name).unwrap ();
+            json_string (out, value);
+        }
+    }
+
+    if record.collector {
+
+        out.push_str (",\"time\":\"");
+        timestamp (out, record.timestamp);
+        out.push ('"');
+    }
+
+    write! (out, ",\"severity\":\"{}\"", severity_name (record.level, record.severity)).unwrap ();
+
+    if !record.tags.is_empty () {
+
+        out.push_str (",\"tags\":[");
+
+        for (i, tag) in record.tags.iter ().enumerate () {
+
+            if i > 0 {
+                out.push (',');
+            }
+
+            json_string (out, tag.as_ref ());
+        }
+
+        out.push (']');
+    }
+
+    out.push ('}');
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Appends a UTC timestamp in RFC 3339 format, with microsecond precision.
+pub fn timestamp (out: &mut String, time: SystemTime) {
+
+    let micros = timestamp_micros (time);
+    let seconds = micros / 1_000_000;
+    let (year, month, day) = civil_from_days ((seconds / 86_400) as i64);
+    let time_of_day = seconds % 86_400;
+
+    write! (out, "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+            year, month, day,
+            time_of_day / 3600,
+            time_of_day / 60 % 60,
+            time_of_day % 60,
+            micros % 1_000_000).unwrap ();
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Returns the UTC date of a time, as in `2026-10-14`.
+pub fn date (time: SystemTime) -> String {
+
+    let seconds = timestamp_micros (time) / 1_000_000;
+    let (year, month, day) = civil_from_days ((seconds / 86_400) as i64);
+
+    format! ("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Returns the number of microseconds since the Unix epoch, or 0 for earlier times.
+pub fn timestamp_micros (time: SystemTime) -> u64 {
+
+    match time.duration_since (UNIX_EPOCH) {
+
+        Ok (duration) => duration.as_secs () * 1_000_000 + u64::from (duration.subsec_micros ()),
+        Err (_) => 0,
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Appends a string as a quoted JSON string.
+pub fn json_string (out: &mut String, value: &str) {
+
+    out.push ('"');
+
+    for c in value.chars () {
+
+        match c {
+
+            '"'  => out.push_str ("\\\""),
+            '\\' => out.push_str ("\\\\"),
+            '\n' => out.push_str ("\\n"),
+            '\r' => out.push_str ("\\r"),
+            '\t' => out.push_str ("\\t"),
+            c if (c as u32) < 0x20 => write! (out, "\\u{:04x}", c as u32).unwrap (),
+            c => out.push (c),
+        }
+    }
+
+    out.push ('"');
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Parses a UTC timestamp in RFC 3339 format, as written by `timestamp`.
+// The fractional seconds and the trailing `Z` are optional, and a bare date means midnight.
+#[cfg (feature = "binary")]
+pub fn parse_timestamp (text: &str) -> Option<SystemTime> {
+
+    let text = text.trim_end_matches ('Z');
+    let (date, time) = match text.find ('T') {
+
+        Some (index) => (&text[..index], &text[index + 1..]),
+        None => (text, "00:00:00"),
+    };
+
+    let mut date_parts = date.splitn (3, '-');
+    let year:  i64 = date_parts.next ()?.parse ().ok ()?;
+    let month: u32 = date_parts.next ()?.parse ().ok ()?;
+    let day:   u32 = date_parts.next ()?.parse ().ok ()?;
+
+    let (time, fraction) = match time.find ('.') {
+
+        Some (index) => (&time[..index], &time[index + 1..]),
+        None => (time, ""),
+    };
+
+    let mut time_parts = time.splitn (3, ':');
+    let hours:   u64 = time_parts.next ()?.parse ().ok ()?;
+    let minutes: u64 = time_parts.next ()?.parse ().ok ()?;
+    let seconds: u64 = time_parts.next ().unwrap_or ("0").parse ().ok ()?;
+
+    if !(1..=12).contains (&month) || !(1..=31).contains (&day) || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+
+    let mut micros = 0u64;
+
+    for (i, digit) in fraction.chars ().take (6).enumerate () {
+        micros += u64::from (digit.to_digit (10)?) * 10u64.pow (5 - i as u32);
+    }
+
+    let days = days_from_civil (year, month, day);
+
+    if days < 0 {
+        return None;
+    }
+
+    let seconds = days as u64 * 86_400 + hours * 3600 + minutes * 60 + seconds;
+    Some (UNIX_EPOCH + Duration::from_secs (seconds) + Duration::from_micros (micros))
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Converts the timestamp of a MessagePack or CBOR record, in microseconds since the Unix epoch.
+#[cfg (feature = "binary")]
+pub fn micros_timestamp (micros: f64) -> Option<SystemTime> {
+
+    if micros < 0.0 || micros.fract () != 0.0 {
+        return None;
+    }
+
+    UNIX_EPOCH.checked_add (Duration::from_micros (micros as u64))
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Returns the severity named by a record's tags, if it was logged with one.
+pub fn tagged_severity<T: AsRef<str>> (tags: &[T]) -> Option<Severity> {
+
+    [Severity::Notice, Severity::Critical, Severity::Fatal].iter ()
+                                                           .cloned ()
+                                                           .find (|severity| tags.iter ().any (|tag| tag.as_ref () == severity.tag ()))
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Returns the severity name log collectors use for a record, from its severity or else its
+// level.
+pub fn severity_name (level: log::LogLevel, severity: Option<Severity>) -> &'static str {
+
+    match severity {
+
+        Some (Severity::Notice)   => return "NOTICE",
+        Some (Severity::Critical) => return "CRITICAL",
+        Some (Severity::Fatal)    => return "EMERGENCY",
+        None => (),
+    }
+
+    match level {
+
+        log::LogLevel::Error => "ERROR",
+        log::LogLevel::Warn  => "WARNING",
+        log::LogLevel::Info  => "INFO",
+        log::LogLevel::Debug | log::LogLevel::Trace => "DEBUG",
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Parses the `severity` field of a structured record. Names of standard levels give `None`.
+#[cfg (feature = "binary")]
+pub fn parse_severity (name: &str) -> Option<Severity> {
+
+    match name {
+
+        "NOTICE"    => Some (Severity::Notice),
+        "CRITICAL"  => Some (Severity::Critical),
+        "EMERGENCY" => Some (Severity::Fatal),
+        _ => None,
+    }
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Converts a (year, month, day) date to days since the Unix epoch.
+pub fn days_from_civil (year: i64, month: u32, day: u32) -> i64 {
+
+    let year = if month <= 2 {year - 1} else {year};
+    let era = year.div_euclid (400);
+    let year_of_era = year.rem_euclid (400);
+    let month_index = i64::from (if month > 2 {month - 3} else {month + 9});
+    let day_of_year = (153 * month_index + 2) / 5 + i64::from (day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Converts days since the Unix epoch to a (year, month, day) date.
+pub fn civil_from_days (days: i64) -> (i64, u32, u32) {
+
+    let days = days + 719_468;
+    let era = days.div_euclid (146_097);
+    let day_of_era = days.rem_euclid (146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {month_index + 3} else {month_index - 9} as u32;
+    let year = year_of_era + era * 400 + if month <= 2 {1} else {0};
+
+    (year, month, day)
+}
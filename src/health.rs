@@ -0,0 +1,155 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Tracks whether each sink is still accepting writes.
+/*===============================================================================================*/
+
+// Module imports
+use Clock;
+
+use std::io;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/*===============================================================================================*/
+/*------CONSTANTS--------------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// The number of failed writes in a row before a sink is reported as down.
+const DOWN_AFTER_FAILURES: u32 = 3;
+
+/*===============================================================================================*/
+/*------SINK STATUS ENUM-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// The state of a sink, as returned by `ion_log::health`.
+#[derive (Clone, Debug, PartialEq, Eq)]
+pub enum SinkStatus {
+
+    /// The last write succeeded.
+    Ok,
+    /// The last write failed.
+    Degraded {
+
+        /// The error from the last write.
+        last_error: String,
+    },
+    /// Several writes in a row have failed.
+    Down {
+
+        /// When the first of the failed writes happened.
+        since:      SystemTime,
+        /// The error from the last write.
+        last_error: String,
+    },
+}
+
+/*===============================================================================================*/
+/*------SINK HEALTH STRUCT-----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// The status of one sink of the running logger.
+#[derive (Clone, Debug, PartialEq, Eq)]
+pub struct SinkHealth {
+
+    // Public
+    /// The sink name: `terminal`, `file` or `mock`.
+    pub name:   &'static str,
+    /// The sink status.
+    pub status: SinkStatus,
+}
+
+/*===============================================================================================*/
+/*------SINK TRACKER STRUCT----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Records the outcome of each write to a sink.
+#[derive (Debug)]
+pub struct SinkTracker {
+
+    // Private
+    clock:      Arc<dyn Clock>,
+    failures:   u32,
+    since:      Option<SystemTime>,
+    last_error: Option<String>,
+}
+
+/*===============================================================================================*/
+/*------SINK TRACKER PUBLIC METHODS--------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl SinkTracker {
+
+    pub fn new (clock: Arc<dyn Clock>) -> Self {
+
+        SinkTracker {
+
+            clock,
+            failures:   0,
+            since:      None,
+            last_error: None,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Records a write, then passes its result through.
+    pub fn track<T> (&mut self, result: io::Result<T>) -> io::Result<T> {
+        self.track_buffered (result, true)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Records a write into a buffer, which only counts as a success if it reached the sink.
+    pub fn track_buffered<T> (&mut self, result: io::Result<T>, reached_sink: bool) -> io::Result<T> {
+
+        match result {
+
+            Ok (_) => if reached_sink {
+
+                self.failures = 0;
+                self.since    = None;
+            },
+
+            Err (ref e) => {
+
+                self.failures  += 1;
+                self.last_error = Some (e.to_string ());
+
+                if self.since.is_none () {
+                    self.since = Some (self.clock.now ());
+                }
+            },
+        }
+
+        result
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    pub fn status (&self) -> SinkStatus {
+
+        let last_error = self.last_error.clone ().unwrap_or_default ();
+
+        match (self.failures, self.since) {
+
+            (0, _) => SinkStatus::Ok,
+            (failures, Some (since)) if failures >= DOWN_AFTER_FAILURES => SinkStatus::Down {since, last_error},
+            _ => SinkStatus::Degraded {last_error},
+        }
+    }
+}
@@ -0,0 +1,156 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Periodic heartbeat records, set with `LogConfig::heartbeat`.
+/*===============================================================================================*/
+
+// Module imports
+use log;
+
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// How often the thread checks whether it should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis (50);
+
+/*===============================================================================================*/
+/*------HEARTBEAT STRUCT-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Logs an info record at a regular interval, so a quiet process can be told apart from a
+/// hung one.
+///
+/// Each record reads `<message>: uptime <time>, <n> records, queue depth <n>`, counting the
+/// records logged since the last heartbeat. It is logged with the `ion_log::heartbeat` target,
+/// so filters can route or block it like any other record.
+#[derive (Clone, Debug, PartialEq, Eq)]
+pub struct Heartbeat {
+
+    // Public
+    /// The time between heartbeats.
+    pub interval: Duration,
+    /// The start of each heartbeat record.
+    pub message:  String,
+}
+
+/*===============================================================================================*/
+/*------HEARTBEAT THREAD STRUCT------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// The thread logging heartbeats.
+pub struct HeartbeatThread {
+
+    // Private
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+/*===============================================================================================*/
+/*------HEARTBEAT PUBLIC METHODS-----------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Heartbeat {
+
+    /// Returns a heartbeat logging `heartbeat` at the interval.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// # use std::time::Duration;
+    /// let mut config = LogConfig::new ();
+    /// config.heartbeat = Some (Heartbeat::new (Duration::from_secs (60)));
+    /// ```
+    pub fn new (interval: Duration) -> Self {
+        Heartbeat {interval, message: "heartbeat".to_string ()}
+    }
+}
+
+/*===============================================================================================*/
+/*------HEARTBEAT THREAD PUBLIC METHODS----------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl HeartbeatThread {
+
+    pub fn start (heartbeat: &Heartbeat) -> io::Result<Self> {
+
+        let heartbeat = heartbeat.clone ();
+        let stop = Arc::new (AtomicBool::new (false));
+        let thread_stop = stop.clone ();
+        let thread = thread::Builder::new ().name ("ion_log_heartbeat".to_string ())
+                                            .spawn (move || HeartbeatThread::run (&heartbeat, &thread_stop))?;
+
+        Ok (HeartbeatThread {stop, thread})
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Stops the thread and waits for it to finish.
+    pub fn stop (self) {
+
+        self.stop.store (true, Ordering::Relaxed);
+        drop (self.thread.join ());
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Stops the thread without waiting, for when the caller holds the logger lock, which the
+    // thread may be waiting on.
+    pub fn stop_later (self) {
+        self.stop.store (true, Ordering::Relaxed);
+    }
+}
+
+/*===============================================================================================*/
+/*------HEARTBEAT THREAD PRIVATE METHODS---------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl HeartbeatThread {
+
+    fn run (heartbeat: &Heartbeat, stop: &AtomicBool) {
+
+        // An unvalidated zero interval would otherwise log as fast as it can.
+        let interval = heartbeat.interval.max (POLL_INTERVAL);
+        let mut next = Instant::now () + interval;
+
+        while !stop.load (Ordering::Relaxed) {
+
+            let now = Instant::now ();
+
+            if now < next {
+
+                thread::sleep (POLL_INTERVAL.min (next - now));
+                continue;
+            }
+
+            next = now + interval;
+
+            if let Some ((uptime, records, queue_depth)) = ::heartbeat_stats () {
+
+                let location = log::LogLocation {__module_path: module_path! (), __file: file! (), __line: line! ()};
+
+                log::__log (log::LogLevel::Info,
+                            "ion_log::heartbeat",
+                            &location,
+                            format_args! ("{}: uptime {}s, {} records, queue depth {}",
+                                          heartbeat.message, uptime.as_secs (), records, queue_depth));
+            }
+        }
+    }
+}
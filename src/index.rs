@@ -0,0 +1,207 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Builds the sidecar index written next to a binary log file.
+//!
+//! The index is rewritten whole on each flush, so it only ever lags the log file. Readers
+//! use it to seek by time and to count records without a full scan.
+/*===============================================================================================*/
+
+// Module imports
+use binary;
+use format;
+use Entry;
+
+use std::fs;
+use std::io;
+
+// The length of an index bucket, in microseconds.
+const MINUTE_MICROS: u64 = 60_000_000;
+
+/*===============================================================================================*/
+/*------INDEX WRITER STRUCT----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+pub struct IndexWriter {
+
+    // Private
+    path: String,
+    frames: Vec<u8>,
+    minutes: Vec<Minute>,
+    offset: u64,
+    dirty: bool,
+    written: Checkpoint,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// The state of the index after the last records which reached the file.
+#[derive (Copy, Clone)]
+struct Checkpoint {
+
+    // Private
+    minutes: usize,
+    counts: [u64; 5],
+    offset: u64,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// The records logged in one minute, starting at the first of them.
+struct Minute {
+
+    // Private
+    minute: u64,
+    first: u64,
+    offset: u64,
+    strings: usize,
+    counts: [u64; 5],
+}
+
+/*===============================================================================================*/
+/*------INDEX WRITER PUBLIC METHODS--------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl IndexWriter {
+
+    // Starts an empty index for a log file, given everything written to it before the first record.
+    // The frames after the file header are copied into the index, so a reader that seeks
+    // past them still has the session.
+    pub fn new (log_path: &str, header: &[u8]) -> Self {
+
+        IndexWriter {
+
+            path: format! ("{}.idx", log_path),
+            frames: header[binary::MAGIC.len () + 1..].to_vec (),
+            minutes: Vec::new (),
+            offset: header.len () as u64,
+            dirty: true,
+            written: Checkpoint {minutes: 0, counts: [0; 5], offset: header.len () as u64},
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Adds an entry which was just encoded into `length` bytes, after `strings` strings
+    // had been defined.
+    pub fn add (&mut self, entry: &Entry, strings: usize, length: usize) {
+
+        let timestamp = format::timestamp_micros (entry.timestamp);
+        let minute = timestamp / MINUTE_MICROS;
+
+        // Records written slightly out of order stay in the current minute.
+        if self.minutes.last ().is_none_or (|last| minute > last.minute) {
+
+            self.minutes.push (Minute {
+
+                minute,
+                first: timestamp,
+                offset: self.offset,
+                strings,
+                counts: [0; 5],
+            });
+        }
+
+        self.minutes.last_mut ().unwrap ().counts[entry.level as usize - 1] += 1;
+        self.offset += length as u64;
+        self.dirty = true;
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Adds a frame written to the log file between records, which applies to the rest of it.
+    pub fn add_frame (&mut self, frame: &[u8]) {
+
+        self.frames.extend_from_slice (frame);
+        self.offset += frame.len () as u64;
+        self.written.offset += frame.len () as u64;
+        self.dirty = true;
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Marks the entries added so far as written to the log file.
+    pub fn commit (&mut self) {
+
+        self.written = Checkpoint {
+
+            minutes: self.minutes.len (),
+            counts: self.minutes.last ().map_or ([0; 5], |minute| minute.counts),
+            offset: self.offset,
+        };
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Removes the entries added since the last commit, whose frames were dropped.
+    pub fn discard (&mut self) {
+
+        self.minutes.truncate (self.written.minutes);
+
+        if let Some (minute) = self.minutes.last_mut () {
+            minute.counts = self.written.counts;
+        }
+
+        self.offset = self.written.offset;
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Replaces the index file, if anything was added since it was last written.
+    // The string table comes from the encoder of the log file.
+    pub fn write (&mut self, encoder: &binary::Encoder) -> io::Result<()> {
+
+        if !self.dirty {
+            return Ok (());
+        }
+
+        let mut out = Vec::new ();
+        let mut payload = Vec::new ();
+
+        out.extend_from_slice (binary::INDEX_MAGIC);
+        out.push (binary::VERSION);
+        out.extend_from_slice (&self.frames);
+        encoder.string_table (&mut out);
+
+        for minute in &self.minutes {
+
+            payload.clear ();
+            binary::write_varint (&mut payload, minute.first);
+            binary::write_varint (&mut payload, minute.offset);
+            binary::write_varint (&mut payload, minute.strings as u64);
+
+            for &count in &minute.counts {
+                binary::write_varint (&mut payload, count);
+            }
+
+            binary::write_frame (&mut out, binary::FRAME_MINUTE, &payload);
+        }
+
+        payload.clear ();
+        binary::write_varint (&mut payload, self.offset);
+        binary::write_frame (&mut out, binary::FRAME_LENGTH, &payload);
+
+        // Written aside and renamed, so a reader never sees half an index.
+        let temp_path = format! ("{}.tmp", self.path);
+
+        fs::write (&temp_path, &out)?;
+        fs::rename (&temp_path, &self.path)?;
+
+        self.dirty = false;
+        Ok (())
+    }
+}
@@ -0,0 +1,342 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! A small JSON parser, for reading back the JSON Lines log format.
+/*===============================================================================================*/
+
+// Module imports
+use std::char;
+use std::str;
+
+/*===============================================================================================*/
+/*------VALUE ENUM-------------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// A parsed JSON value. Objects keep their members in order.
+#[derive (Clone, Debug, PartialEq)]
+pub enum Value {
+
+    Null,
+    Bool (bool),
+    Number (f64),
+    String (String),
+    Array (Vec<Value>),
+    Object (Vec<(String, Value)>),
+}
+
+/*===============================================================================================*/
+/*------VALUE PUBLIC METHODS---------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Value {
+
+    // Returns the value of an object member, if this is an object and has it.
+    // A `null` member counts as missing.
+    pub fn get (&self, name: &str) -> Option<&Value> {
+
+        match *self {
+
+            Value::Object (ref members) => members.iter ()
+                                                  .find (|(member, _)| member == name)
+                                                  .map (|(_, value)| value)
+                                                  .filter (|value| **value != Value::Null),
+            _ => None,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    pub fn as_str (&self) -> Option<&str> {
+
+        match *self {
+
+            Value::String (ref value) => Some (value),
+            _ => None,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    pub fn as_f64 (&self) -> Option<f64> {
+
+        match *self {
+
+            Value::Number (value) => Some (value),
+            _ => None,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    pub fn as_array (&self) -> Option<&[Value]> {
+
+        match *self {
+
+            Value::Array (ref values) => Some (values),
+            _ => None,
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------PARSER STRUCT----------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+struct Parser<'a> {
+
+    // Private
+    text: &'a [u8],
+    pos: usize,
+}
+
+/*===============================================================================================*/
+/*------PARSER PRIVATE METHODS-------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl<'a> Parser<'a> {
+
+    fn value (&mut self) -> Option<Value> {
+
+        self.skip_whitespace ();
+
+        match *self.text.get (self.pos)? {
+
+            b'n' => self.literal ("null", Value::Null),
+            b't' => self.literal ("true", Value::Bool (true)),
+            b'f' => self.literal ("false", Value::Bool (false)),
+            b'"' => self.string ().map (Value::String),
+            b'[' => self.array (),
+            b'{' => self.object (),
+            _    => self.number (),
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn literal (&mut self, word: &str, value: Value) -> Option<Value> {
+
+        if !self.text[self.pos..].starts_with (word.as_bytes ()) {
+            return None;
+        }
+
+        self.pos += word.len ();
+        Some (value)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn number (&mut self) -> Option<Value> {
+
+        let start = self.pos;
+
+        while self.pos < self.text.len () && b"+-.eE0123456789".contains (&self.text[self.pos]) {
+            self.pos += 1;
+        }
+
+        str::from_utf8 (&self.text[start..self.pos]).ok ()?.parse ().ok ().map (Value::Number)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn string (&mut self) -> Option<String> {
+
+        self.pos += 1;
+
+        let mut value = Vec::new ();
+
+        loop {
+
+            let byte = *self.text.get (self.pos)?;
+            self.pos += 1;
+
+            match byte {
+
+                b'"' => return String::from_utf8 (value).ok (),
+                b'\\' => {
+
+                    let escape = *self.text.get (self.pos)?;
+                    self.pos += 1;
+
+                    let c = match escape {
+
+                        b'"'  => '"',
+                        b'\\' => '\\',
+                        b'/'  => '/',
+                        b'b'  => '\u{8}',
+                        b'f'  => '\u{c}',
+                        b'n'  => '\n',
+                        b'r'  => '\r',
+                        b't'  => '\t',
+                        b'u'  => self.unicode_escape ()?,
+                        _     => return None,
+                    };
+
+                    value.extend_from_slice (c.encode_utf8 (&mut [0; 4]).as_bytes ());
+                },
+
+                _ => value.push (byte),
+            }
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Reads the digits of a `\u` escape, and the low half if it starts a surrogate pair.
+    fn unicode_escape (&mut self) -> Option<char> {
+
+        let high = self.hex4 ()?;
+
+        if (0xd800..0xdc00).contains (&high) {
+
+            if !self.text[self.pos..].starts_with (b"\\u") {
+                return None;
+            }
+
+            self.pos += 2;
+
+            let low = self.hex4 ()?;
+
+            if !(0xdc00..0xe000).contains (&low) {
+                return None;
+            }
+
+            return char::from_u32 (0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00));
+        }
+
+        char::from_u32 (high)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn hex4 (&mut self) -> Option<u32> {
+
+        let digits = str::from_utf8 (self.text.get (self.pos..self.pos + 4)?).ok ()?;
+        let value = u32::from_str_radix (digits, 16).ok ()?;
+
+        self.pos += 4;
+        Some (value)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn array (&mut self) -> Option<Value> {
+
+        self.pos += 1;
+
+        let mut values = Vec::new ();
+
+        if self.next_is (b']') {
+            return Some (Value::Array (values));
+        }
+
+        loop {
+
+            values.push (self.value ()?);
+
+            if self.next_is (b']') {
+                return Some (Value::Array (values));
+            }
+
+            if !self.next_is (b',') {
+                return None;
+            }
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn object (&mut self) -> Option<Value> {
+
+        self.pos += 1;
+
+        let mut members = Vec::new ();
+
+        if self.next_is (b'}') {
+            return Some (Value::Object (members));
+        }
+
+        loop {
+
+            self.skip_whitespace ();
+
+            if self.text.get (self.pos) != Some (&b'"') {
+                return None;
+            }
+
+            let name = self.string ()?;
+
+            if !self.next_is (b':') {
+                return None;
+            }
+
+            members.push ((name, self.value ()?));
+
+            if self.next_is (b'}') {
+                return Some (Value::Object (members));
+            }
+
+            if !self.next_is (b',') {
+                return None;
+            }
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Skips whitespace, then consumes the byte if it comes next.
+    fn next_is (&mut self, byte: u8) -> bool {
+
+        self.skip_whitespace ();
+
+        if self.text.get (self.pos) != Some (&byte) {
+            return false;
+        }
+
+        self.pos += 1;
+        true
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn skip_whitespace (&mut self) {
+
+        while self.pos < self.text.len () && b" \t\r\n".contains (&self.text[self.pos]) {
+            self.pos += 1;
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Parses a JSON document, or returns `None` if it isn't valid JSON.
+pub fn parse (text: &str) -> Option<Value> {
+
+    let mut parser = Parser {text: text.as_bytes (), pos: 0};
+    let value = parser.value ()?;
+
+    parser.skip_whitespace ();
+
+    if parser.pos == parser.text.len () {
+        Some (value)
+    }
+
+    else {
+        None
+    }
+}
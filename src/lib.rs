@@ -38,6 +38,30 @@
 //!     ion_log::release ();
 //! }
 //! ```
+//!
+//! # Features
+//! The `colour`, `regex` and `binary` features are on by default. Without them, terminal
+//! output is never coloured and `ansi_term` isn't needed, filter expressions can't use
+//! `/regex/` and `regex` isn't needed, and the binary format and `reader` module are left
+//! out, leaving only text and JSON output.
+//!
+//! The `max_level_*` and `release_max_level_*` features (e.g. `release_max_level_info`)
+//! set a maximum log level at compile time. Records above it are removed entirely,
+//! from both the `log` macros and `ScopedLogger`, so disabled levels cost nothing.
+//!
+//! The `clap` feature adds the [`clap`](clap/index.html) module, with command line flags
+//! that convert into a `LogConfig`.
+//!
+//! The `metrics` feature reports pipeline metrics through the `metrics` crate, so they can be
+//! exported to Prometheus or similar: `ion_log_records_total` (by `level`),
+//! `ion_log_queue_depth`, `ion_log_dropped_total`, `ion_log_overflow_total`,
+//! `ion_log_bytes_written_total` and `ion_log_sink_errors_total` (by `sink`).
+//!
+//! The `anyhow` and `eyre` features add `ErrorReport`, which logs their errors with the full
+//! context chain and any captured backtrace.
+//!
+//! The `bench` feature adds the [`bench`](bench/index.html) module, which measures records
+//! per second and call latency for a config, to compare setups on the target hardware.
 /*===============================================================================================*/
 
 // Crate attributes
@@ -45,17 +69,334 @@
 #![deny (missing_docs)]
 
 // Create imports
+#[cfg (feature = "colour")]
 extern crate ansi_term;
+#[cfg (feature = "anyhow")]
+extern crate anyhow;
+#[cfg (feature = "clap")]
+extern crate clap as clap_crate;
+#[cfg (feature = "eyre")]
+extern crate eyre;
 extern crate log;
+#[cfg (feature = "metrics")]
+extern crate metrics as metrics_crate;
+#[cfg (feature = "regex")]
+extern crate regex;
+
+// Module declarations
+mod admin;
+mod audit;
+mod banner;
+#[cfg (feature = "bench")]
+pub mod bench;
+mod cbor;
+mod clock;
+mod env_config;
+mod environment;
+#[cfg (feature = "binary")]
+mod binary;
+mod error_chain;
+#[cfg (feature = "clap")]
+pub mod clap;
+mod expression;
+mod filter;
+mod format;
+mod health;
+mod heartbeat;
+#[cfg (feature = "binary")]
+mod json;
+#[cfg (feature = "binary")]
+mod index;
+mod metrics;
+mod mock;
+mod msgpack;
+mod pool;
+#[cfg (feature = "binary")]
+pub mod reader;
+mod recent;
+mod rotation;
+mod strings;
+mod style;
+pub mod test;
+mod timing;
+mod validate;
+mod zone;
 
 // Module imports
+#[cfg (feature = "colour")]
+use ansi_term::Style;
+#[cfg (feature = "colour")]
 use ansi_term::Colour::{Green, Blue, Purple, Yellow, Red};
+use admin::AdminServer;
+use audit::AuditLog;
+use health::SinkTracker;
+use heartbeat::HeartbeatThread;
+use pool::{FormatPool, FormatSettings, FormattedBatch};
+use recent::RecentErrors;
+use rotation::Rotator;
+use strings::{Message, Target};
+#[cfg (feature = "binary")]
+use index::IndexWriter;
+pub use admin::AdminEndpoint;
+pub use banner::SessionBanner;
+pub use clock::{Clock, MockClock, SystemClock};
+pub use env_config::ParseEnvError;
+pub use environment::Environment;
+pub use error_chain::ErrorChain;
+#[cfg (any (feature = "anyhow", feature = "eyre"))]
+pub use error_chain::ErrorReport;
+pub use expression::{FilterExpression, ParseExpressionError};
+pub use filter::{ModuleFilter, ParseFilterError};
+pub use format::{LevelName, Severity, JSON_SCHEMA};
+pub use health::{SinkHealth, SinkStatus};
+pub use heartbeat::Heartbeat;
+pub use mock::MockSink;
+pub use recent::RecentError;
+pub use rotation::{Rotation, RotationHook};
+#[cfg (feature = "regex")]
+pub use style::HighlightRule;
+pub use test::ChannelSink;
+pub use style::{TargetColour, TermColour, TermStyle};
+pub use timing::ScopeTimer;
 pub use log::LogLevelFilter as LogLevel;
+pub use validate::ConfigError;
 
 use std::boxed::Box;
-use std::fs::File;
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::VecDeque;
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write as FmtWrite;
+use std::fs::{self, File, OpenOptions};
+use std::hash::BuildHasher;
+use std::io;
 use std::io::BufWriter;
+use std::io::IsTerminal;
 use std::io::prelude::Write;
+use std::ops::Range;
+use std::panic::Location;
+use std::path::Path;
+use std::process;
+use std::mem;
+use std::sync::{Arc, Condvar, Mutex, OnceLock, RwLock};
+use std::sync::atomic::{self, AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle, Thread, ThreadId};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/*===============================================================================================*/
+/*------LOG FORMAT ENUM--------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// The format records are written to the log file in.
+#[derive (Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+
+    /// Plain text, one record per line.
+    Text,
+    /// JSON Lines, one object per record in the layout described by `JSON_SCHEMA`.
+    Json,
+    /// A compact binary format, which can be read back with `ion_log::reader`.
+    Binary,
+    /// MessagePack maps with the fields of the JSON layout, each framed by its length as a
+    /// big endian u32. The timestamp is microseconds since the Unix epoch.
+    /// Read back with `reader::MessagePackReader`.
+    MessagePack,
+    /// A CBOR sequence of maps with the fields of the JSON layout. The timestamp is
+    /// microseconds since the Unix epoch. Read back with `reader::CborReader`.
+    Cbor,
+}
+
+/*===============================================================================================*/
+/*------FILE ENCODING ENUM-----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// The character encoding of text and JSON log files. The binary formats ignore it.
+#[derive (Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileEncoding {
+
+    /// UTF-8, without a byte order mark.
+    Utf8,
+    /// UTF-8, starting with a byte order mark.
+    Utf8Bom,
+    /// UTF-16 little endian, starting with a byte order mark, as some Windows tools expect.
+    /// `ion_log::reader` can't read these files back.
+    Utf16Le,
+}
+
+/*===============================================================================================*/
+/*------OVERFLOW POLICY ENUM---------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// What logging does when the async queue is full, or over `LogConfig::queue_memory`.
+#[derive (Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+
+    /// Wait for the background thread to make room.
+    Block,
+    /// Drop the new record, and count it in `overflow_count`.
+    Drop,
+}
+
+/*===============================================================================================*/
+/*------LOAD SHEDDING STRUCT---------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// How full the async queue may get before records of each level are dropped, set with
+/// `LogConfig::load_shedding`. Warnings and errors are never shed.
+///
+/// Each threshold is a fraction of `LogConfig::async_capacity`, or of `queue_memory` if that
+/// is fuller. Shed records are counted in `overflow_count`.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let mut config = LogConfig::prod ("app.log");
+/// config.load_shedding = Some (LoadShedding {trace: 0.25, debug: 0.5, info: 0.9});
+/// ```
+#[derive (Copy, Clone, Debug, PartialEq)]
+pub struct LoadShedding {
+
+    // Public
+    /// The fill above which trace records are dropped.
+    pub trace: f32,
+    /// The fill above which debug records are dropped.
+    pub debug: f32,
+    /// The fill above which info records are dropped. 1.0 never sheds them.
+    pub info:  f32,
+}
+
+/*===============================================================================================*/
+/*------ASYNC BATCHING STRUCT--------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Bounds for the batches the background thread writes, set with `LogConfig::async_batching`.
+///
+/// The batch size adapts to the load. It doubles while records arrive faster than they are
+/// written, halves once batches come out less than half full, and drops back to
+/// `min_records` whenever the queue runs dry. A batch above
+/// `min_records` waits up to `max_delay` to fill, so bursts are written in fewer, larger
+/// writes, while a quiet logger writes each record as soon as it arrives.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// # use std::time::Duration;
+/// let mut config = LogConfig::prod ("app.log");
+/// config.async_batching = AsyncBatching {min_records: 1, max_records: 4096, max_delay: Duration::from_millis (10)};
+/// ```
+#[derive (Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AsyncBatching {
+
+    // Public
+    /// The smallest batch size, used at low throughput.
+    pub min_records: usize,
+    /// The largest batch size, reached under sustained bursts.
+    pub max_records: usize,
+    /// The longest a batch waits to fill once it's grown above `min_records`.
+    pub max_delay:   Duration,
+}
+
+/*===============================================================================================*/
+/*------ESCALATION STRUCT------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Raises the log level for a while after each error, set with `LogConfig::escalation`.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// # use std::time::Duration;
+/// let mut config = LogConfig::prod ("app.log");
+/// config.escalation = Some (Escalation {level: LogLevel::Debug, window: Duration::from_secs (60)});
+/// ```
+#[derive (Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Escalation {
+
+    // Public
+    /// The level to log at after an error.
+    pub level:  LogLevel,
+    /// How long after the last error to keep logging at `level`.
+    pub window: Duration,
+}
+
+/*===============================================================================================*/
+/*------LOCATION STYLE ENUM----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// How text lines show where a record was logged.
+#[derive (Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LocationStyle {
+
+    /// The module path and line, as in `[my_app::net::server - 123]`.
+    Module,
+    /// The source file and line, as in `src/net/server.rs:123:`, which the VS Code and
+    /// IntelliJ terminals turn into a link to the source.
+    File,
+    /// No location, for shipping builds where source paths must not reach users.
+    ///
+    /// The module path, file and line are dropped as each record is logged, so no output
+    /// sees them. JSON and binary records carry an empty module path and line 0, and
+    /// `LogConfig::source_links` is ignored. The `log` macros still embed the paths in
+    /// the binary.
+    Hidden,
+}
+
+/*===============================================================================================*/
+/*------INIT ERROR ENUM--------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Why `init` failed.
+#[derive (Debug)]
+pub enum InitError {
+
+    /// A logger is already installed. The `log` crate only takes one per process.
+    AlreadyInstalled (log::SetLoggerError),
+    /// A sink or background thread couldn't be started, such as when the log file can't be
+    /// created or the admin socket's address is taken.
+    Io (io::Error),
+}
+
+/*===============================================================================================*/
+/*------INIT ERROR TRAIT IMPLEMENTATIONS---------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl fmt::Display for InitError {
+
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        match *self {
+
+            InitError::AlreadyInstalled (ref e) => write! (f, "{}", e),
+            InitError::Io (ref e) => write! (f, "can't start the logger: {}", e),
+        }
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl Error for InitError {}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl From<log::SetLoggerError> for InitError {
+
+    fn from (e: log::SetLoggerError) -> Self {
+        InitError::AlreadyInstalled (e)
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl From<io::Error> for InitError {
+
+    fn from (e: io::Error) -> Self {
+        InitError::Io (e)
+    }
+}
 
 /*===============================================================================================*/
 /*------LOG CONFIG STRUCT------------------------------------------------------------------------*/
@@ -68,14 +409,199 @@ pub struct LogConfig {
     // Public
     /// Whether to log to the terminal.
     pub log_to_io:       bool,
+    /// Whether to turn terminal output off when stdout is closed or the process has no
+    /// controlling terminal, as for a daemon.
+    ///
+    /// This is checked at `init`, `reconfigure` and `post_fork_child`.
+    pub daemon_mode:     bool,
     /// Whether to log to a file.
     pub log_to_file:     bool,
     /// The log output file path.
+    ///
+    /// With `rotation`, this is always the file being written; only finished files are
+    /// renamed.
     pub log_output_path: String,
+    /// An already open file to log to instead of opening `log_output_path`, for processes
+    /// which give up filesystem access after startup. `log_to_file` must still be set.
+    ///
+    /// The file isn't truncated, and each `init` or `reconfigure` duplicates the handle, so
+    /// the config can be reused. The binary index is only written if `log_output_path` is set.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// # use std::sync::Arc;
+    /// # let path = std::env::temp_dir ().join ("ion_log_doc_handle.log");
+    /// let file = std::fs::File::create (&path).unwrap ();
+    ///
+    /// let mut config = LogConfig::new ();
+    /// config.log_to_file     = true;
+    /// config.log_file_handle = Some (Arc::new (file));
+    /// config.validate ().unwrap ();
+    /// ```
+    pub log_file_handle: Option<Arc<File>>,
+    /// The log output file format.
+    pub log_format:      LogFormat,
+    /// The character encoding of text and JSON log files.
+    ///
+    /// The byte order mark, if any, is written when the file is created empty.
+    pub file_encoding:   FileEncoding,
+    /// Whether to keep an index at `<log_output_path>.idx` when writing the binary format.
+    ///
+    /// The index is updated on each flush, and lets `reader::Index` seek by time and count
+    /// records without reading the whole log.
+    pub log_file_index:  bool,
+    /// When to start a new log file, renaming the current one. `None` keeps writing to one
+    /// file.
+    pub rotation:        Option<Rotation>,
+    /// How text lines show where a record was logged, on the terminal and in text files.
+    /// `LocationStyle::Hidden` leaves the location out of every output.
+    pub location_style:  LocationStyle,
+    /// Whether to write terminal output as JSON Lines, in the `JSON_SCHEMA` layout with the
+    /// fields log collectors expect. Colour and the other terminal styling is ignored.
+    pub terminal_json:   bool,
     /// Whether to use colour coded output.
+    ///
+    /// On Windows, this is ignored if the console can't handle ANSI escape codes. It is always
+    /// ignored without the `colour` feature.
     pub coloured_output: bool,
+    /// Colours for the location part of terminal lines, chosen by the most specific
+    /// matching target.
+    pub target_colours:  Vec<TargetColour>,
+    /// Styles for parts of terminal messages matching a regex. Files are never highlighted.
+    #[cfg (feature = "regex")]
+    pub highlights:      Vec<HighlightRule>,
+    /// Whether to draw debug and trace records faint on the terminal, so the eye goes to
+    /// the more important ones. Needs coloured output.
+    pub dim_low_levels:  bool,
+    /// Other names to write levels under in text output, chosen by the most specific
+    /// matching target.
+    pub level_names:     Vec<LevelName>,
     /// The maximum log level.
     pub max_log_level:   LogLevel,
+    /// Per-module overrides of the maximum log level.
+    pub module_filters:  Vec<ModuleFilter>,
+    /// Targets whose records are always dropped, matched like module filters.
+    pub blocked_targets: Vec<String>,
+    /// Targets marked with `opt_in_targets` which should be logged, matched like module filters.
+    pub allowed_targets: Vec<String>,
+    /// An expression records must also match to be logged.
+    pub record_filter:   Option<FilterExpression>,
+    /// An expression records must match to be written to the terminal.
+    ///
+    /// Together with `file_filter`, this can route records by tag, as in `tag == "net"`.
+    pub terminal_filter: Option<FilterExpression>,
+    /// An expression records must match to be written to the log file.
+    pub file_filter:     Option<FilterExpression>,
+    /// Whether to write records from a background thread.
+    ///
+    /// Each producing thread queues records in a buffer of its own, which the background thread
+    /// sweeps and merges in timestamp order, so threads only meet on the queue's depth count.
+    /// See the baseline in `bench`.
+    pub async_output:    bool,
+    /// The number of records the background queue can hold before `overflow_policy` applies.
+    pub async_capacity:  usize,
+    /// The bounds for adapting the background thread's batch size to the load.
+    pub async_batching:  AsyncBatching,
+    /// The number of threads serializing JSON, MessagePack and CBOR file records in large
+    /// async batches, so a slow serialization doesn't hold up the writes. A batch of at least
+    /// 64 records is serialized by these threads while the background thread writes the batch
+    /// before it, and is split so each thread gets at least 64. Zero or one serializes every
+    /// record on the background thread.
+    pub format_threads:  usize,
+    /// The most memory, in bytes, records waiting in the background queue or in the batch
+    /// being written may use before `overflow_policy` applies. Zero for no limit.
+    ///
+    /// A single record larger than the whole budget is still queued once the queue is empty.
+    pub queue_memory:    usize,
+    /// What logging does when the background queue is full or over `queue_memory`.
+    ///
+    /// Session banner records always wait for room.
+    pub overflow_policy: OverflowPolicy,
+    /// Drops trace, then debug, then info records as the background queue fills, before
+    /// `overflow_policy` applies.
+    pub load_shedding:   Option<LoadShedding>,
+    /// The longest `release` waits for the background thread to write out the queue.
+    /// `None` waits for every record.
+    ///
+    /// Records still queued when it passes are dropped, and counted by `try_release`.
+    pub drain_timeout:   Option<Duration>,
+    /// Describes the application in a record written at `init`, and another at `release`.
+    ///
+    /// These records are always written, whatever the filters.
+    pub session_banner:  Option<SessionBanner>,
+    /// The audit log path, appended to by `audit!`. Empty for no audit log.
+    pub audit_log_path:  String,
+    /// Whether to start each text line with the session ID.
+    ///
+    /// The binary format always records it.
+    pub session_in_text: bool,
+    /// The application name, added to every record. Empty for none.
+    ///
+    /// Text lines start with `app_name[instance_id]`, and the binary format stores both.
+    pub app_name:        String,
+    /// Identifies this instance of the application, such as a host or worker name.
+    /// Empty for none.
+    pub instance_id:     String,
+    /// The clock records are timestamped with. Tests can use a `MockClock`.
+    pub clock:           Arc<dyn Clock>,
+    /// A sink for fault injection tests, which also receives every record.
+    pub mock_sink:       Option<MockSink>,
+    /// Sends each record to the channel subscribed by the thread which logged it, for tests
+    /// to assert on. See `ion_log::test::channel`.
+    pub channel_sink:    Option<ChannelSink>,
+    /// Whether to shorten a multi-line message, such as a backtrace, which repeats the last
+    /// multi-line message. The repeat is written as its first line and a count, as in
+    /// `thread 'main' panicked (same as above, 14x)`.
+    ///
+    /// Only text output is shortened, binary and JSON files keep the full message.
+    pub fold_repeats:    bool,
+    /// A URL template which makes the location in terminal lines an OSC 8 hyperlink, for
+    /// terminals which support them. `{file}` and `{line}` are replaced by the source file,
+    /// relative to its crate, and the line, as in `file:///home/me/my_app/{file}`.
+    ///
+    /// Links are only written when stdout is a terminal.
+    pub source_links:    Option<String>,
+    /// Keeps debug and trace records back in memory instead of writing them. When an error is
+    /// logged, the records from this long before it are written first, giving the context of
+    /// the error without the volume of logging everything. They come out just before the
+    /// error, so after any info and warning records logged since. Binary logs note the window,
+    /// so `reader::Reader::range` still finds records written that late. The recorded records
+    /// are kept across `reconfigure`.
+    ///
+    /// Debug and trace records must still pass the level filters to be kept. At most
+    /// 10,000 are kept, the oldest going first.
+    pub flight_recorder: Option<Duration>,
+    /// Logs at a more verbose level for a while after each error, so an incident captures
+    /// more detail. Blocked targets stay blocked.
+    pub escalation:      Option<Escalation>,
+    /// How many of the latest warning and error records `ion_log::recent_errors` can return.
+    /// Zero to keep none.
+    pub recent_errors:   usize,
+    /// Listens for commands that adjust the running logger, one per line, so an operator can
+    /// change logging with `nc` or `socat`:
+    ///
+    /// | Command               | Effect                                                    |
+    /// |-----------------------|-----------------------------------------------------------|
+    /// | `level <level>`       | Sets `max_log_level`.                                     |
+    /// | `filter add <list>`   | Applies filter directives, as for `parse_filters`.        |
+    /// | `filter clear`        | Removes every module filter.                              |
+    /// | `flush`               | Flushes every sink.                                       |
+    /// | `rotate`              | Starts a new log file, as for `ion_log::rotate`.          |
+    /// | `stats`               | Lists the session, levels, filters, overflows and sinks.  |
+    /// | `help`                | Lists the commands.                                       |
+    ///
+    /// Each command is answered with any output and then `ok`, or with `error: <reason>`.
+    /// Changes are made with `reconfigure`, so last until the next call to it. There is no
+    /// authentication; anyone who can connect can change the logging. A Unix socket only lets
+    /// its owner connect, but a TCP port is open to every local user.
+    pub admin_socket:    Option<AdminEndpoint>,
+    /// Makes `fatal! (exit: code, ...)` panic after flushing, instead of shutting the logger
+    /// down and exiting the process, so tests and code that catches panics can handle it.
+    pub fatal_panics:    bool,
+    /// Logs an info record at a regular interval, with the uptime, the records logged since
+    /// the last one and the async queue depth.
+    pub heartbeat:       Option<Heartbeat>,
 }
 
 /*===============================================================================================*/
@@ -108,137 +634,4198 @@ impl LogConfig {
         LogConfig {
 
             log_to_io:       true,
+            daemon_mode:     false,
             log_to_file:     false,
             log_output_path: String::new (),
+            log_file_handle: None,
+            log_format:      LogFormat::Text,
+            file_encoding:   FileEncoding::Utf8,
+            log_file_index:  false,
+            rotation:        None,
+            location_style:  LocationStyle::Module,
+            terminal_json:   false,
             coloured_output: true,
+            target_colours:  Vec::new (),
+            #[cfg (feature = "regex")]
+            highlights:      Vec::new (),
+            dim_low_levels:  false,
+            level_names:     Vec::new (),
             max_log_level:   LogLevel::Trace,
+            module_filters:  Vec::new (),
+            blocked_targets: Vec::new (),
+            allowed_targets: Vec::new (),
+            record_filter:   None,
+            terminal_filter: None,
+            file_filter:     None,
+            async_output:    false,
+            async_capacity:  1024,
+            async_batching:  AsyncBatching {min_records: 1, max_records: 256, max_delay: Duration::from_millis (2)},
+            format_threads:  0,
+            queue_memory:    0,
+            overflow_policy: OverflowPolicy::Block,
+            load_shedding:   None,
+            drain_timeout:   None,
+            session_banner:  None,
+            audit_log_path:  String::new (),
+            session_in_text: false,
+            app_name:        String::new (),
+            instance_id:     String::new (),
+            clock:           Arc::new (SystemClock),
+            mock_sink:       None,
+            channel_sink:    None,
+            fold_repeats:    false,
+            source_links:    None,
+            flight_recorder: None,
+            escalation:      None,
+            recent_errors:   0,
+            admin_socket:    None,
+            fatal_panics:    false,
+            heartbeat:       None,
         }
     }
-}
-
-/*===============================================================================================*/
-/*------LOGGER STRUCT----------------------------------------------------------------------------*/
-/*===============================================================================================*/
-
-struct Logger {
 
-    // Private
-    config: LogConfig,
-    log_output_buffer: Option<BufWriter<File>>,
-}
+/*-----------------------------------------------------------------------------------------------*/
 
-/*===============================================================================================*/
-/*------LOGGER TRAIT IMPLEMENTATIONS-------------------------------------------------------------*/
-/*===============================================================================================*/
+    /// Returns a config for development: coloured terminal output at every level, and no
+    /// log file.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// let config = LogConfig::dev ();
+    /// assert_eq! (config.max_log_level, LogLevel::Trace);
+    /// assert! (!config.log_to_file);
+    /// ```
+    pub fn dev () -> Self {
 
-impl log::Log for Logger {
+        LogConfig {
 
-    fn enabled (&self, metadata: &log::LogMetadata) -> bool {
-        metadata.level () <= self.config.max_log_level
+            log_to_io:       true,
+            log_to_file:     false,
+            coloured_output: true,
+            max_log_level:   LogLevel::Trace,
+            ..LogConfig::new ()
+        }
     }
 
 /*-----------------------------------------------------------------------------------------------*/
 
-    fn log (&self, record: &log::LogRecord) {
+    /// Returns a config for production: info and above, written to a JSON Lines file and to
+    /// the terminal without colour. Terminal output is dropped when running as a daemon.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// let config = LogConfig::prod ("app.log");
+    /// assert_eq! (config.log_format, LogFormat::Json);
+    /// assert_eq! (config.max_log_level, LogLevel::Info);
+    /// ```
+    pub fn prod (log_output_path: &str) -> Self {
 
-        let log_string = self.format_log_string (record);
+        LogConfig {
+
+            log_to_io:       true,
+            daemon_mode:     true,
+            log_to_file:     true,
+            log_output_path: log_output_path.to_string (),
+            log_format:      LogFormat::Json,
+            coloured_output: false,
+            max_log_level:   LogLevel::Info,
+            ..LogConfig::new ()
+        }
+    }
 
-        if self.config.log_to_io {
+/*-----------------------------------------------------------------------------------------------*/
 
-            if self.config.coloured_output {
-                println! ("{}", self.format_log_colour (record, &log_string));
-            }
+    /// Returns a config for running in a container, as a twelve-factor app: info and above,
+    /// written to stdout as JSON Lines for the platform's log collector, with no colour and
+    /// no log file.
+    ///
+    /// Each record is written to stdout as soon as it is logged, whether or not there is a
+    /// terminal.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use ion_log::*;
+    /// ion_log::init (&LogConfig::container ()).unwrap ();
+    /// ```
+    pub fn container () -> Self {
 
-            else {
-                println! ("{}", log_string);
-            }
-        }
+        LogConfig {
 
-        if self.config.log_to_file {
-            self.log_output_buffer.as_ref ().unwrap ().get_ref ().write (log_string.as_bytes ()).unwrap ();
+            log_to_io:       true,
+            daemon_mode:     false,
+            log_to_file:     false,
+            terminal_json:   true,
+            coloured_output: false,
+            max_log_level:   LogLevel::Info,
+            async_output:    false,
+            ..LogConfig::new ()
         }
     }
-}
 
-/*===============================================================================================*/
-/*------LOGGER PRIVATE METHODS-------------------------------------------------------------------*/
-/*===============================================================================================*/
+/*-----------------------------------------------------------------------------------------------*/
 
-impl Logger {
+    /// Returns a config suited to the environment:
+    ///
+    /// * `Kubernetes` uses `LogConfig::container`.
+    /// * `Systemd` writes info and above to stdout as text without colour, for the journal.
+    /// * `Ci` writes debug and above to stdout as text without colour.
+    /// * `Terminal` uses `LogConfig::dev`.
+    /// * `Other` writes info and above to stdout as text without colour.
+    ///
+    /// The result is a starting point, and any field can be changed before `init`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// let mut config = LogConfig::for_environment (Environment::detect ());
+    /// config.app_name = "my_app".to_string ();
+    ///
+    /// assert! (LogConfig::for_environment (Environment::Kubernetes).terminal_json);
+    /// ```
+    pub fn for_environment (environment: Environment) -> Self {
+
+        let plain = |max_log_level| LogConfig {
+
+            log_to_io:       true,
+            daemon_mode:     false,
+            log_to_file:     false,
+            coloured_output: false,
+            max_log_level,
+            ..LogConfig::new ()
+        };
 
-    fn format_log_string (&self, record: &log::LogRecord) -> String {
+        match environment {
 
-        let log_string = format! ("[{} - {}] {}: {}\n",
-                                  record.location ().module_path (),
-                                  record.location ().line (),
-                                  record.level (),
-                                  record.args ());
-        log_string
+            Environment::Kubernetes => LogConfig::container (),
+            Environment::Systemd    => plain (LogLevel::Info),
+            Environment::Ci         => plain (LogLevel::Debug),
+            Environment::Terminal   => LogConfig::dev (),
+            Environment::Other      => plain (LogLevel::Info),
+        }
     }
 
 /*-----------------------------------------------------------------------------------------------*/
 
-    fn format_log_colour (&self, record: &log::LogRecord, log_string: &str) -> String {
+    /// Applies a comma separated list of level filter directives.
+    ///
+    /// A bare level sets `max_log_level`, `module=level` adds a module filter, and a bare
+    /// module name logs everything from that module. The most specific module filter applies,
+    /// and a filter for `a::b` also covers `a::b::c`.
+    ///
+    /// Module names may contain `*` wildcards, as in `my_app::net::*` or `*::ffi`.
+    ///
+    /// # Errors
+    /// Fails without changing the config if a directive or level can't be parsed.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// let mut config = LogConfig::new ();
+    /// config.parse_filters ("info,sqlx=warn,my_app::gpu=trace").unwrap ();
+    ///
+    /// assert_eq! (config.max_log_level, LogLevel::Info);
+    /// assert_eq! (config.module_filters.len (), 2);
+    ///
+    /// config.parse_filters ("*::ffi=off").unwrap ();
+    /// ```
+    pub fn parse_filters (&mut self, directives: &str) -> Result<(), ParseFilterError> {
 
-        match record.level () {
+        let (default_level, mut filters) = filter::parse (directives)?;
 
-            log::LogLevel::Trace => Green.paint  (log_string),
-            log::LogLevel::Debug => Blue.paint   (log_string),
-            log::LogLevel::Info  => Purple.paint (log_string),
-            log::LogLevel::Warn  => Yellow.paint (log_string),
-            log::LogLevel::Error => Red.paint    (log_string)
+        if let Some (level) = default_level {
+            self.max_log_level = level;
+        }
 
-        }.to_string ()
+        self.module_filters.append (&mut filters);
+        Ok (())
     }
-}
 
-/*===============================================================================================*/
-/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
-/*===============================================================================================*/
+/*-----------------------------------------------------------------------------------------------*/
 
-/// Initializes the logger.
-///
-/// # Examples
-/// ```
-/// # use ion_log::*;
-/// let config = LogConfig::new ();
-/// ion_log::init (&config).unwrap ();
-/// # ion_log::release ();
-/// ```
-pub fn init (config: &LogConfig) -> Result<(), log::SetLoggerError> {
+    /// Applies filter directives from the `ION_LOG` environment variable, if it is set.
+    ///
+    /// The variable uses the same syntax as `parse_filters`. `apply_env` reads it along with
+    /// the other `ION_LOG_*` variables.
+    ///
+    /// # Errors
+    /// Fails if the variable holds an invalid directive.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// let mut config = LogConfig::new ();
+    /// config.parse_env ().unwrap ();
+    /// ion_log::init (&config).unwrap ();
+    /// # ion_log::release ();
+    /// ```
+    pub fn parse_env (&mut self) -> Result<(), ParseFilterError> {
 
-    log::set_logger (|max_log_level| {
+        match env::var ("ION_LOG") {
 
-        max_log_level.set (config.max_log_level);
+            Ok (directives) => self.parse_filters (&directives),
+            Err (_) => Ok (()),
+        }
+    }
 
-        Box::new (Logger {
+/*-----------------------------------------------------------------------------------------------*/
 
-            config: config.clone (),
-            log_output_buffer: {
+    /// Applies overrides from the `ION_LOG*` environment variables which are set, so a
+    /// deployment can be reconfigured without changing code.
+    ///
+    /// | Variable                  | Sets                                                       |
+    /// |---------------------------|------------------------------------------------------------|
+    /// | `ION_LOG`                 | Filter directives, as for `parse_filters`.                 |
+    /// | `ION_LOG_LEVEL`           | `max_log_level`.                                           |
+    /// | `ION_LOG_FILE`            | `log_output_path`, and `log_to_file` unless it's empty.    |
+    /// | `ION_LOG_FORMAT`          | `log_format`: `text`, `json`, `binary`, `msgpack`, `cbor`. |
+    /// | `ION_LOG_ENCODING`        | `file_encoding`: `utf-8`, `utf-8-bom` or `utf-16le`.       |
+    /// | `ION_LOG_INDEX`           | `log_file_index`.                                          |
+    /// | `ION_LOG_TERMINAL`        | `log_to_io`.                                               |
+    /// | `ION_LOG_TERMINAL_FORMAT` | `terminal_json`: `text` or `json`.                         |
+    /// | `ION_LOG_COLOR`           | `coloured_output`. `ION_LOG_COLOUR` also works.            |
+    /// | `ION_LOG_LOCATION`        | `location_style`: `module`, `file` or `hidden`.            |
+    /// | `ION_LOG_ASYNC`           | `async_output`.                                            |
+    /// | `ION_LOG_ASYNC_CAPACITY`  | `async_capacity`.                                          |
+    /// | `ION_LOG_AUDIT_FILE`      | `audit_log_path`.                                          |
+    /// | `ION_LOG_APP_NAME`        | `app_name`.                                                |
+    /// | `ION_LOG_INSTANCE_ID`     | `instance_id`.                                             |
+    ///
+    /// Switches accept `1`, `true`, `yes`, `on` or `always`, and `0`, `false`, `no`, `off` or
+    /// `never`.
+    ///
+    /// # Errors
+    /// Fails without changing the config if a variable has an invalid value.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// std::env::set_var ("ION_LOG_FORMAT", "json");
+    /// std::env::set_var ("ION_LOG_COLOR", "off");
+    ///
+    /// let mut config = LogConfig::new ();
+    /// config.apply_env ().unwrap ();
+    ///
+    /// assert_eq! (config.log_format, LogFormat::Json);
+    /// assert! (!config.coloured_output);
+    /// ```
+    pub fn apply_env (&mut self) -> Result<(), ParseEnvError> {
 
-                if config.log_to_file {
-                    Some (BufWriter::new (File::create (&config.log_output_path).unwrap ()))
-                }
+        let mut config = self.clone ();
 
-                else {
-                    None
-                }
-            }
-        })
-    })
-}
+        env_config::apply (&mut config)?;
+        *self = config;
+        Ok (())
+    }
 
 /*-----------------------------------------------------------------------------------------------*/
 
-/// Releases the logger.
-///
-/// # Examples
-/// ```
-/// # use ion_log::*;
-/// let config = LogConfig::new ();
-/// ion_log::init (&config).unwrap ();
-/// ion_log::release ();
-/// ```
-pub fn release () {
-    drop (log::shutdown_logger ().unwrap ());
+    /// Returns the maximum log level for a target: the level of the most specific module
+    /// filter matching it, or `max_log_level` if none does.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// let mut config = LogConfig::new ();
+    /// config.parse_filters ("info,my_app::gpu=trace").unwrap ();
+    ///
+    /// assert_eq! (config.level_for ("my_app::gpu::shaders"), LogLevel::Trace);
+    /// assert_eq! (config.level_for ("my_app::audio"), LogLevel::Info);
+    /// ```
+    pub fn level_for (&self, target: &str) -> LogLevel {
+        filter::level_for (&self.module_filters, target).unwrap_or (self.max_log_level)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Checks the config for problems that would otherwise only show up in `init`, or not at all.
+    ///
+    /// This catches file logging without an output path, a log file or audit log that can't be
+    /// written to, a rotation that can't or won't happen, malformed module names in
+    /// `module_filters`, `blocked_targets` and `allowed_targets`, and an async queue with no
+    /// capacity. Nothing is created on disk.
+    ///
+    /// # Errors
+    /// Returns every problem found, not just the first.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// let mut config = LogConfig::new ();
+    /// config.log_to_file = true;
+    /// config.blocked_targets.push ("hyper::".to_string ());
+    ///
+    /// let errors = config.validate ().unwrap_err ();
+    ///
+    /// assert_eq! (errors, vec! [ConfigError::MissingOutputPath,
+    ///                           ConfigError::InvalidBlockedTarget ("hyper::".to_string ())]);
+    /// ```
+    pub fn validate (&self) -> Result<(), Vec<ConfigError>> {
+
+        let errors = validate::validate (self);
+
+        if errors.is_empty () {Ok (())} else {Err (errors)}
+    }
+}
+
+/*===============================================================================================*/
+/*------LOG LEVEL EXT TRAIT----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Extra constructors for `LogLevel`.
+pub trait LogLevelExt {
+
+    /// Returns the level for a command line verbosity.
+    ///
+    /// `count` is the number of `-v` flags: none gives `Info`, one `Debug` and two or more `Trace`.
+    /// `quiet` (`-q`) overrides it with `Error`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// assert_eq! (LogLevel::from_verbosity (0, false), LogLevel::Info);
+    /// assert_eq! (LogLevel::from_verbosity (2, false), LogLevel::Trace);
+    /// assert_eq! (LogLevel::from_verbosity (2, true),  LogLevel::Error);
+    /// ```
+    fn from_verbosity (count: u8, quiet: bool) -> Self;
+}
+
+/*===============================================================================================*/
+/*------LOG LEVEL EXT TRAIT IMPLEMENTATIONS------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl LogLevelExt for LogLevel {
+
+    fn from_verbosity (count: u8, quiet: bool) -> Self {
+
+        match (quiet, count) {
+
+            (true, _) => LogLevel::Error,
+            (_, 0)    => LogLevel::Info,
+            (_, 1)    => LogLevel::Debug,
+            _         => LogLevel::Trace,
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------LOGGER STRUCT----------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// The active logger state, shared between the installed logger and `reconfigure`.
+static LOGGER: RwLock<Option<Logger>> = RwLock::new (None);
+
+// Stands in for record locations with `LocationStyle::Hidden`.
+const HIDDEN_LOCATION: log::LogLocation = log::LogLocation {__module_path: "", __file: "", __line: 0};
+
+// The targets libraries have marked with `opt_in_targets`.
+static OPT_IN_TARGETS: RwLock<Vec<String>> = RwLock::new (Vec::new ());
+
+// The number of records dropped by `OverflowPolicy::Drop`, or because the background thread
+// had stopped.
+static OVERFLOWS: AtomicUsize = AtomicUsize::new (0);
+
+// Numbers each async queue, so a thread can tell whether its producer belongs to the current one.
+static NEXT_QUEUE_ID: AtomicUsize = AtomicUsize::new (0);
+
+thread_local! {
+
+    // Reused for formatting messages in synchronous mode, so logging doesn't allocate.
+    static FORMAT_BUFFER: RefCell<String> = const {RefCell::new (String::new ())};
+
+    // The trace context set with `set_trace_context`.
+    static TRACE_CONTEXT: RefCell<Option<TraceContext>> = const {RefCell::new (None)};
+
+    // The tags from enclosing `tagged!` calls, outermost first.
+    static TAGS: RefCell<Vec<&'static str>> = const {RefCell::new (Vec::new ())};
+
+    // This thread's producer for the async queue, with the id of the queue it belongs to.
+    static PRODUCER: RefCell<Option<(usize, Arc<Producer>)>> = const {RefCell::new (None)};
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+struct Logger {
+
+    // Private
+    config: LogConfig,
+    output: Arc<Mutex<LogOutput>>,
+    writer: Option<AsyncWriter>,
+    max_log_level: log::MaxLogLevelFilter,
+    started: SystemTime,
+    audit_log: Option<AuditLog>,
+    escalated_until: AtomicU64,
+    recent_errors: RecentErrors,
+    admin: Option<AdminServer>,
+    heartbeat: Option<HeartbeatThread>,
+    records: AtomicU64,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// The logger handed to the log crate, which forwards to the active state.
+struct GlobalLogger;
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// A record captured from the log crate, as seen by the sinks.
+struct Entry<'a> {
+
+    // Private
+    level: log::LogLevel,
+    target: &'a str,
+    location: log::LogLocation,
+    thread: ThreadId,
+    timestamp: SystemTime,
+    message: &'a str,
+    trace_context: Option<&'a TraceContext>,
+    tags: &'a [&'static str],
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// An `Entry` which owns its strings, so it can be queued for the background thread.
+struct QueuedEntry {
+
+    // Private
+    level: log::LogLevel,
+    target: Target,
+    location: log::LogLocation,
+    thread: ThreadId,
+    timestamp: SystemTime,
+    message: Message,
+    trace_context: Option<TraceContext>,
+    tags: Vec<&'static str>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// The distributed trace a thread is currently working on behalf of.
+#[derive (Clone)]
+struct TraceContext {
+
+    // Private
+    trace_id: String,
+    span_id: String,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// The sinks that entries are written to.
+struct LogOutput {
+
+    // Private
+    log_to_io: bool,
+    #[cfg (feature = "colour")]
+    coloured_output: bool,
+    #[cfg (feature = "colour")]
+    target_colours: Vec<TargetColour>,
+    #[cfg (feature = "colour")]
+    highlighter: style::Highlighter,
+    #[cfg (feature = "colour")]
+    dim_low_levels: bool,
+    session_id: String,
+    session_in_text: bool,
+    terminal_json: bool,
+    location_style: LocationStyle,
+    level_names: Vec<Vec<LevelName>>,
+    app_prefix: String,
+    app_name: Option<String>,
+    instance_id: Option<String>,
+    terminal_filter: Option<FilterExpression>,
+    file_filter: Option<FilterExpression>,
+    log_file: Option<LogFile>,
+    rotation_config: Option<LogConfig>,
+    mock_sink: Option<MockSink>,
+    channel_sink: Option<ChannelSink>,
+    fold_repeats: bool,
+    source_links: Option<String>,
+    flight_recorder: Option<Duration>,
+    recorded: VecDeque<QueuedEntry>,
+    repeated_text: String,
+    repeats: usize,
+    line_buffer: String,
+    fold_buffer: String,
+    link_buffer: String,
+    terminal_buffer: String,
+    file_buffer: Vec<u8>,
+    json_buffer: String,
+    mock_buffer: String,
+    terminal_health: SinkTracker,
+    file_health: SinkTracker,
+    mock_health: SinkTracker,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// An open output file, with its encoder and index when writing the binary format.
+struct LogFile {
+
+    // Private
+    buffer: BufWriter<File>,
+    format: LogFormat,
+    encoding: FileEncoding,
+    rotator: Option<Rotator>,
+    size: u64,
+    header_size: u64,
+    #[cfg (feature = "binary")]
+    encoder: Option<binary::Encoder>,
+    #[cfg (feature = "binary")]
+    index: Option<IndexWriter>,
+    #[cfg (feature = "binary")]
+    reorder: Duration,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// The maximum number of records the flight recorder keeps back.
+const FLIGHT_RECORDER_CAPACITY: usize = 10_000;
+
+// How often `release` checks whether the background thread has written out the queue.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis (1);
+
+// The longest a thread waiting for room in a full queue goes without checking again.
+const ROOM_POLL_INTERVAL: Duration = Duration::from_millis (10);
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// The background thread used for asynchronous output, and the bounded queue feeding it.
+struct AsyncWriter {
+
+    // Private
+    queue: Arc<AsyncQueue>,
+    thread: JoinHandle<()>,
+    abandon: Arc<AtomicBool>,
+    load_shedding: Option<LoadShedding>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// The records waiting for the background thread. Each logging thread adds to a producer of
+// its own, which the background thread sweeps, so logging threads don't contend with each
+// other to queue a record.
+struct AsyncQueue {
+
+    // Private
+    id: usize,
+    capacity: usize,
+    budget: Option<QueueBudget>,
+    producers: Mutex<Vec<Arc<Producer>>>,
+    flushes: Mutex<Vec<Sender<io::Result<()>>>>,
+    writer: OnceLock<Thread>,
+    room: Mutex<()>,
+    freed: Condvar,
+    waiting: AtomicUsize,
+    closed: AtomicBool,
+    stopped: AtomicBool,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// A logging thread's records, waiting for the background thread. The lock is only ever
+// shared with the background thread's sweeps.
+struct Producer {
+
+    // Private
+    records: Mutex<Vec<QueuedEntry>>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// The buffers the background thread swaps in for the ones it sweeps, so sweeping doesn't
+// allocate once each producer's buffer has grown to fit its bursts.
+#[derive (Default)]
+struct Sweep {
+
+    // Private
+    spare: Vec<Vec<QueuedEntry>>,
+    taken: Vec<Vec<QueuedEntry>>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// The records the background thread has swept but not yet batched. If the thread panics,
+// they're counted as overflows.
+struct Swept<'a> {
+
+    // Private
+    queue: &'a AsyncQueue,
+    records: VecDeque<QueuedEntry>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Marks the queue stopped when the background thread ends, even by panicking, so records
+// logged after it are dropped rather than left waiting. A panic while writing leaves the
+// output usable for the logger's own flushes and release.
+struct WriterGuard<'a> {
+
+    // Private
+    queue: &'a AsyncQueue,
+    output: &'a Mutex<LogOutput>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// The memory used by queued and batched records, for `LogConfig::queue_memory`.
+struct QueueBudget {
+
+    // Private
+    limit: usize,
+    used: Mutex<usize>,
+    freed: Condvar,
+}
+
+/*===============================================================================================*/
+/*------LOGGER TRAIT IMPLEMENTATIONS-------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl log::Log for Logger {
+
+    fn enabled (&self, metadata: &log::LogMetadata) -> bool {
+        self.level_enabled (metadata.level (), metadata.target ())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn log (&self, record: &log::LogRecord) {
+
+        if !self.enabled (record.metadata ()) {
+            return;
+        }
+
+        let timestamp = self.config.clock.now ();
+
+        if record.level () == log::LogLevel::Error {
+            self.escalate (timestamp);
+        }
+
+        match self.writer {
+
+            Some (ref writer) => {
+
+                // Shed before formatting, so an overloaded logger does as little as it can.
+                if writer.shed (record.level ()) {
+                    return;
+                }
+
+                let message = Message::format (*record.args ());
+                let tags = TAGS.with (|tags| tags.borrow ().clone ());
+
+                if !self.passes_record_filter (record, message.as_str (), &tags) {
+                    return;
+                }
+
+                let location = self.location (record.location ());
+
+                self.recent_errors.add (record, &location, message.as_str (), timestamp);
+                self.records.fetch_add (1, Ordering::Relaxed);
+
+                metrics::record (record.level ());
+
+                let entry = QueuedEntry {
+
+                    level:    record.level (),
+                    target:   strings::intern_target (record.target ()),
+                    location,
+                    thread:   thread::current ().id (),
+                    timestamp,
+                    message,
+                    trace_context: TRACE_CONTEXT.with (|context| context.borrow ().clone ()),
+                    tags,
+                };
+
+                writer.send (entry, self.config.overflow_policy);
+            },
+
+            None => FORMAT_BUFFER.with (|buffer| {
+
+                // Fall back to a fresh string if a record is logged while formatting another one.
+                let mut fallback = String::new ();
+                let mut borrowed = buffer.try_borrow_mut ();
+
+                let message = match borrowed {
+
+                    Ok (ref mut buffer) => &mut **buffer,
+                    Err (_) => &mut fallback,
+                };
+
+                message.clear ();
+                write! (message, "{}", record.args ()).unwrap ();
+
+                TAGS.with (|tags| TRACE_CONTEXT.with (|context| {
+
+                    let tags = tags.borrow ();
+
+                    if !self.passes_record_filter (record, message, &tags) {
+                        return;
+                    }
+
+                    let location = self.location (record.location ());
+
+                    self.recent_errors.add (record, &location, message, timestamp);
+                    self.records.fetch_add (1, Ordering::Relaxed);
+
+                    metrics::record (record.level ());
+
+                    let context = context.borrow ();
+                    let entry = Entry {
+
+                        level:    record.level (),
+                        target:   record.target (),
+                        location,
+                        thread:   thread::current ().id (),
+                        timestamp,
+                        message,
+                        trace_context: context.as_ref (),
+                        tags:     &tags,
+                    };
+
+                    if self.output.lock ().unwrap ().write (&entry).is_err () {
+                        metrics::dropped (1);
+                    }
+                }));
+            }),
+        }
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl log::Log for GlobalLogger {
+
+    fn enabled (&self, metadata: &log::LogMetadata) -> bool {
+
+        match *LOGGER.read ().unwrap () {
+
+            Some (ref logger) => logger.enabled (metadata),
+            None => false,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn log (&self, record: &log::LogRecord) {
+
+        if let Some (ref logger) = *LOGGER.read ().unwrap () {
+            logger.log (record);
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------LOGGER PRIVATE METHODS-------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Logger {
+
+    fn new (config: &LogConfig, max_log_level: log::MaxLogLevelFilter) -> io::Result<Self> {
+
+        max_log_level.set (Logger::most_verbose_level (config));
+        metrics::describe ();
+
+        let output = Arc::new (Mutex::new (LogOutput::new (config, new_session_id (), None)?));
+
+        let logger = Logger {
+
+            config: config.clone (),
+            writer: AsyncWriter::start (config, &output)?,
+            output,
+            max_log_level,
+            started: config.clock.now (),
+            audit_log: Logger::open_audit_log (config, None)?,
+            escalated_until: AtomicU64::new (0),
+            recent_errors: RecentErrors::new (config.recent_errors),
+            admin: Logger::start_admin (config)?,
+            heartbeat: Logger::start_heartbeat (config)?,
+            records: AtomicU64::new (0),
+        };
+
+        if let Some (ref banner) = config.session_banner {
+            logger.log_session (banner::header (banner, config, logger.started));
+        }
+
+        Ok (logger)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn reconfigure (&mut self, config: &LogConfig) -> io::Result<()> {
+
+        // Drain anything still queued against the old sinks first.
+        if let Some (writer) = self.writer.take () {
+            writer.stop ();
+        }
+
+        {
+            let mut output = self.output.lock ().unwrap ();
+
+            let same_handle = match (&config.log_file_handle, &self.config.log_file_handle) {
+
+                (Some (handle), Some (current)) => Arc::ptr_eq (handle, current),
+                (None, None) => true,
+                _ => false,
+            };
+
+            // Keep the current file open if it hasn't changed, so it isn't truncated. A new
+            // encoding or index setting only takes effect on a new file, but a new rotation
+            // applies to the current one.
+            let keep_file = config.log_to_file &&
+                            self.config.log_to_file &&
+                            config.log_output_path == self.config.log_output_path &&
+                            same_handle &&
+                            config.log_format == self.config.log_format &&
+                            config.file_encoding == self.config.file_encoding &&
+                            config.log_file_index == self.config.log_file_index;
+
+            let mut log_file = if keep_file {output.log_file.take ()} else {None};
+
+            if let Some (ref mut log_file) = log_file {
+                log_file.set_rotation (config);
+            }
+
+            let session_id = output.session_id.clone ();
+            let recorded = mem::take (&mut output.recorded);
+
+            *output = LogOutput::new (config, session_id, log_file)?;
+
+            // The flight recorder keeps its context, if it's still on.
+            if output.flight_recorder.is_some () {
+                output.recorded = recorded;
+            }
+        }
+
+        self.audit_log = Logger::open_audit_log (config, self.audit_log.take ())?;
+        self.writer = AsyncWriter::start (config, &self.output)?;
+        self.max_log_level.set (Logger::most_verbose_level (config));
+
+        if config.recent_errors != self.config.recent_errors {
+            self.recent_errors = RecentErrors::new (config.recent_errors);
+        }
+
+        // The change may have come from the admin server itself, so it can't be waited on.
+        if config.admin_socket != self.config.admin_socket {
+
+            if let Some (admin) = self.admin.take () {
+                admin.stop_later ();
+            }
+
+            self.admin = Logger::start_admin (config)?;
+        }
+
+        if config.heartbeat != self.config.heartbeat {
+
+            if let Some (heartbeat) = self.heartbeat.take () {
+                heartbeat.stop_later ();
+            }
+
+            self.heartbeat = Logger::start_heartbeat (config)?;
+        }
+
+        self.config = config.clone ();
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Stops the background thread and writes out everything buffered, so nothing is
+    // duplicated or lost when the process forks.
+    fn prepare_fork (&mut self) -> io::Result<()> {
+
+        if let Some (writer) = self.writer.take () {
+            writer.stop ();
+        }
+
+        self.output.lock ().unwrap ().flush ()
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn post_fork_parent (&mut self) -> io::Result<()> {
+
+        self.writer = AsyncWriter::start (&self.config, &self.output)?;
+        self.admin = Logger::start_admin (&self.config)?;
+        self.heartbeat = Logger::start_heartbeat (&self.config)?;
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Replaces the file handles shared with the parent, and starts a new session.
+    fn post_fork_child (&mut self) -> io::Result<()> {
+
+        {
+            let mut output = self.output.lock ().unwrap ();
+            let session_id = new_session_id ();
+
+            // The buffers and index were written out by `prepare_fork`, so dropping the old
+            // file writes nothing.
+            let log_file = match output.log_file.take () {
+
+                Some (_) => Some (LogFile::reopen_in_child (&self.config, &session_id)?),
+                None => None,
+            };
+
+            *output = LogOutput::new (&self.config, session_id, log_file)?;
+        }
+
+        self.audit_log = Logger::open_audit_log (&self.config, None)?;
+        self.writer = AsyncWriter::start (&self.config, &self.output)?;
+        self.heartbeat = Logger::start_heartbeat (&self.config)?;
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Starts the admin server, if an endpoint is configured.
+    fn start_admin (config: &LogConfig) -> io::Result<Option<AdminServer>> {
+        config.admin_socket.as_ref ().map (AdminServer::start).transpose ()
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn start_heartbeat (config: &LogConfig) -> io::Result<Option<HeartbeatThread>> {
+        config.heartbeat.as_ref ().map (HeartbeatThread::start).transpose ()
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Opens the configured audit log, reusing the current one if the path hasn't changed.
+    fn open_audit_log (config: &LogConfig, current: Option<AuditLog>) -> io::Result<Option<AuditLog>> {
+
+        match current {
+
+            _ if config.audit_log_path.is_empty () => Ok (None),
+            Some (audit_log) if audit_log.path () == config.audit_log_path => Ok (Some (audit_log)),
+            _ => AuditLog::open (&config.audit_log_path).map (Some),
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn flush (&self) -> io::Result<()> {
+
+        match self.writer {
+
+            Some (ref writer) => writer.flush (),
+            None => self.output.lock ().unwrap ().flush (),
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Rotates the log file after writing out the async queue, so earlier records stay in it.
+    fn rotate (&self) -> io::Result<()> {
+
+        self.flush ()?;
+        self.output.lock ().unwrap ().rotate (self.config.clock.now ())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn level_enabled (&self, level: log::LogLevel, target: &str) -> bool {
+
+        if filter::matches_any (&self.config.blocked_targets, target) || self.opted_out (target) {
+            return false;
+        }
+
+        level <= log::__static_max_level () && (level <= self.config.level_for (target) || self.escalated (level))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Whether the target was marked with `opt_in_targets`, and the application hasn't allowed it.
+    fn opted_out (&self, target: &str) -> bool {
+
+        filter::matches_any (&OPT_IN_TARGETS.read ().unwrap (), target) &&
+        !filter::matches_any (&self.config.allowed_targets, target)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Whether the level is let through by an escalation after a recent error.
+    fn escalated (&self, level: log::LogLevel) -> bool {
+
+        match self.config.escalation {
+
+            Some (escalation) if level <= escalation.level => {
+
+                let until = self.escalated_until.load (Ordering::Relaxed);
+                until != 0 && micros_since_epoch (self.config.clock.now ()) < until
+            },
+
+            _ => false,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Starts or extends the escalation window after an error.
+    fn escalate (&self, timestamp: SystemTime) {
+
+        if let Some (escalation) = self.config.escalation {
+            self.escalated_until.fetch_max (micros_since_epoch (timestamp + escalation.window), Ordering::Relaxed);
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn passes_record_filter (&self, record: &log::LogRecord, message: &str, tags: &[&str]) -> bool {
+
+        match self.config.record_filter {
+
+            Some (ref filter) => filter.matches_tagged (record.level (), record.target (), record.location ().module_path (), message, tags),
+            None => true,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // The log crate's global maximum has to let through the most verbose module filter.
+    fn most_verbose_level (config: &LogConfig) -> LogLevel {
+
+        config.module_filters.iter ()
+                             .map (|filter| filter.max_log_level)
+                             .chain (config.escalation.map (|escalation| escalation.level))
+                             .fold (config.max_log_level, cmp::max)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Returns the location to keep for a record, which is blank with `LocationStyle::Hidden`.
+    fn location (&self, location: &log::LogLocation) -> log::LogLocation {
+
+        match self.config.location_style {
+
+            LocationStyle::Hidden => HIDDEN_LOCATION,
+            _                     => *location,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Writes a session banner record, bypassing the filters.
+    fn log_session (&self, message: String) {
+
+        let entry = QueuedEntry {
+
+            level:    log::LogLevel::Info,
+            target:   strings::intern_target (module_path! ()),
+            location: self.location (&log::LogLocation {__module_path: module_path! (), __file: file! (), __line: line! ()}),
+            thread:   thread::current ().id (),
+            timestamp: self.config.clock.now (),
+            message:  Message::new (&message),
+            trace_context: None,
+            tags:     Vec::new (),
+        };
+
+        match self.writer {
+
+            Some (ref writer) => writer.send (entry, OverflowPolicy::Block),
+
+            None => {
+
+                if self.output.lock ().unwrap ().write (&entry.as_entry ()).is_err () {
+                    metrics::dropped (1);
+                }
+            },
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Returns the number of queued records dropped at the drain timeout.
+    fn release (mut self) -> io::Result<usize> {
+
+        if let Some (admin) = self.admin.take () {
+            admin.stop ();
+        }
+
+        if let Some (heartbeat) = self.heartbeat.take () {
+            heartbeat.stop ();
+        }
+
+        if let Some (ref banner) = self.config.session_banner {
+            self.log_session (banner::footer (banner, self.started, self.config.clock.now ()));
+        }
+
+        let drain_timeout = self.config.drain_timeout;
+
+        // After the timeout the abandoned thread may still be stuck writing, so the sinks
+        // aren't flushed.
+        if let Some (undelivered) = self.writer.and_then (|writer| writer.drain (drain_timeout)) {
+            return Ok (undelivered);
+        }
+
+        self.output.lock ().unwrap ().flush ()?;
+        Ok (0)
+    }
+}
+
+/*===============================================================================================*/
+/*------QUEUED ENTRY PRIVATE METHODS-------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl QueuedEntry {
+
+    fn from_entry (entry: &Entry) -> Self {
+
+        QueuedEntry {
+
+            level:    entry.level,
+            target:   strings::intern_target (entry.target),
+            location: entry.location,
+            thread:   entry.thread,
+            timestamp: entry.timestamp,
+            message:  Message::new (entry.message),
+            trace_context: entry.trace_context.cloned (),
+            tags:     entry.tags.to_vec (),
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn as_entry (&self) -> Entry<'_> {
+
+        Entry {
+
+            level:     self.level,
+            target:    self.target.as_str (),
+            location:  self.location,
+            thread:    self.thread,
+            timestamp: self.timestamp,
+            message:   self.message.as_str (),
+            trace_context: self.trace_context.as_ref (),
+            tags:      &self.tags,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // The memory the entry uses, counted against `LogConfig::queue_memory`.
+    // Interned targets are shared, so they aren't counted.
+    fn size (&self) -> usize {
+
+        mem::size_of::<QueuedEntry> () +
+        self.target.heap_size () +
+        self.message.heap_size () +
+        self.tags.capacity () * mem::size_of::<&str> () +
+        self.trace_context.as_ref ().map_or (0, |context| context.trace_id.capacity () + context.span_id.capacity ())
+    }
+}
+
+/*===============================================================================================*/
+/*------LOG OUTPUT PRIVATE METHODS---------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl LogOutput {
+
+    fn new (config: &LogConfig, session_id: String, log_file: Option<LogFile>) -> io::Result<Self> {
+
+        let mut log_file = match log_file {
+
+            Some (log_file) => Some (log_file),
+            None if config.log_to_file => Some (LogFile::create (config, &session_id)?),
+            None => None,
+        };
+
+        if let (Some (log_file), Some (window)) = (log_file.as_mut (), config.flight_recorder) {
+            log_file.allow_reorder (window)?;
+        }
+
+        let log_to_io = config.log_to_io && (!config.daemon_mode || terminal_attached ());
+
+        Ok (LogOutput {
+
+            log_to_io,
+            #[cfg (feature = "colour")]
+            coloured_output: config.coloured_output && log_to_io && enable_terminal_colour (),
+            #[cfg (feature = "colour")]
+            target_colours:  config.target_colours.clone (),
+            #[cfg (feature = "colour")]
+            highlighter:     style::Highlighter::new (config),
+            #[cfg (feature = "colour")]
+            dim_low_levels:  config.dim_low_levels,
+            session_id,
+            session_in_text: config.session_in_text,
+            terminal_json:   config.terminal_json,
+            location_style:  config.location_style,
+            level_names:     LogOutput::level_names (config),
+            app_prefix:      LogOutput::app_prefix (config),
+            app_name:        Some (config.app_name.clone ()).filter (|name| !name.is_empty ()),
+            instance_id:     Some (config.instance_id.clone ()).filter (|id| !id.is_empty ()),
+            terminal_filter: config.terminal_filter.clone (),
+            file_filter:     config.file_filter.clone (),
+            log_file,
+            rotation_config: config.rotation.as_ref ().map (|_| config.clone ()),
+            mock_sink:       config.mock_sink.clone (),
+            channel_sink:    config.channel_sink.clone (),
+            fold_repeats:    config.fold_repeats,
+            source_links:    config.source_links.clone ()
+                                       .filter (|_| config.location_style != LocationStyle::Hidden && io::stdout ().is_terminal ()),
+            flight_recorder: config.flight_recorder,
+            recorded:        VecDeque::new (),
+            repeated_text:   String::new (),
+            repeats:         0,
+            line_buffer:     String::new (),
+            fold_buffer:     String::new (),
+            link_buffer:     String::new (),
+            terminal_buffer: String::new (),
+            file_buffer:     Vec::new (),
+            json_buffer:     String::new (),
+            mock_buffer:     String::new (),
+            terminal_health: SinkTracker::new (config.clock.clone ()),
+            file_health:     SinkTracker::new (config.clock.clone ()),
+            mock_health:     SinkTracker::new (config.clock.clone ()),
+        })
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn write (&mut self, entry: &Entry) -> io::Result<()> {
+
+        self.submit (entry, None);
+        self.commit ()
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Appends an entry, unless the flight recorder keeps it back.
+    // An error first appends the recorded entries from the window before it.
+    fn submit (&mut self, entry: &Entry, file_record: Option<&[u8]>) {
+
+        if let Some (window) = self.flight_recorder {
+
+            if entry.level >= log::LogLevel::Debug {
+
+                let start = entry.timestamp.checked_sub (window).unwrap_or (entry.timestamp);
+
+                while self.recorded.front ().is_some_and (|recorded| recorded.timestamp < start) ||
+                      self.recorded.len () >= FLIGHT_RECORDER_CAPACITY {
+
+                    self.recorded.pop_front ();
+                }
+
+                self.recorded.push_back (QueuedEntry::from_entry (entry));
+                return;
+            }
+
+            if entry.level == log::LogLevel::Error {
+
+                let start = entry.timestamp.checked_sub (window).unwrap_or (entry.timestamp);
+                let mut recorded = mem::take (&mut self.recorded);
+
+                for recorded_entry in recorded.iter ().filter (|recorded| recorded.timestamp >= start) {
+                    self.append (&recorded_entry.as_entry (), None);
+                }
+
+                recorded.clear ();
+                self.recorded = recorded;
+            }
+        }
+
+        self.append (entry, file_record);
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Writes several entries with a single write per sink, given their file records if the
+    // format pool serialized them.
+    // The batch is dropped if a sink fails, as there is nobody to report the error to.
+    fn write_batch (&mut self, entries: &[QueuedEntry], file_records: &[Vec<u8>]) {
+
+        for (index, entry) in entries.iter ().enumerate () {
+            self.submit (&entry.as_entry (), file_records.get (index).map (Vec::as_slice));
+        }
+
+        if self.commit ().is_err () {
+            metrics::dropped (entries.len ());
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn flush (&mut self) -> io::Result<()> {
+
+        self.terminal_health.track (io::stdout ().flush ())?;
+
+        if let Some (ref mut log_file) = self.log_file {
+
+            self.file_health.track (log_file.buffer.flush ())?;
+            log_file.write_index ()?;
+        }
+
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn health (&self) -> Vec<SinkHealth> {
+
+        let mut health = Vec::new ();
+
+        if self.log_to_io {
+            health.push (SinkHealth {name: "terminal", status: self.terminal_health.status ()});
+        }
+
+        if self.log_file.is_some () {
+            health.push (SinkHealth {name: "file", status: self.file_health.status ()});
+        }
+
+        if self.mock_sink.is_some () {
+            health.push (SinkHealth {name: "mock", status: self.mock_health.status ()});
+        }
+
+        health
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Formats an entry into the pending buffers of the sinks it is routed to.
+    // The file record is used instead of encoding the entry for the file, if given.
+    fn append (&mut self, entry: &Entry, file_record: Option<&[u8]>) {
+
+        let to_terminal = self.log_to_io && LogOutput::routes (&self.terminal_filter, entry);
+        let to_file     = self.log_file.is_some () && LogOutput::routes (&self.file_filter, entry);
+        let to_mock     = self.mock_sink.is_some ();
+
+        if let Some (ref channel_sink) = self.channel_sink {
+            test::send (channel_sink, entry.thread, || LogOutput::test_record (entry));
+        }
+
+        if !to_terminal && !to_file && !to_mock {
+            return;
+        }
+
+        self.line_buffer.clear ();
+        self.line_buffer.push_str (&self.app_prefix);
+
+        if self.session_in_text {
+
+            self.line_buffer.push_str (&self.session_id);
+            self.line_buffer.push (' ');
+        }
+
+        let text_start = self.line_buffer.len ();
+        let folded = self.fold_repeat (entry.message);
+        let message = if folded {&self.fold_buffer} else {entry.message};
+        let level = filter::most_specific (&self.level_names[entry.level as usize - 1], |name| &name.target, entry.target)
+                           .map_or (format::level_name (entry.level), |name| name.name.as_str ());
+
+        let location_length = match self.location_style {
+
+            LocationStyle::Module => format::text_line (&mut self.line_buffer,
+                                                        level,
+                                                        entry.location.module_path (),
+                                                        entry.location.line (),
+                                                        message),
+
+            LocationStyle::File => format::file_text_line (&mut self.line_buffer,
+                                                           level,
+                                                           entry.location.file (),
+                                                           entry.location.line (),
+                                                           message),
+
+            LocationStyle::Hidden => format::bare_text_line (&mut self.line_buffer, level, message),
+        };
+
+        if to_terminal && self.terminal_json {
+
+            LogOutput::json_line (&mut self.json_buffer, entry, &self.session_id, &self.app_name, &self.instance_id, true);
+            self.terminal_buffer.push_str (&self.json_buffer);
+            self.terminal_buffer.push ('\n');
+        }
+
+        else if to_terminal {
+
+            let location_end = text_start + location_length;
+            let message_start = self.line_buffer.len () - message.len () - 1;
+
+            self.link_buffer.clear ();
+
+            if let Some (ref url) = self.source_links {
+
+                self.link_buffer.push_str (&url.replace ("{file}", entry.location.file ())
+                                               .replace ("{line}", &entry.location.line ().to_string ()));
+            }
+
+            self.push_terminal_line (entry.level, entry.target, text_start..location_end, message_start);
+        }
+
+        if to_mock {
+            self.mock_buffer.push_str (&self.line_buffer);
+        }
+
+        if to_file && self.rotation_due (entry.timestamp) {
+            drop (self.rotate (entry.timestamp));
+        }
+
+        if let Some (log_file) = self.log_file.as_mut ().filter (|_| to_file) {
+
+            if log_file.encode_binary (entry, &mut self.file_buffer) {
+                return;
+            }
+
+            match file_record {
+
+                Some (file_record) => self.file_buffer.extend_from_slice (file_record),
+                None if LogOutput::is_structured (log_file.format) => {
+
+                    LogOutput::encode_file_record (entry,
+                                                   log_file.format,
+                                                   log_file.encoding,
+                                                   (&self.session_id, &self.app_name, &self.instance_id),
+                                                   &mut self.json_buffer,
+                                                   &mut self.file_buffer);
+                },
+
+                None => log_file.encode_text (&self.line_buffer, &mut self.file_buffer),
+            }
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Whether file records in the format are built from the JSON layout's fields.
+    fn is_structured (format: LogFormat) -> bool {
+        format == LogFormat::Json || format == LogFormat::MessagePack || format == LogFormat::Cbor
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Appends an entry to a JSON, MessagePack or CBOR file buffer.
+    // The session ID, app name and instance ID are passed together, as they are for every record.
+    fn encode_file_record (entry: &Entry,
+                           format: LogFormat,
+                           encoding: FileEncoding,
+                           (session_id, app_name, instance_id): (&str, &Option<String>, &Option<String>),
+                           json_buffer: &mut String,
+                           out: &mut Vec<u8>) {
+
+        let record = LogOutput::record_fields (entry, session_id, app_name, instance_id, false);
+
+        match format {
+
+            LogFormat::MessagePack => msgpack::record (out, &record),
+            LogFormat::Cbor => cbor::record (out, &record),
+            _ => {
+
+                json_buffer.clear ();
+                format::json_line (json_buffer, &record);
+                json_buffer.push ('\n');
+                LogFile::push_text (encoding, json_buffer, out);
+            },
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Returns what the format pool needs to serialize this output's file records, if the file
+    // is JSON, MessagePack or CBOR.
+    fn format_settings (&self) -> Option<FormatSettings> {
+
+        let log_file = self.log_file.as_ref ().filter (|log_file| LogOutput::is_structured (log_file.format))?;
+
+        Some (FormatSettings {
+
+            format:      log_file.format,
+            encoding:    log_file.encoding,
+            session_id:  self.session_id.clone (),
+            app_name:    self.app_name.clone (),
+            instance_id: self.instance_id.clone (),
+        })
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Writes out the pending sink buffers.
+    fn commit (&mut self) -> io::Result<()> {
+
+        if !self.terminal_buffer.is_empty () {
+
+            let result = self.terminal_health.track (io::stdout ().write_all (self.terminal_buffer.as_bytes ()));
+
+            match result {
+
+                Ok (_) => metrics::bytes_written (self.terminal_buffer.len ()),
+                Err (_) => metrics::sink_error ("terminal"),
+            }
+
+            self.terminal_buffer.clear ();
+            result?;
+        }
+
+        self.commit_file ()?;
+
+        if let Some (mock_sink) = self.mock_sink.as_ref ().filter (|_| !self.mock_buffer.is_empty ()) {
+
+            let result = self.mock_health.track (mock::write (mock_sink, &self.mock_buffer));
+
+            match result {
+
+                Ok (_) => metrics::bytes_written (self.mock_buffer.len ()),
+                Err (_) => metrics::sink_error ("mock"),
+            }
+
+            self.mock_buffer.clear ();
+            result?;
+        }
+
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Writes out the pending log file buffer.
+    fn commit_file (&mut self) -> io::Result<()> {
+
+        let log_file = match self.log_file {
+
+            Some (ref mut log_file) => log_file,
+            None => return Ok (()),
+        };
+
+        // A write that only filled the buffer says nothing about the file itself.
+        let pending = log_file.buffer.buffer ().len () + self.file_buffer.len ();
+        let result  = log_file.buffer.write_all (&self.file_buffer);
+        let result  = self.file_health.track_buffered (result, log_file.buffer.buffer ().len () < pending);
+
+        match result {
+
+            Ok (_) => {
+
+                metrics::bytes_written (self.file_buffer.len ());
+                log_file.size += self.file_buffer.len () as u64;
+                log_file.commit_binary ();
+            },
+
+            Err (_) => {
+
+                metrics::sink_error ("file");
+                log_file.discard_binary ();
+            },
+        }
+
+        self.file_buffer.clear ();
+        result
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Whether the log file should be rotated before a record with the timestamp is added.
+    fn rotation_due (&mut self, timestamp: SystemTime) -> bool {
+
+        let pending = self.file_buffer.len ();
+        self.log_file.as_mut ().is_some_and (|log_file| log_file.rotation_due (pending, timestamp))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Rotates the log file. If that fails, records keep going to the current file, and
+    // rotation is tried again a little later.
+    fn rotate (&mut self, now: SystemTime) -> io::Result<()> {
+
+        let result = self.start_next_file (now);
+
+        if result.is_err () {
+
+            metrics::sink_error ("file");
+
+            if let Some (rotator) = self.log_file.as_mut ().and_then (|log_file| log_file.rotator.as_mut ()) {
+                rotator.retry_later (now);
+            }
+        }
+
+        self.file_health.track (result)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Writes out the current log file, renames it and starts a new one in its place.
+    fn start_next_file (&mut self, now: SystemTime) -> io::Result<()> {
+
+        self.commit_file ()?;
+
+        let flight_recorder = self.flight_recorder;
+        let (log_file, config) = match (self.log_file.as_mut (), self.rotation_config.as_ref ()) {
+
+            (Some (log_file), Some (config)) => (log_file, config),
+            _ => return Err (io::Error::other ("the log file isn't rotated")),
+        };
+
+        let mut rotator = match log_file.rotator.take () {
+
+            Some (rotator) => rotator,
+            None => return Err (io::Error::other ("the log file isn't rotated")),
+        };
+
+        let path = rotator.path ().to_path_buf ();
+        let rotated = rotator.rotated_path ();
+
+        let result = log_file.buffer.flush ()
+                             .and_then (|_| log_file.write_index ())
+                             .and_then (|_| log_file.rename (&path, &rotated));
+
+        if let Err (error) = result {
+
+            log_file.rotator = Some (rotator);
+            return Err (error);
+        }
+
+        let next_file = LogFile::create (config, &self.session_id).and_then (|mut next_file| {
+
+            if let Some (window) = flight_recorder {
+                next_file.allow_reorder (window)?;
+            }
+
+            Ok (next_file)
+        });
+
+        let mut next_file = match next_file {
+
+            Ok (next_file) => next_file,
+            Err (error) => {
+
+                // Keep writing to the current file under its old name.
+                drop (fs::rename (&rotated, &path));
+                log_file.rotator = Some (rotator);
+                return Err (error);
+            },
+        };
+
+        rotator.rotated (&rotated, now);
+        next_file.rotator = Some (rotator);
+        *log_file = next_file;
+
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Splits the level names by level, from error to trace.
+    fn level_names (config: &LogConfig) -> Vec<Vec<LevelName>> {
+
+        (1..=5).map (|level| config.level_names.iter ()
+                                               .filter (|name| name.level as usize == level)
+                                               .cloned ()
+                                               .collect ())
+               .collect ()
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn test_record (entry: &Entry) -> test::Record {
+
+        test::Record {
+
+            level:       entry.level,
+            timestamp:   entry.timestamp,
+            target:      entry.target.to_string (),
+            module_path: entry.location.module_path ().to_string (),
+            file:        entry.location.file ().to_string (),
+            line:        entry.location.line (),
+            message:     entry.message.to_string (),
+            trace_id:    entry.trace_context.map (|context| context.trace_id.clone ()),
+            span_id:     entry.trace_context.map (|context| context.span_id.clone ()),
+            tags:        entry.tags.iter ().map (|tag| tag.to_string ()).collect (),
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Returns `app_name[instance_id] `, leaving out whichever parts are empty.
+    fn app_prefix (config: &LogConfig) -> String {
+
+        match (config.app_name.is_empty (), config.instance_id.is_empty ()) {
+
+            (true, true)   => String::new (),
+            (false, true)  => format! ("{} ", config.app_name),
+            (true, false)  => format! ("[{}] ", config.instance_id),
+            (false, false) => format! ("{}[{}] ", config.app_name, config.instance_id),
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Replaces the buffer's contents with the entry as a JSON line, without the newline.
+    fn json_line (out: &mut String,
+                  entry: &Entry,
+                  session_id: &str,
+                  app_name: &Option<String>,
+                  instance_id: &Option<String>,
+                  collector: bool) {
+
+        out.clear ();
+        format::json_line (out, &LogOutput::record_fields (entry, session_id, app_name, instance_id, collector));
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Borrows the fields of an entry, for writing as JSON, MessagePack or CBOR.
+    fn record_fields<'a> (entry: &'a Entry,
+                          session_id: &'a str,
+                          app_name: &'a Option<String>,
+                          instance_id: &'a Option<String>,
+                          collector: bool) -> format::JsonRecord<'a, &'static str> {
+
+        format::JsonRecord {
+
+            level:       entry.level,
+            timestamp:   entry.timestamp,
+            target:      entry.target,
+            module_path: entry.location.module_path (),
+            line:        entry.location.line (),
+            message:     entry.message,
+            trace_id:    entry.trace_context.map (|context| context.trace_id.as_str ()),
+            span_id:     entry.trace_context.map (|context| context.span_id.as_str ()),
+            session_id:  Some (session_id),
+            app_name:    app_name.as_deref (),
+            instance_id: instance_id.as_deref (),
+            tags:        entry.tags,
+            severity:    format::tagged_severity (entry.tags),
+            collector,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Whether the message is a multi-line message repeating the last one, and should be
+    // written as its first line and a count. If so, that summary is left in the fold buffer.
+    fn fold_repeat (&mut self, message: &str) -> bool {
+
+        if !self.fold_repeats || !message.contains ('\n') {
+            return false;
+        }
+
+        if message != self.repeated_text {
+
+            self.repeated_text.clear ();
+            self.repeated_text.push_str (message);
+            self.repeats = 1;
+            return false;
+        }
+
+        self.repeats += 1;
+        self.fold_buffer.clear ();
+
+        write! (self.fold_buffer, "{} (same as above, {}x)", message.lines ().next ().unwrap_or (""), self.repeats).unwrap ();
+        true
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn routes (filter: &Option<FilterExpression>, entry: &Entry) -> bool {
+
+        filter.as_ref ().is_none_or (|filter| {
+            filter.matches_tagged (entry.level, entry.target, entry.location.module_path (), entry.message, entry.tags)
+        })
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Appends the formatted line to the terminal buffer, in the level's colour if enabled.
+    // The location is drawn in the target's colour instead, if it has one, and the message
+    // from `message_start` has the highlight rules applied.
+    #[cfg (feature = "colour")]
+    fn push_terminal_line (&mut self, level: log::LogLevel, target: &str, location: Range<usize>, message_start: usize) {
+
+        if !self.coloured_output {
+
+            self.push_plain_terminal_line (location);
+            return;
+        }
+
+        let colour = self.level_style (level);
+        let line = &self.line_buffer;
+        let (message, end) = line[message_start..].split_at (line.len () - message_start - 1);
+
+        match filter::most_specific (&self.target_colours, |colour| &colour.target, target) {
+
+            Some (target_colour) => {
+
+                let target_colour = style::ansi (target_colour.colour);
+
+                write! (self.terminal_buffer, "{}{}", target_colour.prefix (), &line[..location.start]).unwrap ();
+                LogOutput::push_location (&mut self.terminal_buffer, &line[location.clone ()], &self.link_buffer);
+                write! (self.terminal_buffer, "{}{}{}", target_colour.suffix (), colour.prefix (), &line[location.end..message_start]).unwrap ();
+            },
+
+            None => {
+
+                write! (self.terminal_buffer, "{}{}", colour.prefix (), &line[..location.start]).unwrap ();
+                LogOutput::push_location (&mut self.terminal_buffer, &line[location.clone ()], &self.link_buffer);
+                self.terminal_buffer.push_str (&line[location.end..message_start]);
+            },
+        }
+
+        self.highlighter.write (&mut self.terminal_buffer, message, colour);
+        writeln! (self.terminal_buffer, "{}", end).unwrap ();
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    #[cfg (not (feature = "colour"))]
+    fn push_terminal_line (&mut self, _level: log::LogLevel, _target: &str, location: Range<usize>, _message_start: usize) {
+        self.push_plain_terminal_line (location);
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn push_plain_terminal_line (&mut self, location: Range<usize>) {
+
+        let line = &self.line_buffer;
+
+        self.terminal_buffer.push_str (&line[..location.start]);
+        LogOutput::push_location (&mut self.terminal_buffer, &line[location.clone ()], &self.link_buffer);
+        writeln! (self.terminal_buffer, "{}", &line[location.end..]).unwrap ();
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Appends the location, as an OSC 8 hyperlink to the URL if there is one.
+    fn push_location (out: &mut String, location: &str, url: &str) {
+
+        if url.is_empty () {
+            out.push_str (location);
+        }
+
+        else {
+            write! (out, "\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, location).unwrap ();
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    #[cfg (feature = "colour")]
+    fn level_style (&self, level: log::LogLevel) -> Style {
+
+        let colour = match level {
+
+            log::LogLevel::Trace => Green,
+            log::LogLevel::Debug => Blue,
+            log::LogLevel::Info  => Purple,
+            log::LogLevel::Warn  => Yellow,
+            log::LogLevel::Error => Red
+        };
+
+        if self.dim_low_levels && level >= log::LogLevel::Debug {
+            colour.dimmed ()
+        }
+
+        else {
+            colour.normal ()
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------LOG FILE TRAIT IMPLEMENTATIONS-----------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Drop for LogFile {
+
+    // Brings the index up to date when the file is closed, as by `release` or `reconfigure`.
+    fn drop (&mut self) {
+
+        if self.buffer.flush ().is_ok () {
+            drop (self.write_index ());
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------LOG FILE PRIVATE METHODS-----------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl LogFile {
+
+    fn new (file: File, config: &LogConfig) -> Self {
+
+        LogFile {
+
+            buffer:  BufWriter::new (file),
+            format:  config.log_format,
+            encoding: config.file_encoding,
+            rotator: None,
+            size:    0,
+            header_size: 0,
+            #[cfg (feature = "binary")]
+            encoder: None,
+            #[cfg (feature = "binary")]
+            index:   None,
+            #[cfg (feature = "binary")]
+            reorder: Duration::ZERO,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn create (config: &LogConfig, session_id: &str) -> io::Result<Self> {
+
+        let file = match config.log_file_handle {
+
+            Some (ref handle) => handle.try_clone ()?,
+            None => {
+
+                // Opened for appending, so a forked child writing to the same file can't overwrite this one.
+                let file = OpenOptions::new ().create (true).append (true).open (&config.log_output_path)?;
+                file.set_len (0)?;
+                file
+            },
+        };
+
+        let size = file.metadata ()?.len ();
+        let mut log_file = LogFile::new (file, config);
+
+        if config.log_format == LogFormat::Binary {
+            log_file.start_binary (config, session_id)?;
+        }
+
+        else if size == 0 {
+            log_file.write_byte_order_mark ()?;
+        }
+
+        log_file.size = size + log_file.buffer.buffer ().len () as u64;
+        log_file.header_size = log_file.size;
+        log_file.set_rotation (config);
+
+        Ok (log_file)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Sets up rotation from the config, if the file was opened from a path. An unchanged
+    // rotation keeps its schedule, so a reconfigure doesn't put off a rotation already due.
+    fn set_rotation (&mut self, config: &LogConfig) {
+
+        let unchanged = match (&self.rotator, &config.rotation) {
+
+            (Some (rotator), Some (rotation)) => rotator.rotation () == rotation &&
+                                                 rotator.path () == Path::new (&config.log_output_path),
+            _ => false,
+        };
+
+        if unchanged {
+            return;
+        }
+
+        self.rotator = config.rotation.as_ref ()
+                                      .filter (|_| config.log_file_handle.is_none () && !config.log_output_path.is_empty ())
+                                      .map (|rotation| Rotator::new (rotation, Path::new (&config.log_output_path), config.clock.now ()));
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Whether the file should be rotated before a record with the timestamp is added, with
+    // `pending` bytes not yet written to it.
+    fn rotation_due (&mut self, pending: usize, timestamp: SystemTime) -> bool {
+
+        let size = self.size + pending as u64;
+        let header_size = self.header_size;
+
+        self.rotator.as_mut ().is_some_and (|rotator| rotator.is_due (size, header_size, timestamp))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Renames the file and its index, if it has one. The buffer and index must be written out first.
+    // The index is closed, as its writer can't follow the rename.
+    fn rename (&mut self, from: &Path, to: &Path) -> io::Result<()> {
+
+        fs::rename (from, to)?;
+
+        if self.has_index () {
+
+            drop (fs::rename (rotation::index_path (from), rotation::index_path (to)));
+            self.close_index ();
+        }
+
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Opens a forked child's own handle to the output file.
+    // Text, JSON, MessagePack and CBOR are appended to the parent's file, but a binary file's string table can't
+    // be shared, so the child starts a new one at `<path>.<pid>`.
+    fn reopen_in_child (config: &LogConfig, session_id: &str) -> io::Result<Self> {
+
+        match config.log_format {
+
+            LogFormat::Text | LogFormat::Json | LogFormat::MessagePack | LogFormat::Cbor => {
+
+                let file = match config.log_file_handle {
+
+                    Some (ref handle) => handle.try_clone ()?,
+                    None => OpenOptions::new ().append (true).open (&config.log_output_path)?,
+                };
+
+                Ok (LogFile::new (file, config))
+            },
+
+            LogFormat::Binary if config.log_file_handle.is_some () => {
+                Err (io::Error::other ("a forked child can't start its own binary log from a pre-opened file"))
+            },
+
+            LogFormat::Binary => {
+
+                let mut config = config.clone ();
+                config.log_output_path = format! ("{}.{}", config.log_output_path, process::id ());
+                config.rotation = None;
+
+                LogFile::create (&config, session_id)
+            },
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Starts a text or JSON file with the byte order mark for its encoding, if it has one.
+    fn write_byte_order_mark (&mut self) -> io::Result<()> {
+
+        if self.format != LogFormat::Text && self.format != LogFormat::Json {
+            return Ok (());
+        }
+
+        match self.encoding {
+
+            FileEncoding::Utf8    => Ok (()),
+            FileEncoding::Utf8Bom => self.buffer.write_all (&[0xef, 0xbb, 0xbf]),
+            FileEncoding::Utf16Le => self.buffer.write_all (&[0xff, 0xfe]),
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Appends text in the file's encoding.
+    fn encode_text (&self, text: &str, out: &mut Vec<u8>) {
+        LogFile::push_text (self.encoding, text, out);
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Appends text in the encoding, for callers without the file at hand.
+    fn push_text (encoding: FileEncoding, text: &str, out: &mut Vec<u8>) {
+
+        match encoding {
+
+            FileEncoding::Utf16Le => out.extend (text.encode_utf16 ().flat_map (u16::to_le_bytes)),
+            _                     => out.extend_from_slice (text.as_bytes ()),
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Writes the binary header, and sets up the encoder and index.
+    #[cfg (feature = "binary")]
+    fn start_binary (&mut self, config: &LogConfig, session_id: &str) -> io::Result<()> {
+
+        let mut header = Vec::new ();
+
+        binary::Encoder::header (&mut header);
+        binary::Encoder::session (session_id, &mut header);
+
+        if !config.app_name.is_empty () || !config.instance_id.is_empty () {
+            binary::Encoder::app (&config.app_name, &config.instance_id, &mut header);
+        }
+
+        self.buffer.write_all (&header)?;
+        self.encoder = Some (binary::Encoder::new ());
+
+        if config.log_file_index && !config.log_output_path.is_empty () {
+            self.index = Some (IndexWriter::new (&config.log_output_path, &header));
+        }
+
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    #[cfg (not (feature = "binary"))]
+    fn start_binary (&mut self, _config: &LogConfig, _session_id: &str) -> io::Result<()> {
+        Err (io::Error::other ("the binary log format needs the `binary` feature"))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Appends an entry in the binary format, returning false if this isn't a binary file.
+    #[cfg (feature = "binary")]
+    fn encode_binary (&mut self, entry: &Entry, out: &mut Vec<u8>) -> bool {
+
+        let encoder = match self.encoder {
+
+            Some (ref mut encoder) => encoder,
+            None => return false,
+        };
+
+        let strings = encoder.string_count ();
+        let start = out.len ();
+
+        encoder.encode (entry, out);
+
+        if let Some (ref mut index) = self.index {
+            index.add (entry, strings, out.len () - start);
+        }
+
+        true
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    #[cfg (not (feature = "binary"))]
+    fn encode_binary (&mut self, _entry: &Entry, _out: &mut Vec<u8>) -> bool {
+        false
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Rewrites the index if there is one. The buffer must be flushed first, so the index
+    // doesn't point past the end of the file.
+    #[cfg (feature = "binary")]
+    fn write_index (&mut self) -> io::Result<()> {
+
+        match (self.index.as_mut (), self.encoder.as_ref ()) {
+
+            (Some (index), Some (encoder)) => index.write (encoder),
+            _ => Ok (()),
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    #[cfg (not (feature = "binary"))]
+    fn write_index (&mut self) -> io::Result<()> {
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    #[cfg (feature = "binary")]
+    fn has_index (&self) -> bool {
+        self.index.is_some ()
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    #[cfg (not (feature = "binary"))]
+    fn has_index (&self) -> bool {
+        false
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    #[cfg (feature = "binary")]
+    fn close_index (&mut self) {
+        self.index = None;
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    #[cfg (not (feature = "binary"))]
+    fn close_index (&mut self) {}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Marks the binary records encoded so far as written to the file.
+    #[cfg (feature = "binary")]
+    fn commit_binary (&mut self) {
+
+        if let Some (ref mut encoder) = self.encoder {
+            encoder.commit ();
+        }
+
+        if let Some (ref mut index) = self.index {
+            index.commit ();
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    #[cfg (not (feature = "binary"))]
+    fn commit_binary (&mut self) {}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Rolls the encoder and index back to the last commit when the pending records are
+    // dropped, so later records don't use strings the file never got.
+    #[cfg (feature = "binary")]
+    fn discard_binary (&mut self) {
+
+        if let Some (ref mut encoder) = self.encoder {
+            encoder.discard ();
+        }
+
+        if let Some (ref mut index) = self.index {
+            index.discard ();
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    #[cfg (not (feature = "binary"))]
+    fn discard_binary (&mut self) {}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Tells readers of a binary file that records can be written up to `window` late, as
+    // flight recorder dumps come out after newer records.
+    #[cfg (feature = "binary")]
+    fn allow_reorder (&mut self, window: Duration) -> io::Result<()> {
+
+        if self.encoder.is_none () || window <= self.reorder {
+            return Ok (());
+        }
+
+        let mut frame = Vec::new ();
+        binary::Encoder::reorder (window, &mut frame);
+
+        self.buffer.write_all (&frame)?;
+        self.reorder = window;
+
+        if self.size == self.header_size {
+            self.header_size += frame.len () as u64;
+        }
+
+        self.size += frame.len () as u64;
+
+        if let Some (ref mut index) = self.index {
+            index.add_frame (&frame);
+        }
+
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    #[cfg (not (feature = "binary"))]
+    fn allow_reorder (&mut self, _window: Duration) -> io::Result<()> {
+        Ok (())
+    }
+}
+
+/*===============================================================================================*/
+/*------ASYNC WRITER PRIVATE METHODS-------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl AsyncWriter {
+
+    fn start (config: &LogConfig, output: &Arc<Mutex<LogOutput>>) -> io::Result<Option<Self>> {
+
+        if !config.async_output {
+            return Ok (None);
+        }
+
+        let queue = Arc::new (AsyncQueue::new (config));
+        let output = output.clone ();
+
+        let pool = match output.lock ().unwrap ().format_settings () {
+
+            Some (settings) if config.format_threads >= 2 => Some (FormatPool::start (config.format_threads, settings)?),
+            _ => None,
+        };
+
+        let abandon = Arc::new (AtomicBool::new (false));
+        let thread_abandon = abandon.clone ();
+        let thread_queue = queue.clone ();
+        let batching = config.async_batching;
+        let thread = thread::Builder::new ().name ("ion_log".to_string ())
+                                            .spawn (move || AsyncWriter::run (&thread_queue, &output, &thread_abandon, pool, batching))?;
+
+        Ok (Some (AsyncWriter {
+
+            queue,
+            thread,
+            abandon,
+            load_shedding: config.load_shedding,
+        }))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Queues a record, applying the overflow policy if the queue is full or over its budget.
+    // Once the background thread has stopped, records are counted as overflows instead.
+    fn send (&self, entry: QueuedEntry, policy: OverflowPolicy) {
+
+        let block = policy == OverflowPolicy::Block;
+        let size = entry.size ();
+        let queue = &*self.queue;
+
+        if let Some (ref budget) = queue.budget {
+
+            if !budget.reserve (size, block, &queue.stopped) {
+                return AsyncWriter::overflow ();
+            }
+        }
+
+        if !queue.reserve (block) {
+
+            if let Some (ref budget) = queue.budget {
+                budget.release (size);
+            }
+
+            return AsyncWriter::overflow ();
+        }
+
+        queue.push (entry);
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Whether a record of the level should be dropped, as the queue is past its shedding
+    // threshold. Dropped records are counted as overflows.
+    fn shed (&self, level: log::LogLevel) -> bool {
+
+        let load_shedding = match self.load_shedding {
+
+            Some (ref load_shedding) => load_shedding,
+            None => return false,
+        };
+
+        let threshold = match level {
+
+            log::LogLevel::Trace => load_shedding.trace,
+            log::LogLevel::Debug => load_shedding.debug,
+            log::LogLevel::Info  => load_shedding.info,
+            _                    => return false,
+        };
+
+        let mut fill = metrics::queue_depth () as f32 / self.queue.capacity as f32;
+
+        if let Some (ref budget) = self.queue.budget {
+            fill = fill.max (*budget.used.lock ().unwrap () as f32 / budget.limit as f32);
+        }
+
+        if fill > threshold {
+
+            AsyncWriter::overflow ();
+            return true;
+        }
+
+        false
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn overflow () {
+
+        OVERFLOWS.fetch_add (1, Ordering::Relaxed);
+        metrics::overflow ();
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Writes queued records until the queue is closed or the thread is abandoned, then stops the
+    // format pool, so no threads are left running at a fork.
+    fn run (queue: &AsyncQueue,
+            output: &Mutex<LogOutput>,
+            abandon: &AtomicBool,
+            pool: Option<FormatPool>,
+            batching: AsyncBatching) {
+
+        let _guard = WriterGuard {queue, output};
+
+        drop (queue.writer.set (thread::current ()));
+        AsyncWriter::write_queue (queue, output, abandon, pool.as_ref (), batching);
+
+        if let Some (pool) = pool {
+            pool.stop ();
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Writes queued records in batches, sweeping the producers whenever the swept records run
+    // out, and sleeping while there are none. Once the batch size has grown under load, a batch
+    // also waits up to `max_delay` to fill. With a format pool, a large batch is serialized by
+    // the pool while the one before it is written. Stops at the next batch once abandoned,
+    // dropping whatever is left.
+    fn write_queue (queue: &AsyncQueue,
+                    output: &Mutex<LogOutput>,
+                    abandon: &AtomicBool,
+                    pool: Option<&FormatPool>,
+                    batching: AsyncBatching) {
+
+        let budget = queue.budget.as_ref ();
+        let mut swept = Swept {queue, records: VecDeque::new ()};
+        let mut sweep = Sweep::default ();
+        let mut batch = Vec::with_capacity (batching.max_records);
+        let mut batch_size = batching.min_records;
+        let mut formatting = None;
+
+        loop {
+
+            // Flushes are taken before the sweep, so they cover every record logged before them.
+            let closed = queue.closed.load (Ordering::Acquire);
+            let flushes = mem::take (&mut *queue.flushes.lock ().unwrap ());
+
+            if swept.records.is_empty () || !flushes.is_empty () {
+                queue.sweep (&mut sweep, &mut swept.records);
+            }
+
+            if !flushes.is_empty () {
+
+                AsyncWriter::write_formatted (output, formatting.take (), budget);
+                queue.take (&mut swept.records, &mut batch, usize::MAX);
+
+                let mut output = output.lock ().unwrap ();
+
+                AsyncWriter::write_batch (&mut output, &mut batch, budget);
+
+                for reply in flushes {
+                    drop (reply.send (output.flush ()));
+                }
+
+                continue;
+            }
+
+            // The queue ran dry, so any burst is over, and the batch with the pool shouldn't
+            // wait for the next one.
+            if swept.records.is_empty () {
+
+                if closed {
+                    break;
+                }
+
+                batch_size = batching.min_records;
+                AsyncWriter::write_formatted (output, formatting.take (), budget);
+                thread::park ();
+                continue;
+            }
+
+            let deadline = Instant::now () + batching.max_delay;
+
+            loop {
+
+                queue.take (&mut swept.records, &mut batch, batch_size);
+
+                let grown = batch_size > batching.min_records;
+                let wait = deadline.saturating_duration_since (Instant::now ());
+
+                if batch.len () >= batch_size || !grown || wait.is_zero () || queue.interrupted () {
+                    break;
+                }
+
+                thread::park_timeout (wait);
+                queue.sweep (&mut sweep, &mut swept.records);
+            }
+
+            if abandon.load (Ordering::Relaxed) {
+                return;
+            }
+
+            batch_size = AsyncWriter::next_batch_size (batch.len (), batch_size, batching);
+
+            match pool {
+
+                Some (pool) if batch.len () >= pool::CHUNK_RECORDS => {
+
+                    let next = pool.format (mem::replace (&mut batch, Vec::with_capacity (batching.max_records)));
+
+                    AsyncWriter::write_formatted (output, formatting.replace (next), budget);
+                },
+
+                _ => {
+
+                    AsyncWriter::write_formatted (output, formatting.take (), budget);
+                    AsyncWriter::write_batch (&mut output.lock ().unwrap (), &mut batch, budget);
+                },
+            }
+        }
+
+        if !abandon.load (Ordering::Relaxed) {
+            AsyncWriter::write_formatted (output, formatting, budget);
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Doubles the batch size after a full batch, and halves it after one less than half full.
+    fn next_batch_size (written: usize, batch_size: usize, batching: AsyncBatching) -> usize {
+
+        if written >= batch_size {
+            cmp::min (batch_size * 2, batching.max_records)
+        }
+
+        else if written < batch_size / 2 {
+            cmp::max (batch_size / 2, batching.min_records)
+        }
+
+        else {
+            batch_size
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Writes the batch, then gives its memory back to the budget.
+    fn write_batch (output: &mut LogOutput, batch: &mut Vec<QueuedEntry>, budget: Option<&QueueBudget>) {
+
+        output.write_batch (batch, &[]);
+
+        if let Some (budget) = budget {
+            budget.release (batch.iter ().map (QueuedEntry::size).sum ());
+        }
+
+        batch.clear ();
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Writes a batch handed to the format pool, if there is one, once its records are ready.
+    fn write_formatted (output: &Mutex<LogOutput>, batch: Option<FormattedBatch>, budget: Option<&QueueBudget>) {
+
+        let (entries, file_records) = match batch {
+
+            Some (batch) => batch.wait (),
+            None => return,
+        };
+
+        output.lock ().unwrap ().write_batch (&entries, &file_records);
+
+        if let Some (budget) = budget {
+            budget.release (entries.iter ().map (QueuedEntry::size).sum ());
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Writes out every record logged before the call, then flushes the sinks.
+    fn flush (&self) -> io::Result<()> {
+
+        let (reply, result) = mpsc::channel ();
+
+        {
+            let mut flushes = self.queue.flushes.lock ().unwrap ();
+
+            if self.queue.stopped.load (Ordering::Relaxed) {
+                return Err (AsyncQueue::stopped_error ());
+            }
+
+            flushes.push (reply);
+        }
+
+        self.queue.wake ();
+        result.recv ().unwrap_or_else (|_| Err (AsyncQueue::stopped_error ()))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Closes the queue and waits for the thread to write out everything left in it.
+    fn stop (self) {
+
+        self.queue.close ();
+        drop (self.thread.join ());
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Closes the queue and waits up to the timeout for the thread to write it out.
+    // If it doesn't, the thread is abandoned and the number of records still queued is returned.
+    fn drain (self, timeout: Option<Duration>) -> Option<usize> {
+
+        let deadline = match timeout {
+
+            Some (timeout) => Instant::now () + timeout,
+            None => {
+
+                self.stop ();
+                return None;
+            },
+        };
+
+        self.queue.close ();
+
+        while !self.thread.is_finished () {
+
+            if Instant::now () >= deadline {
+
+                self.abandon.store (true, Ordering::Relaxed);
+                return Some (metrics::queue_depth ());
+            }
+
+            thread::sleep (DRAIN_POLL_INTERVAL);
+        }
+
+        self.thread.join ().err ().map (|_| metrics::queue_depth ())
+    }
+}
+
+/*===============================================================================================*/
+/*------ASYNC QUEUE PRIVATE METHODS--------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl AsyncQueue {
+
+    fn new (config: &LogConfig) -> Self {
+
+        let budget = match config.queue_memory {
+
+            0 => None,
+            limit => Some (QueueBudget {limit, used: Mutex::new (0), freed: Condvar::new ()}),
+        };
+
+        AsyncQueue {
+
+            id:        NEXT_QUEUE_ID.fetch_add (1, Ordering::Relaxed),
+            capacity:  config.async_capacity,
+            budget,
+            producers: Mutex::new (Vec::new ()),
+            flushes:   Mutex::new (Vec::new ()),
+            writer:    OnceLock::new (),
+            room:      Mutex::new (()),
+            freed:     Condvar::new (),
+            waiting:   AtomicUsize::new (0),
+            closed:    AtomicBool::new (false),
+            stopped:   AtomicBool::new (false),
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Takes room for a record, waiting for it if `block` is set, or failing otherwise.
+    // Fails once the background thread has stopped.
+    fn reserve (&self, block: bool) -> bool {
+
+        loop {
+
+            if self.stopped.load (Ordering::Relaxed) {
+                return false;
+            }
+
+            if metrics::queued () <= self.capacity {
+                return true;
+            }
+
+            metrics::dequeued ();
+
+            if !block {
+                return false;
+            }
+
+            // The fence pairs with the one in `made_room`, so either this thread sees the room
+            // made, or the background thread sees it waiting.
+            self.waiting.fetch_add (1, Ordering::Relaxed);
+            atomic::fence (Ordering::SeqCst);
+
+            let room = self.room.lock ().unwrap ();
+
+            if metrics::queue_depth () >= self.capacity && !self.stopped.load (Ordering::Relaxed) {
+                drop (self.freed.wait_timeout (room, ROOM_POLL_INTERVAL).unwrap ());
+            }
+
+            self.waiting.fetch_sub (1, Ordering::Relaxed);
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Adds a record to the calling thread's producer.
+    fn push (&self, entry: QueuedEntry) {
+
+        let mut entry = Some (entry);
+
+        let registered = PRODUCER.try_with (|producer| {
+
+            let mut producer = producer.borrow_mut ();
+
+            match *producer {
+
+                Some ((id, _)) if id == self.id => (),
+                _ => *producer = Some ((self.id, self.register ())),
+            }
+
+            if let (Some ((_, ref producer)), Some (entry)) = (producer.as_ref (), entry.take ()) {
+                self.add (producer, entry);
+            }
+        });
+
+        // A thread's producer is gone once it starts exiting, so a record logged then gets
+        // one of its own.
+        if registered.is_err () {
+
+            if let Some (entry) = entry {
+                self.add (&self.register (), entry);
+            }
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn register (&self) -> Arc<Producer> {
+
+        let producer = Arc::new (Producer {records: Mutex::new (Vec::new ())});
+
+        self.producers.lock ().unwrap ().push (producer.clone ());
+        producer
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Adds a record to a producer, waking the background thread if it was empty. The record
+    // is dropped if the background thread has stopped.
+    fn add (&self, producer: &Producer, entry: QueuedEntry) {
+
+        let mut records = producer.records.lock ().unwrap ();
+
+        // Checked under the lock, so the record is either dropped here or swept when the
+        // background thread stops.
+        if self.stopped.load (Ordering::Relaxed) {
+
+            drop (records);
+            self.discard (&entry);
+            return AsyncWriter::overflow ();
+        }
+
+        records.push (entry);
+
+        let was_empty = records.len () == 1;
+
+        drop (records);
+
+        if was_empty {
+            self.wake ();
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Gives back the room a record took, once it won't be written.
+    fn discard (&self, entry: &QueuedEntry) {
+
+        metrics::dequeued ();
+
+        if let Some (ref budget) = self.budget {
+            budget.release (entry.size ());
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Moves every producer's records to the end of `records`, merged in timestamp order with
+    // each thread's records kept in the order they were logged. The producers of threads that
+    // have exited are dropped once they're empty.
+    fn sweep (&self, sweep: &mut Sweep, records: &mut VecDeque<QueuedEntry>) {
+
+        self.producers.lock ().unwrap ().retain (|producer| {
+
+            let mut buffer = producer.records.lock ().unwrap ();
+
+            if !buffer.is_empty () {
+
+                let spare = sweep.spare.pop ().unwrap_or_default ();
+                sweep.taken.push (mem::replace (&mut *buffer, spare));
+            }
+
+            // Only the queue still holds the producer of a thread that has exited.
+            Arc::strong_count (producer) > 1
+        });
+
+        match sweep.taken.len () {
+
+            0 => (),
+            1 => records.extend (sweep.taken[0].drain (..)),
+            _ => {
+
+                // Each buffer is reversed, so its oldest record is at the end.
+                for buffer in &mut sweep.taken {
+                    buffer.reverse ();
+                }
+
+                while let Some ((_, oldest)) = sweep.taken.iter ()
+                                                          .enumerate ()
+                                                          .filter_map (|(i, buffer)| buffer.last ().map (|entry| (entry.timestamp, i)))
+                                                          .min () {
+
+                    records.extend (sweep.taken[oldest].pop ());
+                }
+            },
+        }
+
+        sweep.spare.append (&mut sweep.taken);
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Moves swept records into the batch until it holds `limit`, making room in the queue.
+    fn take (&self, records: &mut VecDeque<QueuedEntry>, batch: &mut Vec<QueuedEntry>, limit: usize) {
+
+        let count = cmp::min (records.len (), limit.saturating_sub (batch.len ()));
+
+        if count == 0 {
+            return;
+        }
+
+        for entry in records.drain (..count) {
+
+            metrics::dequeued ();
+            batch.push (entry);
+        }
+
+        self.made_room ();
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Wakes any threads waiting for room in the queue.
+    fn made_room (&self) {
+
+        atomic::fence (Ordering::SeqCst);
+
+        if self.waiting.load (Ordering::Relaxed) > 0 {
+
+            drop (self.room.lock ().unwrap ());
+            self.freed.notify_all ();
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Whether a batch waiting to fill should be written now, for a flush or a close.
+    fn interrupted (&self) -> bool {
+        self.closed.load (Ordering::Acquire) || !self.flushes.lock ().unwrap ().is_empty ()
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn wake (&self) {
+
+        if let Some (writer) = self.writer.get () {
+            writer.unpark ();
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Tells the background thread to write out what's left and stop.
+    fn close (&self) {
+
+        self.closed.store (true, Ordering::Release);
+        self.wake ();
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn stopped_error () -> io::Error {
+        io::Error::other ("the ion_log writer thread has stopped")
+    }
+}
+
+/*===============================================================================================*/
+/*------SWEPT TRAIT IMPLEMENTATIONS--------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl<'a> Drop for Swept<'a> {
+
+    fn drop (&mut self) {
+
+        // Only an abandoned or panicking thread leaves records behind, and only the panic
+        // loses them without `release` reporting it.
+        let panicking = thread::panicking ();
+
+        for entry in self.records.drain (..) {
+
+            self.queue.discard (&entry);
+
+            if panicking {
+                AsyncWriter::overflow ();
+            }
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------WRITER GUARD TRAIT IMPLEMENTATIONS-------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl<'a> Drop for WriterGuard<'a> {
+
+    // Marks the queue stopped, drops the records still waiting, counting them as overflows
+    // after a panic, and wakes every thread waiting on the queue. Waiting flushes fail.
+    fn drop (&mut self) {
+
+        let queue = self.queue;
+        let panicking = thread::panicking ();
+
+        self.output.clear_poison ();
+
+        queue.stopped.store (true, Ordering::Relaxed);
+
+        if let Ok (producers) = queue.producers.lock () {
+
+            for producer in producers.iter () {
+
+                let records = match producer.records.lock () {
+
+                    Ok (mut records) => mem::take (&mut *records),
+                    Err (_) => continue,
+                };
+
+                for entry in &records {
+
+                    queue.discard (entry);
+
+                    if panicking {
+                        AsyncWriter::overflow ();
+                    }
+                }
+            }
+        }
+
+        if let Ok (mut flushes) = queue.flushes.lock () {
+            flushes.clear ();
+        }
+
+        drop (queue.room.lock ());
+        queue.freed.notify_all ();
+
+        if let Some (ref budget) = queue.budget {
+
+            drop (budget.used.lock ());
+            budget.freed.notify_all ();
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------QUEUE BUDGET PRIVATE METHODS-------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl QueueBudget {
+
+    // Takes room for a record, waiting for it if `block` is set, or failing otherwise.
+    // Anything fits in an empty queue, so a large record can't wait forever. Fails once the
+    // background thread has stopped.
+    fn reserve (&self, size: usize, block: bool, stopped: &AtomicBool) -> bool {
+
+        let mut used = self.used.lock ().unwrap ();
+
+        while *used > 0 && *used + size > self.limit {
+
+            if !block || stopped.load (Ordering::Relaxed) {
+                return false;
+            }
+
+            used = self.freed.wait (used).unwrap ();
+        }
+
+        *used += size;
+        true
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn release (&self, size: usize) {
+
+        *self.used.lock ().unwrap () -= size;
+        self.freed.notify_all ();
+    }
+}
+
+/*===============================================================================================*/
+/*------SCOPED LOGGER STRUCT---------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// A named handle for logging on behalf of a component.
+///
+/// Records logged through a `ScopedLogger` use the scope name as both their target
+/// and module path, so the output shows the component name instead of the Rust module.
+#[derive (Copy, Clone, Debug)]
+pub struct ScopedLogger {
+
+    // Private
+    name: &'static str,
+}
+
+/*===============================================================================================*/
+/*------SCOPED LOGGER PUBLIC METHODS-------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl ScopedLogger {
+
+    /// Returns the scope name.
+    pub fn name (&self) -> &'static str {
+        self.name
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Logs a message at the given level.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// # let config = LogConfig::new ();
+    /// # ion_log::init (&config).unwrap ();
+    /// let renderer = ion_log::scoped ("renderer");
+    /// renderer.log (log::LogLevel::Info, format_args! ("Loaded {} textures", 12));
+    /// # ion_log::release ();
+    /// ```
+    #[track_caller]
+    pub fn log (&self, level: log::LogLevel, args: fmt::Arguments) {
+
+        if level <= log::__static_max_level () && level <= log::max_log_level () {
+
+            let caller = Location::caller ();
+            let location = log::LogLocation {
+
+                __module_path: self.name,
+                __file:        caller.file (),
+                __line:        caller.line (),
+            };
+
+            log::__log (level, self.name, &location, args);
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Logs a message at the trace level.
+    #[track_caller]
+    pub fn trace (&self, args: fmt::Arguments) {
+        self.log (log::LogLevel::Trace, args);
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Logs a message at the debug level.
+    #[track_caller]
+    pub fn debug (&self, args: fmt::Arguments) {
+        self.log (log::LogLevel::Debug, args);
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Logs a message at the info level.
+    #[track_caller]
+    pub fn info (&self, args: fmt::Arguments) {
+        self.log (log::LogLevel::Info, args);
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Logs a message at the warning level.
+    #[track_caller]
+    pub fn warn (&self, args: fmt::Arguments) {
+        self.log (log::LogLevel::Warn, args);
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Logs a message at the error level.
+    #[track_caller]
+    pub fn error (&self, args: fmt::Arguments) {
+        self.log (log::LogLevel::Error, args);
+    }
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Turns on escape sequence handling in the Windows console.
+// Consoles that don't support it get plain output instead of raw escape codes.
+#[cfg (all (windows, feature = "colour"))]
+fn enable_terminal_colour () -> bool {
+    ansi_term::enable_ansi_support ().is_ok ()
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+#[cfg (all (not (windows), feature = "colour"))]
+fn enable_terminal_colour () -> bool {
+    true
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Whether stdout is open and the process has a controlling terminal.
+#[cfg (unix)]
+fn terminal_attached () -> bool {
+    Path::new ("/dev/fd/1").exists () && File::open ("/dev/tty").is_ok ()
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+#[cfg (not (unix))]
+fn terminal_attached () -> bool {
+    io::stdout ().is_terminal ()
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+fn micros_since_epoch (time: SystemTime) -> u64 {
+    time.duration_since (UNIX_EPOCH).map_or (0, |since| since.as_micros () as u64)
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Returns a random version 4 UUID, such as `1b4e28ba-2fa1-41d2-883f-0016d3cca427`.
+// Each `RandomState` is seeded differently, so hashing the time with two of them gives 128 random bits.
+fn new_session_id () -> String {
+
+    let now = SystemTime::now ();
+    let high = RandomState::new ().hash_one (now);
+    let low  = RandomState::new ().hash_one ((now, process::id ()));
+
+    // Set the version and variant bits.
+    let bits = u128::from (high) << 64 | u128::from (low);
+    let bits = (bits & !(0xf << 76)) | (0x4 << 76);
+    let bits = (bits & !(0x3 << 62)) | (0x2 << 62);
+
+    let hex = format! ("{:032x}", bits);
+
+    format! ("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+/*===============================================================================================*/
+/*------MACROS-----------------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Attaches tags to the records logged by an expression.
+///
+/// Tags are kept in the binary format and in `reader::Record`, and filter expressions can
+/// match on them with `tag`, so `LogConfig::terminal_filter` and `file_filter` can route
+/// records by tag. Nested calls add to the enclosing tags.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate log;
+/// # #[macro_use] extern crate ion_log;
+/// # use ion_log::*;
+/// # fn main () {
+/// let mut config = LogConfig::new ();
+/// config.terminal_filter = Some (FilterExpression::parse (r#"tag != "net""#).unwrap ());
+/// ion_log::init (&config).unwrap ();
+///
+/// // Written to the file if there is one, but not the terminal.
+/// tagged! (tags: ["gameplay", "net"], info! ("Player {} joined", "ada"));
+/// # ion_log::release ();
+/// # }
+/// ```
+#[macro_export]
+macro_rules! tagged {
+
+    (tags: [$($tag:expr),* $(,)*], $body:expr) => {
+        $crate::__with_tags (&[$($tag),*], || $body)
+    };
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Writes a security relevant event to the audit log.
+///
+/// Audit records go only to `LogConfig::audit_log_path`, whatever the filters, and are synced
+/// to disk before this returns. It evaluates to an `io::Result<()>`, so a record that
+/// couldn't be stored is never silently dropped.
+///
+/// # Errors
+/// Fails if the logger isn't initialized, no audit log is configured, or the write fails.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate ion_log;
+/// # use ion_log::*;
+/// # fn main () {
+/// let path = std::env::temp_dir ().join ("ion_log_audit.log");
+///
+/// let mut config = LogConfig::new ();
+/// config.audit_log_path = path.to_string_lossy ().into_owned ();
+/// ion_log::init (&config).unwrap ();
+///
+/// audit! ("User {} granted admin rights", "ada").unwrap ();
+/// # ion_log::release ();
+/// # }
+/// ```
+#[macro_export]
+macro_rules! audit {
+
+    ($($arg:tt)+) => {
+        $crate::__audit (module_path! (), line! (), format_args! ($($arg)+))
+    };
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Logs a message at info with the `notice` severity, for significant but normal events.
+///
+/// See `Severity` for how the severity is recorded. Takes the same arguments as `info!`.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate ion_log;
+/// # use ion_log::*;
+/// # fn main () {
+/// let config = LogConfig::new ();
+/// ion_log::init (&config).unwrap ();
+///
+/// notice! ("Configuration reloaded");
+/// notice! (target: "auth", "Password changed for {}", "ada");
+/// # ion_log::release ();
+/// # }
+/// ```
+#[macro_export]
+macro_rules! notice {
+
+    (target: $target:expr, $($arg:tt)+) => {
+        $crate::__log_severity ($crate::Severity::Notice, $target, module_path! (), file! (), line! (), format_args! ($($arg)+))
+    };
+
+    ($($arg:tt)+) => {
+        $crate::__log_severity ($crate::Severity::Notice, module_path! (), module_path! (), file! (), line! (), format_args! ($($arg)+))
+    };
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Logs a message at error with the `critical` severity.
+///
+/// See `Severity` for how the severity is recorded. Takes the same arguments as `error!`.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate ion_log;
+/// # use ion_log::*;
+/// # fn main () {
+/// let config = LogConfig::new ();
+/// ion_log::init (&config).unwrap ();
+///
+/// critical! ("Replica {} is out of sync", 2);
+/// # ion_log::release ();
+/// # }
+/// ```
+#[macro_export]
+macro_rules! critical {
+
+    (target: $target:expr, $($arg:tt)+) => {
+        $crate::__log_severity ($crate::Severity::Critical, $target, module_path! (), file! (), line! (), format_args! ($($arg)+))
+    };
+
+    ($($arg:tt)+) => {
+        $crate::__log_severity ($crate::Severity::Critical, module_path! (), module_path! (), file! (), line! (), format_args! ($($arg)+))
+    };
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Logs a message at error with the `fatal` severity, for failures the process can't recover
+/// from.
+///
+/// On its own it only logs, leaving it to the caller to stop. Starting with `exit: code,` it
+/// also releases the logger, writing out everything queued and buffered, and then exits the
+/// process with the code. If `LogConfig::fatal_panics` is set, it flushes and panics instead.
+/// Either way it doesn't return.
+///
+/// See `Severity` for how the severity is recorded. Otherwise takes the same arguments as
+/// `error!`.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate ion_log;
+/// # use ion_log::*;
+/// # fn main () {
+/// let mut config = LogConfig::new ();
+/// config.fatal_panics = true;
+/// ion_log::init (&config).unwrap ();
+///
+/// fatal! ("Database schema is newer than this build");
+///
+/// let result = std::panic::catch_unwind (|| {
+///     fatal! (exit: 2, "Can't open the save directory");
+/// });
+///
+/// assert! (result.is_err ());
+/// # ion_log::release ();
+/// # }
+/// ```
+#[macro_export]
+macro_rules! fatal {
+
+    (exit: $code:expr, target: $target:expr, $($arg:tt)+) => {{
+
+        $crate::__log_severity ($crate::Severity::Fatal, $target, module_path! (), file! (), line! (), format_args! ($($arg)+));
+        $crate::__fatal_exit ($code)
+    }};
+
+    (exit: $code:expr, $($arg:tt)+) => {{
+
+        $crate::__log_severity ($crate::Severity::Fatal, module_path! (), module_path! (), file! (), line! (), format_args! ($($arg)+));
+        $crate::__fatal_exit ($code)
+    }};
+
+    (target: $target:expr, $($arg:tt)+) => {
+        $crate::__log_severity ($crate::Severity::Fatal, $target, module_path! (), file! (), line! (), format_args! ($($arg)+))
+    };
+
+    ($($arg:tt)+) => {
+        $crate::__log_severity ($crate::Severity::Fatal, module_path! (), module_path! (), file! (), line! (), format_args! ($($arg)+))
+    };
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Logs how long the rest of the enclosing scope takes, at debug when it ends.
+///
+/// With a `threshold`, only scopes that take at least that long are logged, so a loop can
+/// report just its slow iterations. The record comes from the calling module.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate ion_log;
+/// # use ion_log::*;
+/// # use std::time::Duration;
+/// # fn main () {
+/// let config = LogConfig::new ();
+/// ion_log::init (&config).unwrap ();
+///
+/// for _ in 0..3 {
+///
+///     time_scope! ("physics_step", threshold: Duration::from_millis (16));
+///     // ...
+/// }
+/// # ion_log::release ();
+/// # }
+/// ```
+#[macro_export]
+macro_rules! time_scope {
+
+    ($name:expr, threshold: $threshold:expr) => {
+        let _scope_timer = $crate::ScopeTimer::__new ($name, (module_path! (), file! (), line! ()), $threshold);
+    };
+
+    ($name:expr) => {
+        let _scope_timer = $crate::ScopeTimer::__new ($name, (module_path! (), file! (), line! ()), ::std::time::Duration::from_secs (0));
+    };
+}
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Initializes the logger.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let config = LogConfig::new ();
+/// ion_log::init (&config).unwrap ();
+/// # ion_log::release ();
+/// ```
+///
+/// A taken admin socket address is reported rather than panicking:
+/// ```
+/// # use ion_log::*;
+/// let listener = std::net::TcpListener::bind ("127.0.0.1:0").unwrap ();
+///
+/// let mut config = LogConfig::new ();
+/// config.admin_socket = Some (AdminEndpoint::Tcp (listener.local_addr ().unwrap ().port ()));
+///
+/// match ion_log::init (&config) {
+///
+///     Err (InitError::Io (e)) => assert_eq! (e.kind (), std::io::ErrorKind::AddrInUse),
+///     result => panic! ("unexpected result {:?}", result),
+/// }
+/// ```
+///
+/// # Errors
+/// Fails if a logger is already installed, or a sink or background thread can't be started.
+/// After a sink fails, the `log` crate keeps the logger it was given, so records are
+/// discarded from then on. `LogConfig::validate` catches most of these problems first.
+pub fn init (config: &LogConfig) -> Result<(), InitError> {
+
+    let mut result = Ok (());
+
+    log::set_logger (|max_log_level| {
+
+        match Logger::new (config, max_log_level) {
+
+            Ok (logger) => *LOGGER.write ().unwrap () = Some (logger),
+            Err (e) => result = Err (InitError::Io (e)),
+        }
+
+        Box::new (GlobalLogger)
+    })?;
+
+    result
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Initializes the logger, unless a logger is already installed.
+///
+/// Returns whether this call installed it, so libraries and test harnesses can call it
+/// without checking first. An error opening the sinks is returned rather than panicking.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let config = LogConfig::new ();
+///
+/// assert! (ion_log::try_init (&config).unwrap ());
+/// assert! (!ion_log::try_init (&config).unwrap ());
+/// # ion_log::release ();
+/// ```
+///
+/// # Errors
+/// Fails if the log file or audit log can't be opened. The `log` crate only takes one logger
+/// per process, so records are discarded from then on.
+pub fn try_init (config: &LogConfig) -> io::Result<bool> {
+
+    let mut result = Ok (());
+
+    let installed = log::set_logger (|max_log_level| {
+
+        match Logger::new (config, max_log_level) {
+
+            Ok (logger) => *LOGGER.write ().unwrap () = Some (logger),
+            Err (e) => result = Err (e),
+        }
+
+        Box::new (GlobalLogger)
+    }).is_ok ();
+
+    result.map (|_| installed)
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Initializes the logger with `LogConfig::dev`.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate log;
+/// # extern crate ion_log;
+/// # fn main () {
+/// ion_log::init_simple ().unwrap ();
+/// info! ("Ready");
+/// # ion_log::release ();
+/// # }
+/// ```
+///
+/// # Errors
+/// Fails as for `init`.
+pub fn init_simple () -> Result<(), InitError> {
+    init (&LogConfig::dev ())
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Initializes terminal logging for a command line tool.
+///
+/// The maximum log level comes from `LogLevel::from_verbosity`, so `-v`, `-vv` and `-q`
+/// mean the same thing in every tool.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let (verbose_flags, quiet) = (1, false);
+/// ion_log::init_cli (verbose_flags, quiet).unwrap ();
+/// # ion_log::release ();
+/// ```
+///
+/// # Errors
+/// Fails as for `init`.
+pub fn init_cli (verbosity: u8, quiet: bool) -> Result<(), InitError> {
+
+    let mut config = LogConfig::new ();
+    config.max_log_level = LogLevel::from_verbosity (verbosity, quiet);
+
+    init (&config)
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Releases the logger.
+///
+/// All sinks are flushed before the logger is dropped. In asynchronous mode, the queued
+/// records are written out first, waiting at most `LogConfig::drain_timeout`. Errors are
+/// ignored; use `try_release` to get them.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let config = LogConfig::new ();
+/// ion_log::init (&config).unwrap ();
+/// ion_log::release ();
+/// ```
+pub fn release () {
+    drop (try_release ());
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Releases the logger like `release`, returning the number of queued records which couldn't
+/// be written before `LogConfig::drain_timeout` passed.
+///
+/// # Errors
+/// Fails if the sinks can't be flushed.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// # use std::time::Duration;
+/// let mut config = LogConfig::new ();
+/// config.async_output  = true;
+/// config.drain_timeout = Some (Duration::from_secs (2));
+/// ion_log::init (&config).unwrap ();
+///
+/// let undelivered = ion_log::try_release ().unwrap ();
+/// assert_eq! (undelivered, 0);
+/// ```
+pub fn try_release () -> io::Result<usize> {
+
+    drop (log::shutdown_logger ().unwrap ());
+
+    // The lock is released first, as the admin server may be waiting on it.
+    let logger = LOGGER.write ().unwrap ().take ();
+
+    let result = match logger {
+
+        Some (logger) => logger.release (),
+        None => Ok (0),
+    };
+
+    strings::clear_targets ();
+    result
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Flushes all sinks of the running logger.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let config = LogConfig::new ();
+/// ion_log::init (&config).unwrap ();
+/// ion_log::flush ().unwrap ();
+/// # ion_log::release ();
+/// ```
+pub fn flush () -> io::Result<()> {
+
+    match *LOGGER.read ().unwrap () {
+
+        Some (ref logger) => logger.flush (),
+        None => Ok (()),
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Rotates the log file now, whatever the limits in `LogConfig::rotation`, as for a `SIGHUP`
+/// handler or a scheduled job. Asynchronous records logged before the call go in the current
+/// file.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate log;
+/// # extern crate ion_log;
+/// # use ion_log::*;
+/// # fn main () {
+/// # let directory = std::env::temp_dir ().join (format! ("ion_log_rotate_{}", std::process::id ()));
+/// # std::fs::create_dir_all (&directory).unwrap ();
+/// let mut config = LogConfig::new ();
+/// config.log_to_io       = false;
+/// config.log_to_file     = true;
+/// config.log_output_path = directory.join ("app.log").to_string_lossy ().into_owned ();
+/// config.rotation        = Some (Rotation::daily ());
+///
+/// ion_log::init (&config).unwrap ();
+///
+/// info! ("Before the rotation");
+/// ion_log::rotate ().unwrap ();
+/// info! ("After it");
+/// ion_log::flush ().unwrap ();
+///
+/// let rotated = std::fs::read_to_string (directory.join ("app.1.log")).unwrap ();
+/// let current = std::fs::read_to_string (directory.join ("app.log")).unwrap ();
+///
+/// assert! (rotated.contains ("Before the rotation"));
+/// assert! (current.contains ("After it") && !current.contains ("Before"));
+/// # ion_log::release ();
+/// # std::fs::remove_dir_all (&directory).unwrap ();
+/// # }
+/// ```
+///
+/// # Errors
+/// Fails if the logger isn't initialized, the log file isn't rotated, or the file can't be
+/// renamed or a new one created. Records keep going to the current file if so.
+pub fn rotate () -> io::Result<()> {
+
+    match *LOGGER.read ().unwrap () {
+
+        Some (ref logger) => logger.rotate (),
+        None => Err (io::Error::other ("the logger has not been initialized")),
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Whether a record at the level from the target would be logged.
+///
+/// This checks blocked targets, module filters, the maximum log level and the compile time
+/// maximum, so it can be used to skip building expensive diagnostics. `record_filter` isn't
+/// checked, as it can depend on the message. Always false if the logger isn't initialized.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let mut config = LogConfig::new ();
+/// config.parse_filters ("info,my_app::gpu=trace").unwrap ();
+/// ion_log::init (&config).unwrap ();
+///
+/// assert! (ion_log::enabled (LogLevel::Trace, "my_app::gpu::shaders"));
+/// assert! (!ion_log::enabled (LogLevel::Debug, "my_app::audio"));
+/// # ion_log::release ();
+/// ```
+pub fn enabled (level: LogLevel, target: &str) -> bool {
+
+    let level = match level.to_log_level () {
+
+        Some (level) => level,
+        None => return false,
+    };
+
+    match *LOGGER.read ().unwrap () {
+
+        Some (ref logger) => logger.level_enabled (level, target),
+        None => false,
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Returns the number of records dropped by `OverflowPolicy::Drop` since the process started,
+/// because the async queue was full or over `LogConfig::queue_memory`. Records logged after the
+/// background thread has stopped, such as after a panic while writing, are counted too.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let mut config = LogConfig::new ();
+/// config.async_output    = true;
+/// config.queue_memory    = 64 * 1024;
+/// config.overflow_policy = OverflowPolicy::Drop;
+///
+/// ion_log::init (&config).unwrap ();
+/// println! ("{} records dropped", ion_log::overflow_count ());
+/// # ion_log::release ();
+/// ```
+pub fn overflow_count () -> usize {
+    OVERFLOWS.load (Ordering::Relaxed)
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Returns the status of each sink of the running logger.
+///
+/// A sink is `Degraded` after a failed write and `Down` after several in a row, and goes back
+/// to `Ok` on the next successful write. Only enabled sinks are listed, and the list is empty
+/// if the logger isn't initialized.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let config = LogConfig::new ();
+/// ion_log::init (&config).unwrap ();
+///
+/// let health = ion_log::health ();
+///
+/// assert_eq! (health.len (), 1);
+/// assert_eq! (health[0].name, "terminal");
+/// assert_eq! (health[0].status, SinkStatus::Ok);
+/// # ion_log::release ();
+/// ```
+pub fn health () -> Vec<SinkHealth> {
+
+    match *LOGGER.read ().unwrap () {
+
+        Some (ref logger) => logger.output.lock ().unwrap ().health (),
+        None => Vec::new (),
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Prepares the logger for a call to `fork`.
+///
+/// A forked child inherits the parent's file handles and buffers, but not its threads, so
+/// logging across a fork can duplicate or corrupt output. Call this just before forking,
+/// then `post_fork_parent` in the parent and `post_fork_child` in the child.
+///
+/// This stops the async writer, admin server and heartbeat threads, and writes out all buffered
+/// records. Other threads shouldn't log between this and the fork, as a lock held at the fork
+/// stays held in the child. Does nothing if the logger isn't initialized.
+///
+/// # Errors
+/// Fails if the buffered records can't be written.
+///
+/// # Examples
+/// ```no_run
+/// # use ion_log::*;
+/// # fn fork () -> i32 { 0 }
+/// # let config = LogConfig::new ();
+/// ion_log::init (&config).unwrap ();
+///
+/// ion_log::prepare_fork ().unwrap ();
+///
+/// match fork () {
+///
+///     0 => ion_log::post_fork_child ().unwrap (),
+///     _ => ion_log::post_fork_parent ().unwrap (),
+/// }
+/// ```
+pub fn prepare_fork () -> io::Result<()> {
+
+    // The admin server and heartbeat read the logger, so they're stopped without holding the lock.
+    let (admin, heartbeat) = match *LOGGER.write ().unwrap () {
+
+        Some (ref mut logger) => (logger.admin.take (), logger.heartbeat.take ()),
+        None => return Ok (()),
+    };
+
+    if let Some (admin) = admin {
+        admin.stop ();
+    }
+
+    if let Some (heartbeat) = heartbeat {
+        heartbeat.stop ();
+    }
+
+    match *LOGGER.write ().unwrap () {
+
+        Some (ref mut logger) => logger.prepare_fork (),
+        None => Ok (()),
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Resumes logging in the parent after a fork, restarting the async writer, admin server and
+/// heartbeat threads.
+///
+/// # Errors
+/// Fails if a thread can't be started, or the admin endpoint can't be bound again.
+pub fn post_fork_parent () -> io::Result<()> {
+
+    match *LOGGER.write ().unwrap () {
+
+        Some (ref mut logger) => logger.post_fork_parent (),
+        None => Ok (()),
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Resumes logging in a forked child.
+///
+/// The inherited file handles are replaced with the child's own, the async writer and heartbeat
+/// threads are started again, and the child gets a new session ID. Text output is appended to
+/// the parent's file, while binary output moves to a new file at `<log_output_path>.<pid>`, as
+/// the two processes can't share a string table. The admin server isn't started in the child,
+/// as its endpoint belongs to the parent.
+///
+/// # Errors
+/// Fails if the output files can't be reopened or a thread can't be started.
+pub fn post_fork_child () -> io::Result<()> {
+
+    match *LOGGER.write ().unwrap () {
+
+        Some (ref mut logger) => logger.post_fork_child (),
+        None => Ok (()),
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Returns the ID generated for this logging session at `init`, as a version 4 UUID.
+///
+/// Binary logs store it with every record, and `LogConfig::session_in_text` adds it to text
+/// lines, so records from several runs or instances can be told apart. The ID is kept across
+/// `reconfigure`, and is `None` if the logger isn't initialized.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let config = LogConfig::new ();
+/// ion_log::init (&config).unwrap ();
+///
+/// let session_id = ion_log::session_id ().unwrap ();
+/// assert_eq! (session_id.len (), 36);
+/// assert_eq! (&session_id[14..15], "4");
+/// # ion_log::release ();
+/// ```
+pub fn session_id () -> Option<String> {
+
+    LOGGER.read ().unwrap ()
+          .as_ref ()
+          .map (|logger| logger.output.lock ().unwrap ().session_id.clone ())
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Returns the latest warning and error records, oldest first, for a status page or crash
+/// report.
+///
+/// Up to `LogConfig::recent_errors` records are kept. They are kept across `reconfigure`
+/// unless that changes, and the list is empty if the logger isn't initialized.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate log;
+/// # extern crate ion_log;
+/// # use ion_log::*;
+/// # fn main () {
+/// let mut config = LogConfig::new ();
+/// config.recent_errors = 16;
+/// ion_log::init (&config).unwrap ();
+///
+/// warn! ("Disk nearly full");
+/// info! ("Still running");
+///
+/// let recent = ion_log::recent_errors ();
+///
+/// assert_eq! (recent.len (), 1);
+/// assert_eq! (recent[0].message, "Disk nearly full");
+/// # ion_log::release ();
+/// # }
+/// ```
+pub fn recent_errors () -> Vec<RecentError> {
+
+    LOGGER.read ().unwrap ()
+          .as_ref ()
+          .map_or (Vec::new (), |logger| logger.recent_errors.get ())
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Returns the uptime, the records logged since the last call and the async queue depth, for
+// the heartbeat.
+fn heartbeat_stats () -> Option<(Duration, u64, usize)> {
+
+    LOGGER.read ().unwrap ().as_ref ().map (|logger| {
+
+        let uptime = logger.config.clock.now ().duration_since (logger.started).unwrap_or_default ();
+        (uptime, logger.records.swap (0, Ordering::Relaxed), metrics::queue_depth ())
+    })
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Returns a copy of the running logger's config, for the admin server, `test::channel` and
+// `bench::run` to change.
+fn current_config () -> Option<LogConfig> {
+    LOGGER.read ().unwrap ().as_ref ().map (|logger| logger.config.clone ())
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Applies a new configuration to the running logger.
+///
+/// Sinks, the output file and the maximum log level are all replaced.
+/// If the output path, format, encoding and index setting are unchanged, the current file is
+/// kept open rather than truncated.
+///
+/// # Errors
+/// Fails if the logger has not been initialized, or the new output file can't be created.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let mut config = LogConfig::new ();
+/// ion_log::init (&config).unwrap ();
+///
+/// config.max_log_level = LogLevel::Warn;
+/// ion_log::reconfigure (&config).unwrap ();
+/// # ion_log::release ();
+/// ```
+///
+/// An unchanged rotation keeps its schedule, so a file still rotates at midnight when the
+/// logger is reconfigured after it:
+/// ```
+/// # #[macro_use] extern crate log;
+/// # extern crate ion_log;
+/// # use ion_log::*;
+/// # use std::sync::Arc;
+/// # use std::time::{Duration, UNIX_EPOCH};
+/// # fn main () {
+/// # let directory = std::env::temp_dir ().join (format! ("ion_log_reconfigure_{}", std::process::id ()));
+/// # std::fs::create_dir_all (&directory).unwrap ();
+/// // 2016-07-31 23:59:50 UTC.
+/// let clock = Arc::new (MockClock::new (UNIX_EPOCH + Duration::from_secs (1_470_009_590)));
+///
+/// let mut config = LogConfig::new ();
+/// config.log_to_io       = false;
+/// config.log_to_file     = true;
+/// config.log_output_path = directory.join ("app.log").to_string_lossy ().into_owned ();
+/// config.clock           = clock.clone ();
+/// config.rotation        = Some (Rotation::daily ());
+///
+/// ion_log::init (&config).unwrap ();
+/// info! ("Before midnight");
+///
+/// clock.advance (Duration::from_secs (20));
+/// config.max_log_level = LogLevel::Debug;
+/// ion_log::reconfigure (&config).unwrap ();
+///
+/// info! ("After midnight");
+/// ion_log::flush ().unwrap ();
+///
+/// let rotated = std::fs::read_to_string (directory.join ("app.1.log")).unwrap ();
+/// assert! (rotated.contains ("Before midnight") && !rotated.contains ("After midnight"));
+/// # ion_log::release ();
+/// # std::fs::remove_dir_all (&directory).unwrap ();
+/// # }
+/// ```
+pub fn reconfigure (config: &LogConfig) -> io::Result<()> {
+
+    match *LOGGER.write ().unwrap () {
+
+        Some (ref mut logger) => logger.reconfigure (config),
+        None => Err (io::Error::other ("the logger has not been initialized")),
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Returns a logger handle for the named component.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let config = LogConfig::new ();
+/// ion_log::init (&config).unwrap ();
+///
+/// let renderer = ion_log::scoped ("renderer");
+/// renderer.info (format_args! ("Swapchain created"));
+/// # ion_log::release ();
+/// ```
+pub fn scoped (name: &'static str) -> ScopedLogger {
+    ScopedLogger {name}
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Attaches a distributed trace and span ID to records logged from the current thread.
+///
+/// The IDs are written along with each record in the binary format, and are included
+/// in `reader::Record`, so records can be correlated with traces. They stay set until
+/// replaced or cleared with `clear_trace_context`.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// ion_log::set_trace_context ("4bf92f3577b34da6a3ce929d0e0e4736", "00f067aa0ba902b7");
+/// // ... handle the traced request ...
+/// ion_log::clear_trace_context ();
+/// ```
+pub fn set_trace_context (trace_id: &str, span_id: &str) {
+
+    TRACE_CONTEXT.with (|context| {
+
+        *context.borrow_mut () = Some (TraceContext {
+
+            trace_id: trace_id.to_string (),
+            span_id:  span_id.to_string (),
+        });
+    });
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Removes the trace and span ID from the current thread.
+pub fn clear_trace_context () {
+    TRACE_CONTEXT.with (|context| *context.borrow_mut () = None);
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Used by `audit!`.
+#[doc (hidden)]
+pub fn __audit (module_path: &str, line: u32, args: fmt::Arguments) -> io::Result<()> {
+
+    match *LOGGER.read ().unwrap () {
+
+        Some (Logger {audit_log: Some (ref audit_log), ref output, ref config, ..}) => {
+
+            let session_id = output.lock ().unwrap ().session_id.clone ();
+            audit_log.write (config.clock.now (), module_path, line, &args.to_string (), &session_id)
+        },
+
+        Some (_) => Err (io::Error::other ("no audit log is configured")),
+        None => Err (io::Error::other ("the logger isn't initialized")),
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Used by `notice!`, `critical!` and `fatal!`. Logs the record with the severity's tag.
+#[doc (hidden)]
+pub fn __log_severity (severity: Severity,
+                       target: &str,
+                       module_path: &'static str,
+                       file: &'static str,
+                       line: u32,
+                       args: fmt::Arguments) {
+
+    let level = severity.level ();
+
+    if level <= log::__static_max_level () && level <= log::max_log_level () {
+
+        let location = log::LogLocation {
+
+            __module_path: module_path,
+            __file:        file,
+            __line:        line,
+        };
+
+        __with_tags (&[severity.tag ()], || log::__log (level, target, &location, args));
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Used by `fatal! (exit: ...)`. Releases the logger and exits, or flushes and panics.
+#[doc (hidden)]
+pub fn __fatal_exit (code: i32) -> ! {
+
+    let panics = LOGGER.read ().unwrap ().as_ref ().is_some_and (|logger| logger.config.fatal_panics);
+
+    if panics {
+
+        drop (flush ());
+        panic! ("fatal error, exit code {}", code);
+    }
+
+    // Unlike `try_release`, this can't fail, as there's no one left to report it to.
+    if log::shutdown_logger ().is_ok () {
+
+        let logger = LOGGER.write ().unwrap ().take ();
+
+        if let Some (logger) = logger {
+            drop (logger.release ());
+        }
+    }
+
+    process::exit (code)
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Used by `tagged!`. Runs the function with the tags added to the current thread's tags.
+#[doc (hidden)]
+pub fn __with_tags<F: FnOnce () -> T, T> (tags: &[&'static str], function: F) -> T {
+
+    // Removes the tags again, even if the function panics.
+    struct Restore (usize);
+
+    impl Drop for Restore {
+
+        fn drop (&mut self) {
+            TAGS.with (|tags| tags.borrow_mut ().truncate (self.0));
+        }
+    }
+
+    let _restore = Restore (TAGS.with (|current| {
+
+        let mut current = current.borrow_mut ();
+        let length = current.len ();
+
+        current.extend_from_slice (tags);
+        length
+    }));
+
+    function ()
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Drops every record from the target, regardless of level, until it is unblocked.
+///
+/// The target is matched like a module filter, so it covers child modules and may contain
+/// `*` wildcards. This does nothing if the logger isn't initialized.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let config = LogConfig::new ();
+/// ion_log::init (&config).unwrap ();
+///
+/// ion_log::block_target ("hyper::proto");
+/// // ...
+/// ion_log::unblock_target ("hyper::proto");
+/// # ion_log::release ();
+/// ```
+pub fn block_target (target: &str) {
+
+    if let Some (ref mut logger) = *LOGGER.write ().unwrap () {
+
+        if !logger.config.blocked_targets.iter ().any (|blocked| blocked == target) {
+            logger.config.blocked_targets.push (target.to_string ());
+        }
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Removes a target added with `block_target` or `LogConfig::blocked_targets`.
+pub fn unblock_target (target: &str) {
+
+    if let Some (ref mut logger) = *LOGGER.write ().unwrap () {
+        logger.config.blocked_targets.retain (|blocked| blocked != target);
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Marks a library's targets as opt-in, so their records are dropped unless the application
+/// allows them with `allow_targets` or `LogConfig::allowed_targets`.
+///
+/// This is meant for libraries, and may be called before the logger is initialized. The
+/// targets are matched like module filters, so `my_lib` covers every module in the crate.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate log;
+/// # extern crate ion_log;
+/// # fn main () {
+/// // In the library.
+/// ion_log::opt_in_targets (&["rust_out"]);
+///
+/// // In the application, to see the library's records.
+/// let mut config = ion_log::LogConfig::new ();
+/// config.allowed_targets.push ("rust_out".to_string ());
+/// ion_log::init (&config).unwrap ();
+///
+/// info! ("Logged, as the application allowed it");
+/// # ion_log::release ();
+/// # }
+/// ```
+pub fn opt_in_targets (targets: &[&str]) {
+
+    let mut opt_in = OPT_IN_TARGETS.write ().unwrap ();
+
+    for target in targets {
+
+        if !opt_in.iter ().any (|existing| existing == target) {
+            opt_in.push (target.to_string ());
+        }
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Logs records from targets marked with `opt_in_targets`, as `LogConfig::allowed_targets`
+/// does. This does nothing if the logger isn't initialized.
+pub fn allow_targets (targets: &[&str]) {
+
+    if let Some (ref mut logger) = *LOGGER.write ().unwrap () {
+
+        for target in targets {
+
+            if !logger.config.allowed_targets.iter ().any (|allowed| allowed == target) {
+                logger.config.allowed_targets.push (target.to_string ());
+            }
+        }
+    }
 }
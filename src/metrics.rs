@@ -0,0 +1,165 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Pipeline metrics, reported through the `metrics` crate when the `metrics` feature is on.
+//!
+//! Without the feature nothing is reported, though the async queue depth is still counted
+//! for heartbeats.
+/*===============================================================================================*/
+
+// Module imports
+#[cfg (feature = "metrics")]
+use metrics_crate::{counter, describe_counter, describe_gauge, gauge, Unit};
+
+use log;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/*===============================================================================================*/
+/*------STATICS----------------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// The number of records waiting in the async queue.
+static QUEUE_DEPTH: AtomicUsize = AtomicUsize::new (0);
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Registers descriptions for every metric, for exporters that show them.
+#[cfg (feature = "metrics")]
+pub fn describe () {
+
+    describe_counter! ("ion_log_records_total",       "Records accepted by the logger, by level");
+    describe_gauge!   ("ion_log_queue_depth",         "Records waiting in the async queue");
+    describe_counter! ("ion_log_dropped_total",       "Records lost because a sink failed");
+    describe_counter! ("ion_log_overflow_total",      "Records dropped because the async queue was full");
+    describe_counter! ("ion_log_bytes_written_total", Unit::Bytes, "Bytes written to all sinks");
+    describe_counter! ("ion_log_sink_errors_total",   "Failed writes, by sink");
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Counts a record that passed every filter.
+#[cfg (feature = "metrics")]
+pub fn record (level: log::LogLevel) {
+    counter! ("ion_log_records_total", "level" => level_name (level)).increment (1);
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Counts a record added to the async queue, returning the number now waiting.
+pub fn queued () -> usize {
+
+    let depth = QUEUE_DEPTH.fetch_add (1, Ordering::Relaxed) + 1;
+
+    report_queue_depth (depth);
+    depth
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Counts a record taken off the async queue.
+pub fn dequeued () {
+    report_queue_depth (QUEUE_DEPTH.fetch_sub (1, Ordering::Relaxed) - 1);
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Returns the number of records waiting in the async queue.
+pub fn queue_depth () -> usize {
+    QUEUE_DEPTH.load (Ordering::Relaxed)
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Counts records that couldn't be written.
+#[cfg (feature = "metrics")]
+pub fn dropped (count: usize) {
+    counter! ("ion_log_dropped_total").increment (count as u64);
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Counts a record dropped by the overflow policy.
+#[cfg (feature = "metrics")]
+pub fn overflow () {
+    counter! ("ion_log_overflow_total").increment (1);
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Counts bytes written to a sink.
+#[cfg (feature = "metrics")]
+pub fn bytes_written (count: usize) {
+    counter! ("ion_log_bytes_written_total").increment (count as u64);
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Counts a failed write to a sink: `terminal`, `file` or `mock`.
+#[cfg (feature = "metrics")]
+pub fn sink_error (sink: &'static str) {
+    counter! ("ion_log_sink_errors_total", "sink" => sink).increment (1);
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+#[cfg (not (feature = "metrics"))]
+pub fn describe () {}
+
+#[cfg (not (feature = "metrics"))]
+pub fn record (_level: log::LogLevel) {}
+
+#[cfg (not (feature = "metrics"))]
+pub fn dropped (_count: usize) {}
+
+#[cfg (not (feature = "metrics"))]
+pub fn overflow () {}
+
+#[cfg (not (feature = "metrics"))]
+pub fn bytes_written (_count: usize) {}
+
+#[cfg (not (feature = "metrics"))]
+pub fn sink_error (_sink: &'static str) {}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+#[cfg (feature = "metrics")]
+fn report_queue_depth (depth: usize) {
+    gauge! ("ion_log_queue_depth").set (depth as f64);
+}
+
+#[cfg (not (feature = "metrics"))]
+fn report_queue_depth (_depth: usize) {}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+#[cfg (feature = "metrics")]
+fn level_name (level: log::LogLevel) -> &'static str {
+
+    match level {
+
+        log::LogLevel::Error => "error",
+        log::LogLevel::Warn  => "warn",
+        log::LogLevel::Info  => "info",
+        log::LogLevel::Debug => "debug",
+        log::LogLevel::Trace => "trace",
+    }
+}
@@ -0,0 +1,193 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! A sink for testing how applications and the logger behave when output goes wrong.
+/*===============================================================================================*/
+
+// Module imports
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+/*===============================================================================================*/
+/*------MOCK SINK STRUCT-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// A sink whose writes can be scripted to fail, block or be delayed, set with
+/// `LogConfig::mock_sink`.
+///
+/// It receives every record as a text line, like the terminal, and shows up in
+/// `ion_log::health` as `mock`. Clones share the same sink, so a test can keep one to script
+/// and inspect while the logger writes to another.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate log;
+/// # extern crate ion_log;
+/// # use ion_log::*;
+/// # fn main () {
+/// let sink = MockSink::new ();
+///
+/// let mut config = LogConfig::new ();
+/// config.log_to_io = false;
+/// config.mock_sink = Some (sink.clone ());
+///
+/// ion_log::init (&config).unwrap ();
+/// info! ("Connected");
+///
+/// sink.fail_next (3);
+/// warn! ("Lost");
+/// warn! ("Lost");
+/// warn! ("Lost");
+///
+/// assert_eq! (sink.lines ().len (), 1);
+/// assert! (sink.lines ()[0].ends_with ("INFO: Connected"));
+/// assert! (matches! (ion_log::health ()[0].status, SinkStatus::Down {..}));
+///
+/// info! ("Reconnected");
+/// assert_eq! (ion_log::health ()[0].status, SinkStatus::Ok);
+/// # ion_log::release ();
+/// # }
+/// ```
+#[derive (Clone, Debug, Default)]
+pub struct MockSink {
+
+    // Private
+    state: Arc<(Mutex<MockState>, Condvar)>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+#[derive (Debug, Default)]
+struct MockState {
+
+    // Private
+    lines: Vec<String>,
+    fail_next: u32,
+    failing: bool,
+    blocked: bool,
+    delay: Duration,
+}
+
+/*===============================================================================================*/
+/*------MOCK SINK PUBLIC METHODS-----------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl MockSink {
+
+    /// Returns a sink which accepts every write.
+    pub fn new () -> Self {
+        MockSink::default ()
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Returns the lines written so far, without their newlines.
+    pub fn lines (&self) -> Vec<String> {
+        self.lock ().lines.clone ()
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Forgets the lines written so far.
+    pub fn clear (&self) {
+        self.lock ().lines.clear ();
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Makes the next few writes fail. Each write may hold several records in
+    /// asynchronous mode.
+    pub fn fail_next (&self, writes: u32) {
+        self.lock ().fail_next = writes;
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Makes every write fail until turned off again.
+    pub fn set_failing (&self, failing: bool) {
+        self.lock ().failing = failing;
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Makes writes wait until `unblock` is called, as a stalled sink would.
+    ///
+    /// In synchronous mode this blocks the logging thread. In asynchronous mode it blocks
+    /// the background thread, so the queue fills up behind it.
+    pub fn block (&self) {
+        self.lock ().blocked = true;
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Lets blocked writes continue.
+    pub fn unblock (&self) {
+
+        self.lock ().blocked = false;
+        self.state.1.notify_all ();
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Makes each write take at least the given time, as a slow sink would.
+    pub fn set_delay (&self, delay: Duration) {
+        self.lock ().delay = delay;
+    }
+}
+
+/*===============================================================================================*/
+/*------MOCK SINK PRIVATE METHODS----------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl MockSink {
+
+    fn lock (&self) -> MutexGuard<'_, MockState> {
+        self.state.0.lock ().unwrap ()
+    }
+}
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Writes newline terminated lines to the sink, failing or waiting as scripted.
+pub fn write (sink: &MockSink, text: &str) -> io::Result<()> {
+
+    let delay = {
+
+        let (ref state, ref unblocked) = *sink.state;
+        let state = unblocked.wait_while (state.lock ().unwrap (), |state| state.blocked).unwrap ();
+        state.delay
+    };
+
+    if delay > Duration::from_secs (0) {
+        thread::sleep (delay);
+    }
+
+    let mut state = sink.lock ();
+
+    if state.failing || state.fail_next > 0 {
+
+        state.fail_next = state.fail_next.saturating_sub (1);
+        return Err (io::Error::other ("injected failure"));
+    }
+
+    state.lines.extend (text.lines ().map (str::to_string));
+    Ok (())
+}
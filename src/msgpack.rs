@@ -0,0 +1,306 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! The MessagePack log format, and a decoder for reading it back.
+/*===============================================================================================*/
+
+// Module imports
+use format::{self, JsonRecord};
+#[cfg (feature = "binary")]
+use json::Value;
+use JSON_SCHEMA;
+
+#[cfg (feature = "binary")]
+use std::str;
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Appends a record as a map with the fields of the JSON layout, framed by its length as a
+// big endian u32. The timestamp is microseconds since the Unix epoch rather than a string.
+pub fn record<T: AsRef<str>> (out: &mut Vec<u8>, record: &JsonRecord<T>) {
+
+    let frame_start = out.len ();
+    out.extend_from_slice (&[0; 4]);
+
+    let optional = [("trace_id",    record.trace_id),
+                    ("span_id",     record.span_id),
+                    ("session_id",  record.session_id),
+                    ("app_name",    record.app_name),
+                    ("instance_id", record.instance_id)];
+
+    let fields = 8 + optional.iter ().filter (|(_, value)| value.is_some ()).count () +
+                 if record.tags.is_empty () {0} else {1};
+
+    map_header (out, fields);
+    string (out, "schema");
+    uint (out, u64::from (JSON_SCHEMA));
+    string (out, "timestamp");
+    uint (out, format::timestamp_micros (record.timestamp));
+    string (out, "level");
+    string (out, &record.level.to_string ());
+    string (out, "target");
+    string (out, record.target);
+    string (out, "module_path");
+    string (out, record.module_path);
+    string (out, "line");
+    uint (out, u64::from (record.line));
+    string (out, "message");
+    string (out, record.message);
+
+    for (name, value) in optional.iter () {
+
+        if let Some (value) = *value {
+
+            string (out, name);
+            string (out, value);
+        }
+    }
+
+    string (out, "severity");
+    string (out, format::severity_name (record.level, record.severity));
+
+    if !record.tags.is_empty () {
+
+        string (out, "tags");
+        array_header (out, record.tags.len ());
+
+        for tag in record.tags {
+            string (out, tag.as_ref ());
+        }
+    }
+
+    let length = (out.len () - frame_start - 4) as u32;
+    out[frame_start..frame_start + 4].copy_from_slice (&length.to_be_bytes ());
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Decodes a single MessagePack value, which must fill the whole of the input.
+// Binary and extension values aren't used by the log format, so aren't accepted.
+#[cfg (feature = "binary")]
+pub fn decode (input: &[u8]) -> Option<Value> {
+
+    let mut decoder = Decoder {input, position: 0};
+    let value = decoder.value ()?;
+
+    if decoder.position == input.len () {Some (value)} else {None}
+}
+
+/*===============================================================================================*/
+/*------DECODER STRUCT---------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+#[cfg (feature = "binary")]
+struct Decoder<'a> {
+
+    // Private
+    input: &'a [u8],
+    position: usize,
+}
+
+/*===============================================================================================*/
+/*------DECODER PRIVATE METHODS------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+#[cfg (feature = "binary")]
+impl<'a> Decoder<'a> {
+
+    fn value (&mut self) -> Option<Value> {
+
+        let marker = self.bytes (1)?[0];
+
+        match marker {
+
+            0x00 ..= 0x7f => Some (Value::Number (f64::from (marker))),
+            0x80 ..= 0x8f => self.map (usize::from (marker & 0x0f)),
+            0x90 ..= 0x9f => self.array (usize::from (marker & 0x0f)),
+            0xa0 ..= 0xbf => self.string (usize::from (marker & 0x1f)),
+            0xc0 => Some (Value::Null),
+            0xc2 => Some (Value::Bool (false)),
+            0xc3 => Some (Value::Bool (true)),
+            0xca => Some (Value::Number (f64::from (f32::from_bits (self.uint (4)? as u32)))),
+            0xcb => Some (Value::Number (f64::from_bits (self.uint (8)?))),
+            0xcc => Some (Value::Number (self.uint (1)? as f64)),
+            0xcd => Some (Value::Number (self.uint (2)? as f64)),
+            0xce => Some (Value::Number (self.uint (4)? as f64)),
+            0xcf => Some (Value::Number (self.uint (8)? as f64)),
+            0xd0 => Some (Value::Number (f64::from (self.uint (1)? as u8 as i8))),
+            0xd1 => Some (Value::Number (f64::from (self.uint (2)? as u16 as i16))),
+            0xd2 => Some (Value::Number (f64::from (self.uint (4)? as u32 as i32))),
+            0xd3 => Some (Value::Number (self.uint (8)? as i64 as f64)),
+
+            // Strings have 8, 16 and 32 bit lengths, arrays and maps 16 and 32 bit ones.
+            0xd9 ..= 0xdb => {
+
+                let length = self.uint (1 << (marker - 0xd9))?;
+                self.string (length as usize)
+            },
+
+            0xdc | 0xdd => {
+
+                let length = self.uint (2 << (marker - 0xdc))?;
+                self.array (length as usize)
+            },
+
+            0xde | 0xdf => {
+
+                let length = self.uint (2 << (marker - 0xde))?;
+                self.map (length as usize)
+            },
+
+            0xe0 ..= 0xff => Some (Value::Number (f64::from (marker as i8))),
+            _ => None,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn bytes (&mut self, count: usize) -> Option<&'a [u8]> {
+
+        let bytes = self.input.get (self.position..self.position.checked_add (count)?)?;
+
+        self.position += count;
+        Some (bytes)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Reads a big endian unsigned integer of the given number of bytes.
+    fn uint (&mut self, size: usize) -> Option<u64> {
+        Some (self.bytes (size)?.iter ().fold (0, |value, &byte| value << 8 | u64::from (byte)))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn string (&mut self, length: usize) -> Option<Value> {
+        str::from_utf8 (self.bytes (length)?).ok ().map (|text| Value::String (text.to_string ()))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // The length comes from the input, so the vector grows as items are read rather than
+    // being allocated up front.
+    fn array (&mut self, length: usize) -> Option<Value> {
+
+        let mut items = Vec::new ();
+
+        for _ in 0..length {
+            items.push (self.value ()?);
+        }
+
+        Some (Value::Array (items))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn map (&mut self, length: usize) -> Option<Value> {
+
+        let mut members = Vec::new ();
+
+        for _ in 0..length {
+
+            let name = match self.value ()? {
+
+                Value::String (name) => name,
+                _ => return None,
+            };
+
+            members.push ((name, self.value ()?));
+        }
+
+        Some (Value::Object (members))
+    }
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+fn uint (out: &mut Vec<u8>, value: u64) {
+
+    match value {
+
+        0 ..= 0x7f => out.push (value as u8),
+        0x80 ..= 0xff => out.extend_from_slice (&[0xcc, value as u8]),
+        0x100 ..= 0xffff => {
+
+            out.push (0xcd);
+            out.extend_from_slice (&(value as u16).to_be_bytes ());
+        },
+
+        0x1_0000 ..= 0xffff_ffff => {
+
+            out.push (0xce);
+            out.extend_from_slice (&(value as u32).to_be_bytes ());
+        },
+
+        _ => {
+
+            out.push (0xcf);
+            out.extend_from_slice (&value.to_be_bytes ());
+        },
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+fn string (out: &mut Vec<u8>, text: &str) {
+
+    length_header (out, text.len (), 0xa0, 32, [0xd9, 0xda, 0xdb]);
+    out.extend_from_slice (text.as_bytes ());
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+fn array_header (out: &mut Vec<u8>, length: usize) {
+    length_header (out, length, 0x90, 16, [0, 0xdc, 0xdd]);
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+fn map_header (out: &mut Vec<u8>, length: usize) {
+    length_header (out, length, 0x80, 16, [0, 0xde, 0xdf]);
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Writes the smallest header for the length: the fixed form if it's under `fixed_limit`, or
+// else the 8, 16 or 32 bit marker. Maps and arrays have no 8 bit form, marked by a zero.
+fn length_header (out: &mut Vec<u8>, length: usize, fixed: u8, fixed_limit: usize, markers: [u8; 3]) {
+
+    if length < fixed_limit {
+        out.push (fixed | length as u8);
+    }
+
+    else if length <= 0xff && markers[0] != 0 {
+        out.extend_from_slice (&[markers[0], length as u8]);
+    }
+
+    else if length <= 0xffff {
+
+        out.push (markers[1]);
+        out.extend_from_slice (&(length as u16).to_be_bytes ());
+    }
+
+    else {
+
+        out.push (markers[2]);
+        out.extend_from_slice (&(length as u32).to_be_bytes ());
+    }
+}
@@ -0,0 +1,207 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! The threads serializing JSON, MessagePack and CBOR file records for the background writer,
+//! set with `LogConfig::format_threads`.
+//!
+//! A batch is handed to the pool as soon as it's taken from the queue, so it's serialized
+//! while the background thread writes the batch before it.
+/*===============================================================================================*/
+
+// Module imports
+use FileEncoding;
+use LogFormat;
+use LogOutput;
+use QueuedEntry;
+
+use std::cmp;
+use std::io;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+// The fewest records given to each thread, below which threads cost more than they save.
+pub const CHUNK_RECORDS: usize = 64;
+
+/*===============================================================================================*/
+/*------FORMAT POOL STRUCT-----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// The serializing threads, and the queue of chunks for them.
+pub struct FormatPool {
+
+    // Private
+    jobs: Sender<Job>,
+    threads: Vec<JoinHandle<()>>,
+    settings: Arc<FormatSettings>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// What every record in the file is serialized with.
+pub struct FormatSettings {
+
+    // Public
+    pub format: LogFormat,
+    pub encoding: FileEncoding,
+    pub session_id: String,
+    pub app_name: Option<String>,
+    pub instance_id: Option<String>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// A batch handed to the pool, whose records are still being serialized.
+pub struct FormattedBatch {
+
+    // Private
+    entries: Arc<Vec<QueuedEntry>>,
+    chunks: usize,
+    results: Receiver<(usize, Vec<Vec<u8>>)>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// A range of a batch for one thread to serialize.
+struct Job {
+
+    // Private
+    entries: Arc<Vec<QueuedEntry>>,
+    range: Range<usize>,
+    settings: Arc<FormatSettings>,
+    reply: Sender<(usize, Vec<Vec<u8>>)>,
+}
+
+/*===============================================================================================*/
+/*------FORMAT POOL PUBLIC METHODS---------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl FormatPool {
+
+    // Starts the threads.
+    pub fn start (threads: usize, settings: FormatSettings) -> io::Result<Self> {
+
+        let (jobs, receiver) = mpsc::channel ();
+        let receiver = Arc::new (Mutex::new (receiver));
+        let threads = (0..threads).map (|_| {
+
+            let receiver = receiver.clone ();
+
+            thread::Builder::new ().name ("ion_log_format".to_string ())
+                                   .spawn (move || FormatPool::run (&receiver))
+        }).collect::<io::Result<_>> ()?;
+
+        Ok (FormatPool {jobs, threads, settings: Arc::new (settings)})
+    }
+
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Splits the batch between the threads, each getting at least `CHUNK_RECORDS` records.
+    pub fn format (&self, entries: Vec<QueuedEntry>) -> FormattedBatch {
+
+        let entries = Arc::new (entries);
+        let threads = cmp::max (cmp::min (self.threads.len (), entries.len () / CHUNK_RECORDS), 1);
+        let chunk_size = cmp::max (entries.len ().div_ceil (threads), 1);
+        let (reply, results) = mpsc::channel ();
+        let mut chunks = 0;
+
+        for start in (0..entries.len ()).step_by (chunk_size) {
+
+            let job = Job {
+
+                entries: entries.clone (),
+                range: start..cmp::min (start + chunk_size, entries.len ()),
+                settings: self.settings.clone (),
+                reply: reply.clone (),
+            };
+
+            drop (self.jobs.send (job));
+            chunks += 1;
+        }
+
+        FormattedBatch {entries, chunks, results}
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Closes the queue and waits for the threads to finish.
+    pub fn stop (self) {
+
+        drop (self.jobs);
+
+        for thread in self.threads {
+            drop (thread.join ());
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------FORMAT POOL PRIVATE METHODS--------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl FormatPool {
+
+    // Serializes chunks until the queue is closed.
+    fn run (jobs: &Mutex<Receiver<Job>>) {
+
+        let mut json_buffer = String::new ();
+
+        loop {
+
+            let job = match jobs.lock ().unwrap ().recv () {
+
+                Ok (job) => job,
+                Err (_) => break,
+            };
+
+            let settings = &*job.settings;
+            let ids = (settings.session_id.as_str (), &settings.app_name, &settings.instance_id);
+            let records = job.entries[job.range.clone ()].iter ().map (|entry| {
+
+                let mut out = Vec::new ();
+
+                LogOutput::encode_file_record (&entry.as_entry (), settings.format, settings.encoding, ids, &mut json_buffer, &mut out);
+                out
+            }).collect ();
+
+            drop (job.reply.send ((job.range.start, records)));
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------FORMATTED BATCH PUBLIC METHODS-----------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl FormattedBatch {
+
+    // Waits for the records, returning them with the batch's entries. If a thread failed,
+    // no records are returned, and they're serialized as they're written instead.
+    pub fn wait (self) -> (Arc<Vec<QueuedEntry>>, Vec<Vec<u8>>) {
+
+        let mut chunks: Vec<_> = self.results.iter ().take (self.chunks).collect ();
+
+        if chunks.len () < self.chunks {
+            return (self.entries, Vec::new ());
+        }
+
+        chunks.sort_by_key (|&(start, _)| start);
+        (self.entries, chunks.into_iter ().flat_map (|(_, records)| records).collect ())
+    }
+}
@@ -0,0 +1,1245 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Reads log files written in the binary, MessagePack and CBOR formats.
+//!
+//! # Example
+//! ```no_run
+//! use ion_log::reader::Reader;
+//!
+//! for record in Reader::open ("game.log").unwrap () {
+//!     print! ("{}", record.unwrap ().to_text ());
+//! }
+//! ```
+/*===============================================================================================*/
+
+// Module imports
+use binary;
+use cbor;
+use format;
+use json::{self, Value};
+use log;
+use msgpack;
+use {Severity, JSON_SCHEMA};
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, SeekFrom};
+use std::io::prelude::{Read, Seek};
+use std::mem;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// How far past the end of a time range `TimeRange` keeps reading, for records written out of order.
+// Files from a logger with a flight recorder give a longer time.
+const ORDER_SLACK: Duration = Duration::from_secs (1);
+
+/*===============================================================================================*/
+/*------RECORD STRUCT----------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// A record read back from a log file.
+///
+/// # Examples
+/// Every structured format keeps the severity of records logged with `notice!`, `critical!`
+/// and `fatal!`:
+///
+/// ```
+/// # #[macro_use] extern crate log;
+/// # #[macro_use] extern crate ion_log;
+/// # use ion_log::*;
+/// use ion_log::reader::{CborReader, MessagePackReader, Reader, Record};
+/// use std::fs;
+/// use std::io;
+///
+/// # fn main () {
+/// let mut config = LogConfig::new ();
+/// config.log_to_io = false;
+///
+/// ion_log::init (&config).unwrap ();
+///
+/// for &format in &[LogFormat::Json, LogFormat::MessagePack, LogFormat::Cbor, LogFormat::Binary] {
+///
+///     let path = std::env::temp_dir ().join (format! ("ion_log_severity_{:?}.log", format));
+/// #   drop (fs::remove_file (&path));
+///
+///     config.log_to_file     = true;
+///     config.log_output_path = path.to_str ().unwrap ().to_string ();
+///     config.log_format      = format;
+///
+///     ion_log::reconfigure (&config).unwrap ();
+///     notice! ("Configuration reloaded");
+///     critical! ("Replica 2 is out of sync");
+///     info! ("Resyncing");
+///     ion_log::flush ().unwrap ();
+///
+///     let records: io::Result<Vec<Record>> = match format {
+///
+///         LogFormat::Json        => fs::read_to_string (&path).unwrap ().lines ().map (Record::from_json).collect (),
+///         LogFormat::MessagePack => MessagePackReader::open (&path).unwrap ().collect (),
+///         LogFormat::Cbor        => CborReader::open (&path).unwrap ().collect (),
+///         _                      => Reader::open (&path).unwrap ().collect (),
+///     };
+///
+///     let records = records.unwrap ();
+///     let severities: Vec<_> = records.iter ().map (|record| record.severity).collect ();
+///
+///     assert_eq! (severities, [Some (Severity::Notice), Some (Severity::Critical), None]);
+///     assert_eq! (Record::from_json (&records[1].to_json ()).unwrap (), records[1]);
+/// }
+/// # ion_log::release ();
+/// # }
+/// ```
+#[derive (Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+
+    // Public
+    /// The record level.
+    pub level:       log::LogLevel,
+    /// The time the record was logged.
+    pub timestamp:   SystemTime,
+    /// The record target.
+    pub target:      String,
+    /// The module path the record was logged from.
+    pub module_path: String,
+    /// The line the record was logged from.
+    pub line:        u32,
+    /// The record message.
+    pub message:     String,
+    /// The trace ID from the logging thread's trace context.
+    pub trace_id:    Option<String>,
+    /// The span ID from the logging thread's trace context.
+    pub span_id:     Option<String>,
+    /// The session ID of the logger that wrote the record.
+    pub session_id:  Option<String>,
+    /// The application name of the logger that wrote the record.
+    pub app_name:    Option<String>,
+    /// The instance ID of the logger that wrote the record.
+    pub instance_id: Option<String>,
+    /// The tags attached with `tagged!`.
+    pub tags:        Vec<String>,
+    /// The severity the record was logged with by `notice!`, `critical!` or `fatal!`.
+    pub severity:    Option<Severity>,
+}
+
+/*===============================================================================================*/
+/*------RECORD PUBLIC METHODS--------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Record {
+
+    /// Returns the record in the plain text layout, including the trailing newline.
+    pub fn to_text (&self) -> String {
+
+        let mut text = String::new ();
+        format::text_line (&mut text, format::level_name (self.level), &self.module_path, self.line, &self.message);
+        text
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Returns the record as a single line JSON object, without a trailing newline.
+    pub fn to_json (&self) -> String {
+
+        let mut json = String::new ();
+
+        format::json_line (&mut json, &format::JsonRecord {
+
+            level:       self.level,
+            timestamp:   self.timestamp,
+            target:      &self.target,
+            module_path: &self.module_path,
+            line:        self.line,
+            message:     &self.message,
+            trace_id:    self.trace_id.as_deref (),
+            span_id:     self.span_id.as_deref (),
+            session_id:  self.session_id.as_deref (),
+            app_name:    self.app_name.as_deref (),
+            instance_id: self.instance_id.as_deref (),
+            tags:        &self.tags,
+            severity:    self.severity,
+            collector:   false,
+        });
+
+        json
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Parses a line of the JSON Lines format, as described by `JSON_SCHEMA`.
+    ///
+    /// Fields it doesn't know are ignored, so lines from newer versions of the crate can still be
+    /// read. A line without a `schema` field is read as the first version.
+    ///
+    /// # Errors
+    /// Fails with `InvalidData` if the line isn't a JSON object with the required fields, or its
+    /// schema is newer than `JSON_SCHEMA`.
+    ///
+    /// # Examples
+    /// ```
+    /// use ion_log::reader::Record;
+    ///
+    /// let line = r#"{"schema":1,"timestamp":"2016-05-01T12:00:00.000000Z","level":"WARN","target":"app","module_path":"app::net","line":12,"message":"slow reply","host":"db1"}"#;
+    /// let record = Record::from_json (line).unwrap ();
+    ///
+    /// assert_eq! (record.message, "slow reply");
+    /// assert_eq! (Record::from_json (&record.to_json ()).unwrap (), record);
+    /// ```
+    pub fn from_json (line: &str) -> io::Result<Record> {
+
+        let value = match json::parse (line) {
+
+            Some (value @ Value::Object (_)) => value,
+            _ => return Err (binary::invalid_data ("not a JSON object")),
+        };
+
+        let timestamp = value.get ("timestamp").and_then (Value::as_str).and_then (format::parse_timestamp);
+        Record::from_value (&value, timestamp, "JSON")
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Parses a record in the MessagePack format, without the length it is framed by.
+    ///
+    /// The fields are those of the JSON layout, except that the timestamp is an integer count
+    /// of microseconds since the Unix epoch. To read a whole file, use `MessagePackReader`.
+    ///
+    /// # Errors
+    /// Fails with `InvalidData` if the data isn't a MessagePack map with the required fields,
+    /// or its schema is newer than `JSON_SCHEMA`.
+    ///
+    /// # Examples
+    /// ```
+    /// use ion_log::reader::Record;
+    ///
+    /// // {"timestamp": 0, "level": "INFO", "target": "app", "module_path": "app", "line": 1, "message": "hi"}
+    /// let data = b"\x86\xa9timestamp\x00\xa5level\xa4INFO\xa6target\xa3app\xabmodule_path\xa3app\xa4line\x01\xa7message\xa2hi";
+    /// let record = Record::from_msgpack (data).unwrap ();
+    ///
+    /// assert_eq! (record.message, "hi");
+    /// assert_eq! (record.line, 1);
+    /// ```
+    pub fn from_msgpack (data: &[u8]) -> io::Result<Record> {
+
+        let value = match msgpack::decode (data) {
+
+            Some (value @ Value::Object (_)) => value,
+            _ => return Err (binary::invalid_data ("not a MessagePack map")),
+        };
+
+        let timestamp = value.get ("timestamp").and_then (Value::as_f64).and_then (format::micros_timestamp);
+        Record::from_value (&value, timestamp, "MessagePack")
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Parses a record in the CBOR format, a map with the fields of the JSON layout. The
+    /// timestamp is an integer count of microseconds since the Unix epoch. To read a whole
+    /// file, use `CborReader`.
+    ///
+    /// # Errors
+    /// Fails with `InvalidData` if the data isn't a single CBOR map with the required fields,
+    /// or its schema is newer than `JSON_SCHEMA`.
+    ///
+    /// # Examples
+    /// ```
+    /// use ion_log::reader::Record;
+    ///
+    /// // {"timestamp": 0, "level": "INFO", "target": "app", "module_path": "app", "line": 1, "message": "hi"}
+    /// let data = b"\xa6\x69timestamp\x00\x65level\x64INFO\x66target\x63app\x6bmodule_path\x63app\x64line\x01\x67message\x62hi";
+    /// let record = Record::from_cbor (data).unwrap ();
+    ///
+    /// assert_eq! (record.message, "hi");
+    /// assert_eq! (record.line, 1);
+    /// ```
+    pub fn from_cbor (mut data: &[u8]) -> io::Result<Record> {
+
+        let value = cbor::decode (&mut data).map_err (|e| match e.kind () {
+
+            io::ErrorKind::UnexpectedEof => binary::invalid_data ("truncated CBOR record"),
+            _ => e,
+        })?;
+
+        match value {
+
+            Some (value @ Value::Object (_)) if data.is_empty () => Record::from_cbor_value (value),
+            _ => Err (binary::invalid_data ("not a CBOR map")),
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------RECORD PRIVATE METHODS-------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Record {
+
+    fn from_cbor_value (value: Value) -> io::Result<Record> {
+
+        let timestamp = value.get ("timestamp").and_then (Value::as_f64).and_then (format::micros_timestamp);
+        Record::from_value (&value, timestamp, "CBOR")
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Reads the fields shared by the JSON, MessagePack and CBOR formats, which only differ in how
+    // they write the timestamp.
+    fn from_value (value: &Value, timestamp: Option<SystemTime>, format_name: &str) -> io::Result<Record> {
+
+        let schema = match value.get ("schema") {
+
+            Some (schema) => schema.as_f64 ().ok_or_else (|| binary::invalid_data (&format! ("invalid {} schema version", format_name)))?,
+            None => 1.0,
+        };
+
+        if schema > f64::from (JSON_SCHEMA) {
+            return Err (binary::invalid_data (&format! ("unsupported {} schema version", format_name)));
+        }
+
+        let string = |name| value.get (name).and_then (Value::as_str).map (str::to_string);
+        let required = |name| string (name).ok_or_else (|| binary::invalid_data (&format! ("missing `{}` field", name)));
+
+        let timestamp = timestamp.ok_or_else (|| binary::invalid_data ("invalid `timestamp` field"))?;
+
+        let level = value.get ("level")
+                         .and_then (Value::as_str)
+                         .and_then (|level| level.parse ().ok ())
+                         .ok_or_else (|| binary::invalid_data ("invalid `level` field"))?;
+
+        let line = value.get ("line")
+                        .and_then (Value::as_f64)
+                        .filter (|&line| line >= 0.0 && line <= f64::from (u32::MAX) && line.fract () == 0.0)
+                        .ok_or_else (|| binary::invalid_data ("invalid `line` field"))?;
+
+        let tags: Vec<String> = value.get ("tags")
+                        .and_then (Value::as_array)
+                        .unwrap_or (&[])
+                        .iter ()
+                        .map (|tag| tag.as_str ().map (str::to_string))
+                        .collect::<Option<_>> ()
+                        .ok_or_else (|| binary::invalid_data ("invalid `tags` field"))?;
+
+        // Records written before the field was added only have the severity's tag.
+        let severity = match value.get ("severity") {
+
+            Some (name) => name.as_str ().map (format::parse_severity)
+                                         .ok_or_else (|| binary::invalid_data ("invalid `severity` field"))?,
+            None => format::tagged_severity (&tags),
+        };
+
+        Ok (Record {
+
+            level,
+            timestamp,
+            target:      required ("target")?,
+            module_path: required ("module_path")?,
+            line:        line as u32,
+            message:     required ("message")?,
+            trace_id:    string ("trace_id"),
+            span_id:     string ("span_id"),
+            session_id:  string ("session_id"),
+            app_name:    string ("app_name"),
+            instance_id: string ("instance_id"),
+            tags,
+            severity,
+        })
+    }
+}
+
+/*===============================================================================================*/
+/*------READER STRUCT----------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Iterates over the records in a binary log.
+pub struct Reader<R: Read> {
+
+    // Private
+    input: R,
+    strings: Vec<String>,
+    payload: Vec<u8>,
+    trace_context: Option<(String, String)>,
+    tags: Vec<String>,
+    severity: Option<Severity>,
+    session_id: Option<String>,
+    app_name: Option<String>,
+    instance_id: Option<String>,
+    slack: Duration,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Iterates over the records in a log written in `LogFormat::MessagePack`.
+#[derive (Debug)]
+pub struct MessagePackReader<R: Read> {
+
+    // Private
+    input: R,
+    frame: Vec<u8>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Iterates over the records in a log written in `LogFormat::Cbor`.
+#[derive (Debug)]
+pub struct CborReader<R: Read> {
+
+    // Private
+    input: R,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+/// Iterates over the records logged within a time range, returned by `Reader::range`.
+pub struct TimeRange<R: Read> {
+
+    // Private
+    reader: Reader<R>,
+    since: SystemTime,
+    until: SystemTime,
+}
+
+/*===============================================================================================*/
+/*------INDEX STRUCT-----------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// The sidecar index of a binary log, written when `LogConfig::log_file_index` is set.
+///
+/// The index is only as current as the last flush of the logger. Records written since
+/// aren't counted, but are still found by `Reader::seek`.
+#[derive (Clone, Debug)]
+pub struct Index {
+
+    // Private
+    session_id: Option<String>,
+    app_name: Option<String>,
+    instance_id: Option<String>,
+    slack: Duration,
+    strings: Vec<String>,
+    minutes: Vec<IndexMinute>,
+    length: u64,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+#[derive (Clone, Debug)]
+struct IndexMinute {
+
+    // Private
+    start: SystemTime,
+    offset: u64,
+    strings: usize,
+    counts: [u64; 5],
+}
+
+/*===============================================================================================*/
+/*------READER TRAIT IMPLEMENTATIONS-------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl<R: Read> Iterator for Reader<R> {
+
+    type Item = io::Result<Record>;
+
+    fn next (&mut self) -> Option<Self::Item> {
+
+        loop {
+
+            let tag = match binary::read_frame (&mut self.input, &mut self.payload) {
+
+                Ok (Some (tag)) => tag,
+                Ok (None) => return None,
+                Err (e) => return Some (Err (e)),
+            };
+
+            let result = match tag {
+
+                binary::FRAME_TARGET   => self.read_string ().map (|_| None),
+                binary::FRAME_RECORD   => self.read_record ().map (Some),
+                binary::FRAME_TRACE    => self.read_trace_context ().map (|_| None),
+                binary::FRAME_SESSION  => self.read_session ().map (|_| None),
+                binary::FRAME_TAGS     => self.read_tags ().map (|_| None),
+                binary::FRAME_APP      => self.read_app ().map (|_| None),
+                binary::FRAME_REORDER  => self.read_reorder ().map (|_| None),
+                binary::FRAME_SEVERITY => self.read_severity ().map (|_| None),
+                _ => Ok (None),
+            };
+
+            match result {
+
+                Ok (Some (record)) => return Some (Ok (record)),
+                Ok (None) => continue,
+                Err (e) => return Some (Err (e)),
+            }
+        }
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl<R: Read> fmt::Debug for Reader<R> {
+
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        f.debug_struct ("Reader")
+         .field ("strings", &self.strings.len ())
+         .finish ()
+    }
+}
+
+/*===============================================================================================*/
+/*------MESSAGE PACK READER PUBLIC METHODS-------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl MessagePackReader<BufReader<File>> {
+
+    /// Opens a MessagePack log file for reading.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[macro_use] extern crate log;
+    /// # extern crate ion_log;
+    /// # use ion_log::*;
+    /// use ion_log::reader::MessagePackReader;
+    ///
+    /// # fn main () {
+    /// # let path = std::env::temp_dir ().join ("ion_log_msgpack_example.log");
+    /// let mut config = LogConfig::new ();
+    /// config.log_to_file     = true;
+    /// config.log_output_path = path.to_str ().unwrap ().to_string ();
+    /// config.log_format      = LogFormat::MessagePack;
+    /// ion_log::init (&config).unwrap ();
+    ///
+    /// info! ("Loaded 12 textures");
+    /// ion_log::release ();
+    ///
+    /// let records: Vec<_> = MessagePackReader::open (&path).unwrap ()
+    ///                                                      .collect::<Result<_, _>> ()
+    ///                                                      .unwrap ();
+    ///
+    /// assert_eq! (records.len (), 1);
+    /// assert_eq! (records[0].message, "Loaded 12 textures");
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Fails if the file can't be opened.
+    pub fn open<P: AsRef<Path>> (path: P) -> io::Result<Self> {
+        Ok (MessagePackReader::new (BufReader::new (File::open (path)?)))
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl<R: Read> MessagePackReader<R> {
+
+    /// Returns a reader over a stream of length framed MessagePack records.
+    pub fn new (input: R) -> Self {
+        MessagePackReader {input, frame: Vec::new ()}
+    }
+}
+
+/*===============================================================================================*/
+/*------MESSAGE PACK READER TRAIT IMPLEMENTATIONS------------------------------------------------*/
+/*===============================================================================================*/
+
+impl<R: Read> Iterator for MessagePackReader<R> {
+
+    type Item = io::Result<Record>;
+
+    fn next (&mut self) -> Option<Self::Item> {
+
+        let mut length = [0u8; 4];
+
+        match self.input.read (&mut length[..1]) {
+
+            Ok (0) => return None,
+            Ok (_) => (),
+            Err (e) => return Some (Err (e)),
+        }
+
+        if let Err (e) = self.input.read_exact (&mut length[1..]) {
+            return Some (Err (e));
+        }
+
+        let length = u64::from (u32::from_be_bytes (length));
+
+        self.frame.clear ();
+
+        if let Err (e) = self.input.by_ref ().take (length).read_to_end (&mut self.frame) {
+            return Some (Err (e));
+        }
+
+        if self.frame.len () as u64 != length {
+            return Some (Err (io::Error::new (io::ErrorKind::UnexpectedEof, "truncated record")));
+        }
+
+        Some (Record::from_msgpack (&self.frame))
+    }
+}
+
+/*===============================================================================================*/
+/*------CBOR READER PUBLIC METHODS---------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl CborReader<BufReader<File>> {
+
+    /// Opens a CBOR log file for reading.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[macro_use] extern crate log;
+    /// # extern crate ion_log;
+    /// # use ion_log::*;
+    /// use ion_log::reader::CborReader;
+    ///
+    /// # fn main () {
+    /// # let path = std::env::temp_dir ().join ("ion_log_cbor_example.log");
+    /// let mut config = LogConfig::new ();
+    /// config.log_to_file     = true;
+    /// config.log_output_path = path.to_str ().unwrap ().to_string ();
+    /// config.log_format      = LogFormat::Cbor;
+    /// ion_log::init (&config).unwrap ();
+    ///
+    /// info! ("Loaded 12 textures");
+    /// ion_log::release ();
+    ///
+    /// let records: Vec<_> = CborReader::open (&path).unwrap ()
+    ///                                               .collect::<Result<_, _>> ()
+    ///                                               .unwrap ();
+    ///
+    /// assert_eq! (records.len (), 1);
+    /// assert_eq! (records[0].message, "Loaded 12 textures");
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Fails if the file can't be opened.
+    pub fn open<P: AsRef<Path>> (path: P) -> io::Result<Self> {
+        Ok (CborReader::new (BufReader::new (File::open (path)?)))
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl<R: Read> CborReader<R> {
+
+    /// Returns a reader over a CBOR sequence of records.
+    pub fn new (input: R) -> Self {
+        CborReader {input}
+    }
+}
+
+/*===============================================================================================*/
+/*------CBOR READER TRAIT IMPLEMENTATIONS--------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl<R: Read> Iterator for CborReader<R> {
+
+    type Item = io::Result<Record>;
+
+    fn next (&mut self) -> Option<Self::Item> {
+
+        match cbor::decode (&mut self.input) {
+
+            Ok (Some (value @ Value::Object (_))) => Some (Record::from_cbor_value (value)),
+            Ok (Some (_)) => Some (Err (binary::invalid_data ("not a CBOR map"))),
+            Ok (None) => None,
+            Err (e) => Some (Err (e)),
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------TIME RANGE TRAIT IMPLEMENTATIONS---------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl<R: Read> Iterator for TimeRange<R> {
+
+    type Item = io::Result<Record>;
+
+    fn next (&mut self) -> Option<Self::Item> {
+
+        while let Some (record) = self.reader.next () {
+
+            let record = match record {
+
+                Ok (record) => record,
+                Err (e) => return Some (Err (e)),
+            };
+
+            if record.timestamp > self.until + self.reader.slack {
+                return None;
+            }
+
+            if record.timestamp >= self.since && record.timestamp <= self.until {
+                return Some (Ok (record));
+            }
+        }
+
+        None
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl<R: Read> fmt::Debug for TimeRange<R> {
+
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        f.debug_struct ("TimeRange")
+         .field ("since", &self.since)
+         .field ("until", &self.until)
+         .finish ()
+    }
+}
+
+/*===============================================================================================*/
+/*------READER PUBLIC METHODS--------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Reader<BufReader<File>> {
+
+    /// Opens a binary log file.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[macro_use] extern crate log;
+    /// # extern crate ion_log;
+    /// # use ion_log::*;
+    /// # use ion_log::reader::Reader;
+    /// # fn main () {
+    /// let path = std::env::temp_dir ().join ("ion_log_reader_open.log");
+    ///
+    /// let mut config = LogConfig::new ();
+    /// config.log_to_io       = false;
+    /// config.log_to_file     = true;
+    /// config.log_output_path = path.to_string_lossy ().into_owned ();
+    /// config.log_format      = LogFormat::Binary;
+    ///
+    /// ion_log::init (&config).unwrap ();
+    /// info! ("Loaded {} textures", 12);
+    /// let session_id = ion_log::session_id ();
+    /// ion_log::release ();
+    ///
+    /// let records: Vec<_> = Reader::open (&path).unwrap ().map (|r| r.unwrap ()).collect ();
+    /// assert_eq! (records.len (), 1);
+    /// assert_eq! (records[0].message, "Loaded 12 textures");
+    /// assert_eq! (records[0].trace_id, None);
+    /// assert_eq! (records[0].session_id, session_id);
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Fails if the file can't be opened or doesn't start with a binary log header.
+    pub fn open<P: AsRef<Path>> (path: P) -> io::Result<Self> {
+        Reader::new (BufReader::new (File::open (path)?))
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl<R: Read> Reader<R> {
+
+    /// Returns a reader over a binary log stream, after checking its header.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::reader::Reader;
+    /// let data: &[u8] = b"IONLOG\x01";
+    /// assert_eq! (Reader::new (data).unwrap ().count (), 0);
+    /// ```
+    pub fn new (mut input: R) -> io::Result<Self> {
+
+        let mut header = [0u8; 7];
+        input.read_exact (&mut header)?;
+
+        if &header[..6] != binary::MAGIC {
+            return Err (binary::invalid_data ("not an ionLog binary file"));
+        }
+
+        if header[6] != binary::VERSION {
+            return Err (binary::invalid_data ("unsupported binary format version"));
+        }
+
+        Ok (Reader {
+
+            input,
+            strings: Vec::new (),
+            payload: Vec::new (),
+            trace_context: None,
+            tags: Vec::new (),
+            severity: None,
+            session_id: None,
+            app_name: None,
+            instance_id: None,
+            slack: ORDER_SLACK,
+        })
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Returns the records logged between two times, inclusive.
+    ///
+    /// Records are written in time order, so reading stops soon after the end of the range
+    /// rather than at the end of the file. Threads logging at the same moment can be written
+    /// slightly out of order, so records up to a second past the end are still checked. A log
+    /// written with `LogConfig::flight_recorder` has its debug and trace records written up to
+    /// the recorder's window late, so that much more is checked.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use ion_log::reader::{self, Reader};
+    /// let since = reader::parse_timestamp ("2016-08-01T14:02:00Z").unwrap ();
+    /// let until = reader::parse_timestamp ("2016-08-01T14:05:00Z").unwrap ();
+    ///
+    /// for record in Reader::open ("game.log").unwrap ().range (since, until) {
+    ///     print! ("{}", record.unwrap ().to_text ());
+    /// }
+    /// ```
+    pub fn range (self, since: SystemTime, until: SystemTime) -> TimeRange<R> {
+        TimeRange {reader: self, since, until}
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl<R: Read + Seek> Reader<R> {
+
+    /// Skips ahead using the log's index, to a point before every record logged at or after
+    /// `time`, but after as many earlier records as the index allows.
+    ///
+    /// This should be called before reading any records. Earlier records may still follow,
+    /// so use `range` to leave them out.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[macro_use] extern crate log;
+    /// # extern crate ion_log;
+    /// # use ion_log::*;
+    /// # use ion_log::reader::{Index, Reader};
+    /// # use std::time::{Duration, SystemTime};
+    /// # fn main () {
+    /// let path = std::env::temp_dir ().join ("ion_log_reader_seek.log");
+    ///
+    /// let mut config = LogConfig::new ();
+    /// config.log_to_io       = false;
+    /// config.log_to_file     = true;
+    /// config.log_output_path = path.to_string_lossy ().into_owned ();
+    /// config.log_format      = LogFormat::Binary;
+    /// config.log_file_index  = true;
+    ///
+    /// ion_log::init (&config).unwrap ();
+    /// warn! ("Low memory");
+    /// ion_log::release ();
+    ///
+    /// let since = SystemTime::now () - Duration::from_secs (60);
+    /// let index = Index::open (&path).unwrap ();
+    /// let mut reader = Reader::open (&path).unwrap ();
+    ///
+    /// reader.seek (&index, since).unwrap ();
+    /// assert_eq! (reader.range (since, SystemTime::now ()).count (), 1);
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Fails if the underlying stream can't seek.
+    pub fn seek (&mut self, index: &Index, time: SystemTime) -> io::Result<()> {
+
+        // Every record before a minute's offset was logged in an earlier minute.
+        let minutes = index.minutes.partition_point (|minute| minute.start <= time);
+
+        let minute = match index.minutes.get (minutes.saturating_sub (1)) {
+
+            Some (minute) => minute,
+            None => return Ok (()),
+        };
+
+        self.input.seek (SeekFrom::Start (minute.offset))?;
+        self.strings = index.strings[..minute.strings].to_vec ();
+        self.session_id = index.session_id.clone ();
+        self.app_name = index.app_name.clone ();
+        self.instance_id = index.instance_id.clone ();
+        self.slack = self.slack.max (index.slack);
+        self.trace_context = None;
+        self.tags.clear ();
+        self.severity = None;
+        Ok (())
+    }
+}
+
+/*===============================================================================================*/
+/*------INDEX PUBLIC METHODS---------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Index {
+
+    /// Opens the index of a binary log file, given the path of the log file itself.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[macro_use] extern crate log;
+    /// # extern crate ion_log;
+    /// # use ion_log::*;
+    /// # use ion_log::reader::Index;
+    /// # fn main () {
+    /// let path = std::env::temp_dir ().join ("ion_log_index_open.log");
+    ///
+    /// let mut config = LogConfig::new ();
+    /// config.log_to_io       = false;
+    /// config.log_to_file     = true;
+    /// config.log_output_path = path.to_string_lossy ().into_owned ();
+    /// config.log_format      = LogFormat::Binary;
+    /// config.log_file_index  = true;
+    ///
+    /// ion_log::init (&config).unwrap ();
+    /// error! ("Shader failed to compile");
+    /// warn! ("Falling back to the default shader");
+    /// warn! ("Texture missing");
+    /// ion_log::release ();
+    ///
+    /// let index = Index::open (&path).unwrap ();
+    /// assert_eq! (index.count (log::LogLevel::Warn), 2);
+    /// assert_eq! (index.total (), 3);
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Fails if the index can't be read, or doesn't belong to the log file.
+    pub fn open<P: AsRef<Path>> (log_path: P) -> io::Result<Self> {
+
+        let log_path = log_path.as_ref ();
+        let mut index_path = log_path.as_os_str ().to_owned ();
+        index_path.push (".idx");
+
+        let index = Index::read (BufReader::new (File::open (index_path)?))?;
+
+        // An index left over from an earlier log at the same path is no use.
+        let mut log = Reader::new (BufReader::new (File::open (log_path)?))?;
+
+        if binary::read_frame (&mut log.input, &mut log.payload)? == Some (binary::FRAME_SESSION) {
+            log.read_session ()?;
+        }
+
+        if log.session_id != index.session_id || log_path.metadata ()?.len () < index.length {
+            return Err (binary::invalid_data ("index doesn't match the log file"));
+        }
+
+        Ok (index)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Returns the number of records at a level.
+    pub fn count (&self, level: log::LogLevel) -> u64 {
+        self.minutes.iter ().map (|minute| minute.counts[level as usize - 1]).sum ()
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Returns the number of records at all levels.
+    pub fn total (&self) -> u64 {
+        self.minutes.iter ().flat_map (|minute| minute.counts.iter ()).sum ()
+    }
+}
+
+/*===============================================================================================*/
+/*------INDEX PRIVATE METHODS--------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Index {
+
+    fn read<R: Read> (mut input: R) -> io::Result<Self> {
+
+        let mut header = [0u8; 7];
+        input.read_exact (&mut header)?;
+
+        if &header[..6] != binary::INDEX_MAGIC || header[6] != binary::VERSION {
+            return Err (binary::invalid_data ("not an ionLog index file"));
+        }
+
+        // The frames are the same as in the log, so reuse the reader's parsing.
+        let mut reader = Reader {
+
+            input,
+            strings: Vec::new (),
+            payload: Vec::new (),
+            trace_context: None,
+            tags: Vec::new (),
+            severity: None,
+            session_id: None,
+            app_name: None,
+            instance_id: None,
+            slack: ORDER_SLACK,
+        };
+
+        let mut minutes = Vec::new ();
+        let mut length = None;
+
+        while let Some (tag) = binary::read_frame (&mut reader.input, &mut reader.payload)? {
+
+            match tag {
+
+                binary::FRAME_SESSION => reader.read_session ()?,
+                binary::FRAME_APP     => reader.read_app ()?,
+                binary::FRAME_REORDER => reader.read_reorder ()?,
+                binary::FRAME_TARGET  => reader.read_string ()?,
+                binary::FRAME_MINUTE  => minutes.push (IndexMinute::read (&reader.payload)?),
+                binary::FRAME_LENGTH  => length = Some (binary::read_varint (&reader.payload, &mut 0)?),
+                _ => (),
+            }
+        }
+
+        if minutes.iter ().any (|minute| minute.strings > reader.strings.len ()) {
+            return Err (binary::invalid_data ("index refers to undefined strings"));
+        }
+
+        Ok (Index {
+
+            session_id:  reader.session_id,
+            app_name:    reader.app_name,
+            instance_id: reader.instance_id,
+            slack:       reader.slack,
+            strings:     reader.strings,
+            minutes,
+            length:      length.ok_or_else (|| binary::invalid_data ("truncated index"))?,
+        })
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl IndexMinute {
+
+    fn read (payload: &[u8]) -> io::Result<Self> {
+
+        let mut pos = 0;
+        let first   = binary::read_varint (payload, &mut pos)?;
+        let offset  = binary::read_varint (payload, &mut pos)?;
+        let strings = binary::read_varint (payload, &mut pos)?;
+        let mut counts = [0; 5];
+
+        for count in &mut counts {
+            *count = binary::read_varint (payload, &mut pos)?;
+        }
+
+        Ok (IndexMinute {
+
+            start: UNIX_EPOCH + Duration::from_secs (first / 60_000_000 * 60),
+            offset,
+            strings: strings as usize,
+            counts,
+        })
+    }
+}
+
+/*===============================================================================================*/
+/*------READER PRIVATE METHODS-------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl<R: Read> Reader<R> {
+
+    fn read_string (&mut self) -> io::Result<()> {
+
+        let mut pos = 0;
+        let id = binary::read_varint (&self.payload, &mut pos)? as usize;
+
+        if id != self.strings.len () {
+            return Err (binary::invalid_data ("out of order string definition"));
+        }
+
+        self.strings.push (utf8 (&self.payload[pos..])?);
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn read_record (&mut self) -> io::Result<Record> {
+
+        let level = match self.payload.first () {
+
+            Some (&1) => log::LogLevel::Error,
+            Some (&2) => log::LogLevel::Warn,
+            Some (&3) => log::LogLevel::Info,
+            Some (&4) => log::LogLevel::Debug,
+            Some (&5) => log::LogLevel::Trace,
+            _ => return Err (binary::invalid_data ("invalid record level")),
+        };
+
+        let mut pos = 1;
+        let timestamp   = binary::read_varint (&self.payload, &mut pos)?;
+        let target      = binary::read_varint (&self.payload, &mut pos)?;
+        let module_path = binary::read_varint (&self.payload, &mut pos)?;
+        let line        = binary::read_varint (&self.payload, &mut pos)?;
+        let (trace_id, span_id) = match self.trace_context.take () {
+
+            Some ((trace_id, span_id)) => (Some (trace_id), Some (span_id)),
+            None => (None, None),
+        };
+
+        // Files written before the frame was added only have the severity's tag.
+        let tags = mem::take (&mut self.tags);
+        let severity = self.severity.take ().or_else (|| format::tagged_severity (&tags));
+
+        Ok (Record {
+
+            level,
+            timestamp:   UNIX_EPOCH + Duration::from_micros (timestamp),
+            target:      self.string (target)?,
+            module_path: self.string (module_path)?,
+            line:        line as u32,
+            message:     utf8 (&self.payload[pos..])?,
+            trace_id,
+            span_id,
+            session_id:  self.session_id.clone (),
+            app_name:    self.app_name.clone (),
+            instance_id: self.instance_id.clone (),
+            tags,
+            severity,
+        })
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn read_trace_context (&mut self) -> io::Result<()> {
+
+        let mut pos = 0;
+        let length = binary::read_varint (&self.payload, &mut pos)? as usize;
+
+        if length > self.payload.len () - pos {
+            return Err (binary::invalid_data ("truncated trace context"));
+        }
+
+        let trace_id = utf8 (&self.payload[pos..pos + length])?;
+        let span_id  = utf8 (&self.payload[pos + length..])?;
+
+        self.trace_context = Some ((trace_id, span_id));
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn read_severity (&mut self) -> io::Result<()> {
+
+        self.severity = match self.payload.first () {
+
+            Some (&1) => Some (Severity::Notice),
+            Some (&2) => Some (Severity::Critical),
+            Some (&3) => Some (Severity::Fatal),
+            _ => return Err (binary::invalid_data ("invalid record severity")),
+        };
+
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn read_tags (&mut self) -> io::Result<()> {
+
+        let mut pos = 0;
+        self.tags.clear ();
+
+        while pos < self.payload.len () {
+
+            let tag = binary::read_varint (&self.payload, &mut pos)?;
+            let tag = self.string (tag)?;
+
+            self.tags.push (tag);
+        }
+
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn read_session (&mut self) -> io::Result<()> {
+
+        self.session_id = Some (utf8 (&self.payload)?);
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn read_app (&mut self) -> io::Result<()> {
+
+        let mut pos = 0;
+        let length = binary::read_varint (&self.payload, &mut pos)? as usize;
+
+        if length > self.payload.len () - pos {
+            return Err (binary::invalid_data ("truncated application name"));
+        }
+
+        let app_name    = utf8 (&self.payload[pos..pos + length])?;
+        let instance_id = utf8 (&self.payload[pos + length..])?;
+
+        self.app_name    = Some (app_name).filter (|name| !name.is_empty ());
+        self.instance_id = Some (instance_id).filter (|id| !id.is_empty ());
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn read_reorder (&mut self) -> io::Result<()> {
+
+        let window = binary::read_varint (&self.payload, &mut 0)?;
+
+        self.slack = self.slack.max (Duration::from_micros (window) + ORDER_SLACK);
+        Ok (())
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn string (&self, id: u64) -> io::Result<String> {
+
+        match self.strings.get (id as usize) {
+
+            Some (string) => Ok (string.clone ()),
+            None => Err (binary::invalid_data ("undefined string id")),
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Parses a UTC timestamp in the RFC 3339 form used by `Record::to_json`.
+///
+/// The fractional seconds and trailing `Z` are optional, and a bare date means midnight.
+///
+/// # Examples
+/// ```
+/// # use ion_log::reader;
+/// # use std::time::{Duration, UNIX_EPOCH};
+/// let time = reader::parse_timestamp ("2016-08-01T12:30:00Z").unwrap ();
+/// assert_eq! (time, UNIX_EPOCH + Duration::from_secs (1_470_054_600));
+/// ```
+pub fn parse_timestamp (text: &str) -> Option<SystemTime> {
+    format::parse_timestamp (text)
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+fn utf8 (bytes: &[u8]) -> io::Result<String> {
+
+    match String::from_utf8 (bytes.to_vec ()) {
+
+        Ok (string) => Ok (string),
+        Err (_) => Err (binary::invalid_data ("invalid UTF-8")),
+    }
+}
@@ -0,0 +1,105 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! A bounded buffer of the latest warning and error records.
+/*===============================================================================================*/
+
+// Module imports
+use log;
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/*===============================================================================================*/
+/*------RECENT ERROR STRUCT----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// A warning or error record kept for `ion_log::recent_errors`.
+#[derive (Clone, Debug, PartialEq, Eq)]
+pub struct RecentError {
+
+    // Public
+    /// The record level, either `Error` or `Warn`.
+    pub level:       log::LogLevel,
+    /// The time the record was logged.
+    pub timestamp:   SystemTime,
+    /// The record target.
+    pub target:      String,
+    /// The module path the record was logged from.
+    pub module_path: String,
+    /// The line the record was logged from.
+    pub line:        u32,
+    /// The record message.
+    pub message:     String,
+}
+
+/*===============================================================================================*/
+/*------RECENT ERRORS STRUCT---------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Keeps the latest records, dropping the oldest once full.
+pub struct RecentErrors {
+
+    // Private
+    capacity: usize,
+    records: Mutex<VecDeque<RecentError>>,
+}
+
+/*===============================================================================================*/
+/*------RECENT ERRORS PUBLIC METHODS-------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl RecentErrors {
+
+    pub fn new (capacity: usize) -> Self {
+        RecentErrors {capacity, records: Mutex::new (VecDeque::with_capacity (capacity))}
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Keeps the record if it is a warning or error and the buffer is enabled.
+    pub fn add (&self, record: &log::LogRecord, location: &log::LogLocation, message: &str, timestamp: SystemTime) {
+
+        if self.capacity == 0 || record.level () > log::LogLevel::Warn {
+            return;
+        }
+
+        let mut records = self.records.lock ().unwrap ();
+
+        if records.len () >= self.capacity {
+            records.pop_front ();
+        }
+
+        records.push_back (RecentError {
+
+            level:       record.level (),
+            timestamp,
+            target:      record.target ().to_string (),
+            module_path: location.module_path ().to_string (),
+            line:        location.line (),
+            message:     message.to_string (),
+        });
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Returns the kept records, oldest first.
+    pub fn get (&self) -> Vec<RecentError> {
+        self.records.lock ().unwrap ().iter ().cloned ().collect ()
+    }
+}
@@ -0,0 +1,693 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Log file rotation, set with `LogConfig::rotation`.
+/*===============================================================================================*/
+
+// Module imports
+use format;
+use zone::Zone;
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// How long rotation waits to try again after failing.
+const RETRY_INTERVAL: Duration = Duration::from_secs (1);
+
+// Called with the rotated file's path and the current file's.
+type Callback = dyn Fn (&Path, &Path) + Send + Sync;
+
+/*===============================================================================================*/
+/*------ROTATION STRUCT--------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// When to start a new log file, set with `LogConfig::rotation`.
+///
+/// The current file is renamed to `<stem>.<n>.<extension>`, as in `app.3.log` for `app.log`,
+/// or as `file_names` says, and a new one is started at `log_output_path`, so `tail -F` on it
+/// follows the live output. `n` counts on from the highest in the directory, so a restart
+/// doesn't overwrite earlier files. A binary file's index is renamed along with it.
+///
+/// Rotation is checked as each record is written, against the record's timestamp, so it
+/// follows `LogConfig::clock`. A file is never rotated before its first record. Rotation
+/// needs `log_output_path`, and is ignored for `log_file_handle` and in a forked child.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let mut config = LogConfig::new ();
+/// config.log_to_file     = true;
+/// config.log_output_path = "app.log".to_string ();
+/// config.rotation        = Some (Rotation {keep: 7, ..Rotation::by_size (10_000_000)});
+///
+/// // Rotated files are named like `app.2026-10-14.1.log`.
+/// config.rotation = Some (Rotation {
+///
+///     file_names: Some ("app.{date}.{seq}.log".to_string ()),
+///     ..Rotation::daily ()
+/// });
+/// ```
+#[derive (Clone, Debug, PartialEq)]
+pub struct Rotation {
+
+    // Public
+    /// Rotates before writing to a file of at least this many bytes, so files end up to one
+    /// write over it. Zero for no size limit.
+    pub max_size:    u64,
+    /// Whether to also rotate daily, at midnight UTC or at `time_of_day`.
+    pub daily:       bool,
+    /// The local time of day to rotate at, as a time since midnight, when `daily` is set.
+    /// `None` rotates at midnight UTC.
+    ///
+    /// The local zone is read from `TZ` or `/etc/localtime` when the file is opened, and UTC
+    /// is used where neither is found, as on Windows. If a daylight saving change skips the
+    /// time, rotation happens as much later as the clock jumped, so 02:30 becomes 03:30. If
+    /// the clock goes back over it, rotation happens the first time round.
+    pub time_of_day: Option<Duration>,
+    /// How many rotated files to keep, deleting the oldest. Zero keeps them all.
+    pub keep:        usize,
+    /// A template for rotated file names, in the log file's directory, for tools that expect a
+    /// naming pattern, as in `app.{date}.{seq}.log`. `{date}` is replaced by the date the file
+    /// was started, in UTC or the zone of `time_of_day`, as in `2026-10-14`, and `{seq}` by the next number not yet used with
+    /// that date, from 1. It must contain `{seq}` once, and no path separators.
+    ///
+    /// `keep` counts the files matching the template, oldest by date and then number.
+    /// `None`, or a template `LogConfig::validate` rejects, names them `<stem>.<n>.<extension>`.
+    pub file_names:  Option<String>,
+    /// Called after each rotation.
+    pub hook:        Option<RotationHook>,
+}
+
+/*===============================================================================================*/
+/*------ROTATION HOOK STRUCT---------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// A callback run after each rotation with the rotated file's new path and the path of the
+/// file now being written, to index, upload or announce the finished file.
+///
+/// It runs on a thread of its own, so it can take its time and log. Hooks for rotations in
+/// quick succession may run at the same time.
+///
+/// # Examples
+/// ```
+/// # #[macro_use] extern crate log;
+/// # extern crate ion_log;
+/// # use ion_log::*;
+/// # use std::sync::mpsc;
+/// # use std::time::Duration;
+/// # fn main () {
+/// # let directory = std::env::temp_dir ().join (format! ("ion_log_hook_{}", std::process::id ()));
+/// # std::fs::create_dir_all (&directory).unwrap ();
+/// let (sender, receiver) = mpsc::channel ();
+/// let sender = std::sync::Mutex::new (sender);
+///
+/// let mut config = LogConfig::new ();
+/// config.log_to_io       = false;
+/// config.log_to_file     = true;
+/// config.log_output_path = directory.join ("app.log").to_string_lossy ().into_owned ();
+/// config.rotation        = Some (Rotation {
+///
+///     hook: Some (RotationHook::new (move |old, _new| {
+///         sender.lock ().unwrap ().send (old.to_path_buf ()).unwrap ();
+///     })),
+///     ..Rotation::by_size (50)
+/// });
+///
+/// ion_log::init (&config).unwrap ();
+///
+/// info! ("A record long enough to fill the first file on its own");
+/// info! ("This one goes in the next file");
+///
+/// let rotated = receiver.recv_timeout (Duration::from_secs (5)).unwrap ();
+/// assert_eq! (rotated, directory.join ("app.1.log"));
+/// # ion_log::release ();
+/// # std::fs::remove_dir_all (&directory).unwrap ();
+/// # }
+/// ```
+///
+/// Archiving each finished file to object storage with a command line client, and removing
+/// it once the upload succeeds:
+///
+/// ```no_run
+/// # use ion_log::*;
+/// # use std::process::Command;
+/// let hook = RotationHook::new (|old, _new| {
+///
+///     let key = format! ("s3://app-logs/host-1/{}", old.file_name ().unwrap ().to_string_lossy ());
+///
+///     match Command::new ("aws").arg ("s3").arg ("cp").arg (old).arg (&key).status () {
+///
+///         Ok (status) if status.success () => drop (std::fs::remove_file (old)),
+///         _ => (),
+///     }
+/// });
+/// ```
+///
+/// Converting each finished JSON Lines file to Parquet for analytics, partitioned by hour, with
+/// the DuckDB command line client:
+///
+/// ```no_run
+/// # use ion_log::*;
+/// # use std::process::Command;
+/// let hook = RotationHook::new (|old, _new| {
+///
+///     let sql = format! ("COPY (SELECT *, strftime (CAST (\"timestamp\" AS TIMESTAMP), '%Y-%m-%d-%H') AS hour \
+///                         FROM read_json_auto ('{}')) \
+///                         TO 'archive' (FORMAT PARQUET, PARTITION_BY (hour), APPEND)", old.display ());
+///
+///     match Command::new ("duckdb").arg ("-c").arg (&sql).status () {
+///
+///         Ok (status) if status.success () => drop (std::fs::remove_file (old)),
+///         _ => (),
+///     }
+/// });
+/// ```
+#[derive (Clone)]
+pub struct RotationHook {
+
+    // Private
+    callback: Arc<Callback>,
+}
+
+/*===============================================================================================*/
+/*------ROTATOR STRUCT---------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Decides when an open log file is rotated, and what the rotated files are called.
+pub struct Rotator {
+
+    // Private
+    rotation: Rotation,
+    path: PathBuf,
+    names: Vec<NamePart>,
+    zone: Option<Zone>,
+    started: SystemTime,
+    due: Option<SystemTime>,
+    retry_at: Option<SystemTime>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// A piece of a rotated file's name.
+#[derive (PartialEq, Eq)]
+enum NamePart {
+
+    Text (String),
+    Date,
+    Number,
+}
+
+/*===============================================================================================*/
+/*------ROTATION PUBLIC METHODS------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Rotation {
+
+    /// Returns a rotation starting a new file once the current one reaches `max_size` bytes.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// let rotation = Rotation::by_size (50_000_000);
+    /// assert! (!rotation.daily);
+    /// ```
+    pub fn by_size (max_size: u64) -> Self {
+        Rotation {max_size, daily: false, time_of_day: None, keep: 0, file_names: None, hook: None}
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Returns a rotation starting a new file every day at midnight UTC.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// let rotation = Rotation {keep: 30, ..Rotation::daily ()};
+    /// assert_eq! (rotation.max_size, 0);
+    /// ```
+    pub fn daily () -> Self {
+        Rotation {max_size: 0, daily: true, time_of_day: None, keep: 0, file_names: None, hook: None}
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Returns a rotation starting a new file every day at a local time, as for maintenance
+    /// scripts expecting the cut at a quiet hour.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ion_log::*;
+    /// # use std::time::Duration;
+    /// let rotation = Rotation::daily_at (3, 0);
+    /// assert_eq! (rotation.time_of_day, Some (Duration::from_secs (3 * 3600)));
+    /// ```
+    ///
+    /// On the day US clocks jump from 02:00 to 03:00, a 02:30 rotation happens at 03:30:
+    /// ```
+    /// # #[macro_use] extern crate log;
+    /// # extern crate ion_log;
+    /// # use ion_log::*;
+    /// # use std::sync::Arc;
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// # fn main () {
+    /// # let directory = std::env::temp_dir ().join (format! ("ion_log_daily_at_{}", std::process::id ()));
+    /// # std::fs::create_dir_all (&directory).unwrap ();
+    /// std::env::set_var ("TZ", "EST5EDT,M3.2.0,M11.1.0");
+    ///
+    /// // 2024-03-10 01:00 EST.
+    /// let clock = Arc::new (MockClock::new (UNIX_EPOCH + Duration::from_secs (1_710_050_400)));
+    ///
+    /// let mut config = LogConfig::new ();
+    /// config.log_to_io       = false;
+    /// config.log_to_file     = true;
+    /// config.log_output_path = directory.join ("app.log").to_string_lossy ().into_owned ();
+    /// config.clock           = clock.clone ();
+    /// config.rotation        = Some (Rotation::daily_at (2, 30));
+    ///
+    /// ion_log::init (&config).unwrap ();
+    /// info! ("01:00 EST");
+    ///
+    /// clock.advance (Duration::from_secs (80 * 60));
+    /// info! ("03:20 EDT");
+    /// assert! (!directory.join ("app.1.log").exists ());
+    ///
+    /// clock.advance (Duration::from_secs (15 * 60));
+    /// info! ("03:35 EDT");
+    /// ion_log::flush ().unwrap ();
+    ///
+    /// let rotated = std::fs::read_to_string (directory.join ("app.1.log")).unwrap ();
+    /// assert! (rotated.contains ("03:20 EDT") && !rotated.contains ("03:35 EDT"));
+    /// # ion_log::release ();
+    /// # std::fs::remove_dir_all (&directory).unwrap ();
+    /// # }
+    /// ```
+    pub fn daily_at (hour: u32, minute: u32) -> Self {
+
+        Rotation {
+
+            time_of_day: Some (Duration::from_secs (u64::from (hour) * 3600 + u64::from (minute) * 60)),
+            ..Rotation::daily ()
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------ROTATION HOOK PUBLIC METHODS-------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl RotationHook {
+
+    /// Returns a hook calling `callback` with the rotated file's path and the current file's.
+    pub fn new<F> (callback: F) -> Self where F: Fn (&Path, &Path) + Send + Sync + 'static {
+        RotationHook {callback: Arc::new (callback)}
+    }
+}
+
+/*===============================================================================================*/
+/*------ROTATION HOOK TRAIT IMPLEMENTATIONS------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl fmt::Debug for RotationHook {
+
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct ("RotationHook").finish_non_exhaustive ()
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Hooks are only equal to their own clones, as callbacks can't be compared.
+impl PartialEq for RotationHook {
+
+    fn eq (&self, other: &Self) -> bool {
+        Arc::ptr_eq (&self.callback, &other.callback)
+    }
+}
+
+/*===============================================================================================*/
+/*------ROTATOR PUBLIC METHODS-------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Rotator {
+
+    pub fn new (rotation: &Rotation, path: &Path, now: SystemTime) -> Self {
+
+        let mut rotator = Rotator {
+
+            rotation: rotation.clone (),
+            path:     path.to_path_buf (),
+            names:    Rotator::name_parts (rotation, path),
+            zone:     rotation.time_of_day.map (|_| Zone::local ()),
+            started:  now,
+            due:      None,
+            retry_at: None,
+        };
+
+        rotator.schedule (now);
+        rotator
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // The path of the file being written.
+    pub fn path (&self) -> &Path {
+        &self.path
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    pub fn rotation (&self) -> &Rotation {
+        &self.rotation
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Whether the file should be rotated before a record with the timestamp is added to it.
+    // `size` is the file's size with any pending writes, of which `header_size` is the header.
+    pub fn is_due (&mut self, size: u64, header_size: u64, timestamp: SystemTime) -> bool {
+
+        if self.retry_at.is_some_and (|retry_at| timestamp < retry_at) {
+            return false;
+        }
+
+        let time_due = self.due.is_some_and (|due| timestamp >= due);
+
+        // A file with nothing in it yet covers the new day as well as the last.
+        if size <= header_size {
+
+            if time_due {
+                self.schedule (timestamp);
+            }
+
+            return false;
+        }
+
+        time_due || (self.rotation.max_size > 0 && size >= self.rotation.max_size)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Returns the path to rename the current file to, numbered on from the highest in the
+    // directory with the same date.
+    pub fn rotated_path (&self) -> PathBuf {
+
+        let date = if self.names.contains (&NamePart::Date) {self.date ()} else {String::new ()};
+        let mut number = self.rotated_files ()
+                             .into_iter ()
+                             .filter (|((file_date, _), _)| *file_date == date)
+                             .map (|((_, number), _)| number)
+                             .max ()
+                             .unwrap_or (0) + 1;
+
+        loop {
+
+            let path = self.directory ().join (self.name (&date, number));
+
+            if !path.exists () {
+                return path;
+            }
+
+            number += 1;
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Schedules the next rotation, removes rotated files beyond those kept and runs the hook,
+    // once the file has been rotated to `rotated`.
+    pub fn rotated (&mut self, rotated: &Path, now: SystemTime) {
+
+        self.retry_at = None;
+        self.started = now;
+        self.schedule (now);
+        self.remove_old ();
+
+        if let Some (ref hook) = self.rotation.hook {
+
+            let callback = hook.callback.clone ();
+            let rotated = rotated.to_path_buf ();
+            let path = self.path.clone ();
+
+            // The rotation has happened either way, so a hook that can't be started is skipped.
+            drop (thread::Builder::new ().name ("ion_log_rotation".to_string ())
+                                         .spawn (move || callback (&rotated, &path)));
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Holds off rotating for a while after a failure, so each record doesn't try again.
+    pub fn retry_later (&mut self, now: SystemTime) {
+        self.retry_at = Some (now + RETRY_INTERVAL);
+    }
+}
+
+/*===============================================================================================*/
+/*------ROTATOR PRIVATE METHODS------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Rotator {
+
+    // Works out when the next time based rotation is due.
+    fn schedule (&mut self, now: SystemTime) {
+
+        if !self.rotation.daily {
+
+            self.due = None;
+            return;
+        }
+
+        let seconds = epoch_seconds (now);
+        let due = match (self.zone.as_ref (), self.rotation.time_of_day) {
+
+            (Some (zone), Some (time_of_day)) => zone.next_time_of_day (seconds, time_of_day.as_secs () as i64 % 86_400),
+            _ => (seconds.div_euclid (86_400) + 1) * 86_400,
+        };
+
+        self.due = Some (UNIX_EPOCH + Duration::from_secs (due.max (0) as u64));
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Returns the date the file was started, in the local zone if rotating at a local time.
+    fn date (&self) -> String {
+
+        let offset = self.zone.as_ref ().map_or (0, |zone| zone.offset (epoch_seconds (self.started)));
+        let local = (epoch_seconds (self.started) + offset).max (0) as u64;
+
+        format::date (UNIX_EPOCH + Duration::from_secs (local))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Splits the template for rotated file names, or the default of `<stem>.<n>.<extension>`.
+    fn name_parts (rotation: &Rotation, path: &Path) -> Vec<NamePart> {
+
+        let template = match rotation.file_names {
+
+            Some (ref template) if is_valid_file_names (template) => template,
+            _ => {
+
+                let stem = path.file_stem ().map_or (String::new (), |stem| stem.to_string_lossy ().into_owned ());
+                let extension = path.extension ()
+                                    .map_or (String::new (), |extension| format! (".{}", extension.to_string_lossy ()));
+
+                return vec! [NamePart::Text (format! ("{}.", stem)), NamePart::Number, NamePart::Text (extension)];
+            },
+        };
+
+        let mut parts = Vec::new ();
+        let mut rest = template.as_str ();
+
+        while !rest.is_empty () {
+
+            let (part, length) = if rest.starts_with ("{date}") {
+                (NamePart::Date, 6)
+            }
+
+            else if rest.starts_with ("{seq}") {
+                (NamePart::Number, 5)
+            }
+
+            else {
+
+                let length = rest.char_indices ().skip (1).find (|&(_, c)| c == '{').map_or (rest.len (), |(index, _)| index);
+                (NamePart::Text (rest[..length].to_string ()), length)
+            };
+
+            parts.push (part);
+            rest = &rest[length..];
+        }
+
+        parts
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Returns a rotated file's name for the date and number.
+    fn name (&self, date: &str, number: u64) -> String {
+
+        let mut name = String::new ();
+
+        for part in &self.names {
+
+            match *part {
+
+                NamePart::Text (ref text) => name.push_str (text),
+                NamePart::Date => name.push_str (date),
+                NamePart::Number => name.push_str (&number.to_string ()),
+            }
+        }
+
+        name
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn directory (&self) -> PathBuf {
+
+        match self.path.parent () {
+
+            Some (parent) if !parent.as_os_str ().is_empty () => parent.to_path_buf (),
+            _ => PathBuf::from ("."),
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Returns the date and number of a rotated file, if the name is one of this file's.
+    // The date is empty if the names have none.
+    fn parse_name (&self, name: &str) -> Option<(String, u64)> {
+
+        let mut rest = name;
+        let mut date = String::new ();
+        let mut number = None;
+
+        for part in &self.names {
+
+            match *part {
+
+                NamePart::Text (ref text) => rest = rest.strip_prefix (text.as_str ())?,
+                NamePart::Date => {
+
+                    let digits = rest.get (..10)?;
+                    let is_date = digits.bytes ().enumerate ().all (|(i, byte)| {
+                        if i == 4 || i == 7 {byte == b'-'} else {byte.is_ascii_digit ()}
+                    });
+
+                    if !is_date {
+                        return None;
+                    }
+
+                    date = digits.to_string ();
+                    rest = &rest[10..];
+                },
+
+                NamePart::Number => {
+
+                    let length = rest.bytes ().take_while (u8::is_ascii_digit).count ();
+
+                    number = Some (rest[..length].parse ().ok ()?);
+                    rest = &rest[length..];
+                },
+            }
+        }
+
+        if !rest.is_empty () {
+            return None;
+        }
+
+        Some ((date, number?))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Returns this file's rotated files in the directory, with their dates and numbers.
+    fn rotated_files (&self) -> Vec<((String, u64), PathBuf)> {
+
+        let entries = match fs::read_dir (self.directory ()) {
+
+            Ok (entries) => entries,
+            Err (_) => return Vec::new (),
+        };
+
+        entries.filter_map (Result::ok)
+               .filter_map (|entry| {
+
+                   let key = self.parse_name (&entry.file_name ().to_string_lossy ())?;
+                   Some ((key, entry.path ()))
+               })
+               .collect ()
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Deletes the oldest rotated files beyond the number kept, along with their indexes.
+    fn remove_old (&self) {
+
+        if self.rotation.keep == 0 {
+            return;
+        }
+
+        let mut rotated = self.rotated_files ();
+
+        if rotated.len () <= self.rotation.keep {
+            return;
+        }
+
+        rotated.sort ();
+
+        for (_, path) in &rotated[..rotated.len () - self.rotation.keep] {
+
+            drop (fs::remove_file (path));
+            drop (fs::remove_file (index_path (path)));
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Whether a template for rotated file names has `{seq}` once, and no path separators.
+pub fn is_valid_file_names (template: &str) -> bool {
+    template.matches ("{seq}").count () == 1 && !template.contains ('/') && !template.contains ('\\')
+}
+
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Returns the path of a binary log file's index.
+pub fn index_path (path: &Path) -> PathBuf {
+
+    let mut index = path.as_os_str ().to_os_string ();
+    index.push (".idx");
+    PathBuf::from (index)
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Returns the seconds since the Unix epoch, or 0 for earlier times.
+fn epoch_seconds (time: SystemTime) -> i64 {
+    time.duration_since (UNIX_EPOCH).map_or (0, |duration| duration.as_secs () as i64)
+}
@@ -0,0 +1,252 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Compact string storage for records queued for the background thread.
+/*===============================================================================================*/
+
+// Module imports
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use std::str;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// The longest message kept inline.
+const INLINE_LENGTH: usize = 63;
+
+// The most targets interned. Targets seen after that are allocated for each record.
+const MAX_TARGETS: usize = 4096;
+
+// The interned targets, and the number of times they have been cleared.
+static TARGETS: RwLock<BTreeSet<Arc<str>>> = RwLock::new (BTreeSet::new ());
+static GENERATION: AtomicUsize = AtomicUsize::new (0);
+
+thread_local! {
+
+    // The interned targets this thread has used, so it doesn't take the shared lock for them
+    // again, and the generation they came from.
+    static CACHE: RefCell<(usize, HashSet<Arc<str>>)> = RefCell::new ((0, HashSet::new ()));
+}
+
+/*===============================================================================================*/
+/*------MESSAGE ENUM-----------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// A message which is kept inline when short enough, and allocated otherwise.
+pub enum Message {
+
+    Inline (u8, [u8; INLINE_LENGTH]),
+    Heap (String),
+}
+
+/*===============================================================================================*/
+/*------TARGET ENUM------------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// A target which is shared when interned, and allocated for each record otherwise.
+pub enum Target {
+
+    Interned (Arc<str>),
+    Heap (Box<str>),
+}
+
+/*===============================================================================================*/
+/*------MESSAGE TRAIT IMPLEMENTATIONS------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl fmt::Write for Message {
+
+    fn write_str (&mut self, text: &str) -> fmt::Result {
+
+        match *self {
+
+            Message::Inline (ref mut length, ref mut bytes) if *length as usize + text.len () <= INLINE_LENGTH => {
+
+                let start = *length as usize;
+
+                bytes[start..start + text.len ()].copy_from_slice (text.as_bytes ());
+                *length += text.len () as u8;
+            },
+
+            Message::Inline (..) => {
+
+                let mut heap = String::with_capacity (INLINE_LENGTH + text.len ());
+
+                heap.push_str (self.as_str ());
+                heap.push_str (text);
+                *self = Message::Heap (heap);
+            },
+
+            Message::Heap (ref mut heap) => heap.push_str (text),
+        }
+
+        Ok (())
+    }
+}
+
+/*===============================================================================================*/
+/*------MESSAGE PUBLIC METHODS-------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Message {
+
+    // Formats the arguments, only allocating if they don't fit inline.
+    pub fn format (args: fmt::Arguments) -> Self {
+
+        let mut message = Message::Inline (0, [0; INLINE_LENGTH]);
+
+        fmt::write (&mut message, args).unwrap ();
+        message
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    pub fn new (text: &str) -> Self {
+        Message::format (format_args! ("{}", text))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    pub fn as_str (&self) -> &str {
+
+        match *self {
+
+            // Only whole strings are copied in, so the bytes are always valid.
+            Message::Inline (length, ref bytes) => str::from_utf8 (&bytes[..length as usize]).unwrap (),
+            Message::Heap (ref heap) => heap,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // The heap memory the message uses.
+    pub fn heap_size (&self) -> usize {
+
+        match *self {
+
+            Message::Inline (..) => 0,
+            Message::Heap (ref heap) => heap.capacity (),
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------TARGET PUBLIC METHODS--------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Target {
+
+    pub fn as_str (&self) -> &str {
+
+        match *self {
+
+            Target::Interned (ref interned) => interned,
+            Target::Heap (ref heap) => heap,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // The heap memory the target uses for this record alone.
+    pub fn heap_size (&self) -> usize {
+
+        match *self {
+
+            Target::Interned (_) => 0,
+            Target::Heap (ref heap) => heap.len (),
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Returns a shared copy of the target, so queued records from the same target share one string.
+// Once `MAX_TARGETS` are interned, new targets get their own copy.
+pub fn intern_target (target: &str) -> Target {
+
+    let generation = GENERATION.load (Ordering::Acquire);
+    let cached = CACHE.try_with (|cache| {
+
+        let mut cache = cache.borrow_mut ();
+
+        if cache.0 != generation {
+            *cache = (generation, HashSet::new ());
+        }
+
+        if let Some (interned) = cache.1.get (target) {
+            return Some (interned.clone ());
+        }
+
+        let interned = shared_target (target)?;
+
+        cache.1.insert (interned.clone ());
+        Some (interned)
+    });
+
+    // The cache is gone if the thread is exiting, so only the shared set is used.
+    let interned = match cached {
+
+        Ok (interned) => interned,
+        Err (_) => shared_target (target),
+    };
+
+    match interned {
+
+        Some (interned) => Target::Interned (interned),
+        None => Target::Heap (Box::from (target)),
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Forgets the interned targets, once the logger is released. Each thread drops its cache the
+// next time it logs.
+pub fn clear_targets () {
+
+    TARGETS.write ().unwrap ().clear ();
+    GENERATION.fetch_add (1, Ordering::Release);
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Looks the target up in the shared set, adding it if there's room.
+fn shared_target (target: &str) -> Option<Arc<str>> {
+
+    if let Some (interned) = TARGETS.read ().unwrap ().get (target) {
+        return Some (interned.clone ());
+    }
+
+    let mut targets = TARGETS.write ().unwrap ();
+
+    if let Some (interned) = targets.get (target) {
+        return Some (interned.clone ());
+    }
+
+    if targets.len () >= MAX_TARGETS {
+        return None;
+    }
+
+    let interned: Arc<str> = Arc::from (target);
+
+    targets.insert (interned.clone ());
+    Some (interned)
+}
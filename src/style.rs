@@ -0,0 +1,244 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Colours for terminal output.
+/*===============================================================================================*/
+
+// Module imports
+#[cfg (feature = "colour")]
+use ansi_term::{Colour, Style};
+#[cfg (feature = "regex")]
+use regex::{self, Regex};
+#[cfg (feature = "colour")]
+use LogConfig;
+
+#[cfg (feature = "colour")]
+use std::fmt::Write;
+
+/*===============================================================================================*/
+/*------TERM COLOUR ENUM-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// One of the eight standard terminal colours.
+#[derive (Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TermColour {
+
+    /// Black.
+    Black,
+    /// Red.
+    Red,
+    /// Green.
+    Green,
+    /// Yellow.
+    Yellow,
+    /// Blue.
+    Blue,
+    /// Purple.
+    Purple,
+    /// Cyan.
+    Cyan,
+    /// White.
+    White,
+}
+
+/*===============================================================================================*/
+/*------TARGET COLOUR STRUCT---------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Draws the `[module - line]` part of terminal lines from a target in a colour, while the
+/// rest of the line keeps its level colour.
+#[derive (Clone, Debug, PartialEq, Eq)]
+pub struct TargetColour {
+
+    // Public
+    /// The target, matched like a module filter. `*` matches any characters.
+    pub target: String,
+    /// The colour to use.
+    pub colour: TermColour,
+}
+
+/*===============================================================================================*/
+/*------TERM STYLE STRUCT------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// How to draw text in the terminal.
+#[derive (Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TermStyle {
+
+    // Public
+    /// The text colour, or `None` to keep the surrounding colour.
+    pub colour:    Option<TermColour>,
+    /// Whether the text is bold.
+    pub bold:      bool,
+    /// Whether the text is underlined.
+    pub underline: bool,
+}
+
+/*===============================================================================================*/
+/*------HIGHLIGHT RULE STRUCT--------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Draws the parts of terminal messages matching a regex in a style.
+///
+/// Highlighting only changes the terminal output, never what is written to files.
+///
+/// # Examples
+/// ```
+/// # use ion_log::*;
+/// let mut config = LogConfig::new ();
+/// let slow = TermStyle {colour: Some (TermColour::Red), bold: true, underline: false};
+///
+/// config.highlights.push (HighlightRule::new (r"\b\d{1,3}(\.\d{1,3}){3}\b", TermStyle {underline: true, ..TermStyle::default ()}).unwrap ());
+/// config.highlights.push (HighlightRule::new (r"\b([1-9]\d{3,}|[1-9]\d{2})ms\b", slow).unwrap ());
+/// ```
+#[cfg (feature = "regex")]
+#[cfg_attr (not (feature = "colour"), allow (dead_code))]
+#[derive (Clone, Debug)]
+pub struct HighlightRule {
+
+    // Private
+    pattern: Regex,
+    style: TermStyle,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Applies the highlight rules to terminal lines.
+#[cfg (feature = "colour")]
+pub struct Highlighter {
+
+    // Private
+    #[cfg (feature = "regex")]
+    rules: Vec<HighlightRule>,
+}
+
+/*===============================================================================================*/
+/*------HIGHLIGHT RULE PUBLIC METHODS------------------------------------------------------------*/
+/*===============================================================================================*/
+
+#[cfg (feature = "regex")]
+impl HighlightRule {
+
+    /// Returns a rule drawing matches of the regex in the style.
+    ///
+    /// # Errors
+    /// Fails if the regex is invalid.
+    pub fn new (pattern: &str, style: TermStyle) -> Result<Self, regex::Error> {
+        Ok (HighlightRule {pattern: Regex::new (pattern)?, style})
+    }
+}
+
+/*===============================================================================================*/
+/*------HIGHLIGHTER PUBLIC METHODS---------------------------------------------------------------*/
+/*===============================================================================================*/
+
+#[cfg (feature = "colour")]
+impl Highlighter {
+
+    #[cfg (feature = "regex")]
+    pub fn new (config: &LogConfig) -> Self {
+        Highlighter {rules: config.highlights.clone ()}
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    #[cfg (not (feature = "regex"))]
+    pub fn new (_config: &LogConfig) -> Self {
+        Highlighter {}
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Appends text which is already in the base style, then ends the style.
+    // Where matches overlap, the one starting first wins, then the earlier rule.
+    #[cfg (feature = "regex")]
+    pub fn write (&self, out: &mut String, text: &str, base: Style) {
+
+        let mut matches: Vec<_> = self.rules.iter ()
+                                            .flat_map (|rule| rule.pattern.find_iter (text).map (move |found| (found.range (), rule.style)))
+                                            .filter (|(range, _)| !range.is_empty ())
+                                            .collect ();
+
+        matches.sort_by_key (|(range, _)| range.start);
+
+        let mut pos = 0;
+
+        for (range, style) in matches {
+
+            if range.start < pos {
+                continue;
+            }
+
+            let style = ansi_style (style);
+
+            write! (out, "{}{}{}{}{}",
+                    &text[pos..range.start],
+                    style.prefix (), &text[range.clone ()], style.suffix (),
+                    base.prefix ()).unwrap ();
+
+            pos = range.end;
+        }
+
+        write! (out, "{}{}", &text[pos..], base.suffix ()).unwrap ();
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    #[cfg (not (feature = "regex"))]
+    pub fn write (&self, out: &mut String, text: &str, base: Style) {
+        write! (out, "{}{}", text, base.suffix ()).unwrap ();
+    }
+}
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Converts to the `ansi_term` colour.
+#[cfg (feature = "colour")]
+pub fn ansi (colour: TermColour) -> Colour {
+
+    match colour {
+
+        TermColour::Black  => Colour::Black,
+        TermColour::Red    => Colour::Red,
+        TermColour::Green  => Colour::Green,
+        TermColour::Yellow => Colour::Yellow,
+        TermColour::Blue   => Colour::Blue,
+        TermColour::Purple => Colour::Purple,
+        TermColour::Cyan   => Colour::Cyan,
+        TermColour::White  => Colour::White,
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+#[cfg (all (feature = "colour", feature = "regex"))]
+fn ansi_style (style: TermStyle) -> Style {
+
+    let mut ansi_style = style.colour.map_or (Style::new (), |colour| ansi (colour).normal ());
+
+    if style.bold {
+        ansi_style = ansi_style.bold ();
+    }
+
+    if style.underline {
+        ansi_style = ansi_style.underline ();
+    }
+
+    ansi_style
+}
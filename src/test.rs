@@ -0,0 +1,196 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Helpers for asserting on log output in tests.
+//!
+//! Records are delivered to the channel subscribed by the thread which logged them, so
+//! tests running in parallel under `cargo test` each see only their own records.
+//!
+//! # Example
+//! ```
+//! # #[macro_use] extern crate log;
+//! # extern crate ion_log;
+//! # fn main () {
+//! let records = ion_log::test::channel ();
+//!
+//! warn! ("Retrying in {}s", 5);
+//!
+//! let record = records.try_recv ().unwrap ();
+//! assert_eq! (record.level, log::LogLevel::Warn);
+//! assert_eq! (record.message, "Retrying in 5s");
+//! # ion_log::release ();
+//! # }
+//! ```
+/*===============================================================================================*/
+
+// Module imports
+use log;
+use LogConfig;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, ThreadId};
+use std::time::SystemTime;
+
+// Serializes `channel`, so parallel tests don't race to install the logger.
+static SETUP: Mutex<()> = Mutex::new (());
+
+/*===============================================================================================*/
+/*------RECORD STRUCT----------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// A record sent to a `ChannelSink` subscriber.
+#[derive (Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+
+    // Public
+    /// The record level.
+    pub level:       log::LogLevel,
+    /// The time the record was logged, from `LogConfig::clock`.
+    pub timestamp:   SystemTime,
+    /// The record target.
+    pub target:      String,
+    /// The module path the record was logged from.
+    pub module_path: String,
+    /// The file the record was logged from.
+    pub file:        String,
+    /// The line the record was logged from.
+    pub line:        u32,
+    /// The record message.
+    pub message:     String,
+    /// The trace ID from the logging thread's trace context.
+    pub trace_id:    Option<String>,
+    /// The span ID from the logging thread's trace context.
+    pub span_id:     Option<String>,
+    /// The tags attached with `tagged!`.
+    pub tags:        Vec<String>,
+}
+
+/*===============================================================================================*/
+/*------CHANNEL SINK STRUCT----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// A sink which sends each record to the channel subscribed by the thread which logged it.
+///
+/// Records from threads without a subscription are discarded.
+#[derive (Clone, Debug, Default)]
+pub struct ChannelSink {
+
+    // Private
+    senders: Arc<Mutex<HashMap<ThreadId, Sender<Record>>>>,
+}
+
+/*===============================================================================================*/
+/*------CHANNEL SINK PUBLIC METHODS--------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl ChannelSink {
+
+    /// Returns a sink without any subscriptions.
+    pub fn new () -> Self {
+        ChannelSink::default ()
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    /// Returns a receiver for the records logged by the current thread, replacing the
+    /// thread's earlier subscription.
+    pub fn subscribe (&self) -> Receiver<Record> {
+
+        let (sender, receiver) = mpsc::channel ();
+
+        self.lock ().insert (thread::current ().id (), sender);
+        receiver
+    }
+}
+
+/*===============================================================================================*/
+/*------CHANNEL SINK PRIVATE METHODS-------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl ChannelSink {
+
+    fn lock (&self) -> MutexGuard<'_, HashMap<ThreadId, Sender<Record>>> {
+        self.senders.lock ().unwrap ()
+    }
+}
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Returns a receiver for the records logged by the current thread.
+///
+/// If the logger is running with a `LogConfig::channel_sink`, the thread subscribes to it.
+/// If it's running without one, it's reconfigured with a new sink. Otherwise it's initialized
+/// with the default config, without terminal output.
+///
+/// # Panics
+/// Panics if the logger can't be initialized or reconfigured.
+pub fn channel () -> Receiver<Record> {
+
+    let _setup = SETUP.lock ().unwrap_or_else (|e| e.into_inner ());
+
+    match ::current_config () {
+
+        Some (ref config) if config.channel_sink.is_some () => {
+            config.channel_sink.as_ref ().unwrap ().subscribe ()
+        },
+
+        Some (mut config) => {
+
+            let sink = ChannelSink::new ();
+            let receiver = sink.subscribe ();
+
+            config.channel_sink = Some (sink);
+            ::reconfigure (&config).unwrap ();
+            receiver
+        },
+
+        None => {
+
+            let sink = ChannelSink::new ();
+            let receiver = sink.subscribe ();
+            let mut config = LogConfig::new ();
+
+            config.log_to_io = false;
+            config.channel_sink = Some (sink);
+            ::init (&config).unwrap ();
+            receiver
+        },
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Used by the logger to deliver a record to the thread's subscriber, if it has one.
+// A subscription whose receiver was dropped is removed.
+#[doc (hidden)]
+pub fn send<F: FnOnce () -> Record> (sink: &ChannelSink, thread: ThreadId, record: F) {
+
+    let mut senders = sink.lock ();
+    let delivered = match senders.get (&thread) {
+
+        Some (sender) => sender.send (record ()).is_ok (),
+        None => return,
+    };
+
+    if !delivered {
+        senders.remove (&thread);
+    }
+}
@@ -0,0 +1,85 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Timing scopes through the logger, for `time_scope!`.
+/*===============================================================================================*/
+
+// Module imports
+use log;
+
+use std::time::{Duration, Instant};
+
+/*===============================================================================================*/
+/*------SCOPE TIMER STRUCT-----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// Logs how long it lived at debug when dropped, created by `time_scope!`.
+#[derive (Debug)]
+pub struct ScopeTimer {
+
+    // Private
+    name: &'static str,
+    location: log::LogLocation,
+    threshold: Duration,
+    start: Instant,
+}
+
+/*===============================================================================================*/
+/*------SCOPE TIMER PUBLIC METHODS---------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl ScopeTimer {
+
+    // Used by `time_scope!`.
+    #[doc (hidden)]
+    pub fn __new (name: &'static str, location: (&'static str, &'static str, u32), threshold: Duration) -> Self {
+
+        let (module_path, file, line) = location;
+
+        ScopeTimer {
+
+            name,
+            location: log::LogLocation {__module_path: module_path, __file: file, __line: line},
+            threshold,
+            start: Instant::now (),
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------SCOPE TIMER TRAIT IMPLEMENTATIONS--------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Drop for ScopeTimer {
+
+    fn drop (&mut self) {
+
+        let level = log::LogLevel::Debug;
+
+        if level > log::__static_max_level () || level > log::max_log_level () {
+            return;
+        }
+
+        let elapsed = self.start.elapsed ();
+
+        if elapsed >= self.threshold {
+
+            let target = self.location.module_path ();
+            log::__log (level, target, &self.location, format_args! ("{} took {:?}", self.name, elapsed));
+        }
+    }
+}
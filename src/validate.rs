@@ -0,0 +1,289 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! Checks a `LogConfig` for problems before it is used.
+/*===============================================================================================*/
+
+// Module imports
+use filter;
+use rotation;
+use LogConfig;
+
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::path::Path;
+
+/*===============================================================================================*/
+/*------CONFIG ERROR ENUM------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+/// A problem found by `LogConfig::validate`.
+#[derive (Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+
+    /// `log_to_file` is set, but neither `log_output_path` nor `log_file_handle` is.
+    MissingOutputPath,
+    /// The log file or audit log can't be written to.
+    UnwritablePath {
+
+        /// The log output file or audit log path.
+        path:   String,
+        /// Why the path can't be written to.
+        reason: String,
+    },
+    /// `rotation` is set for a log file opened from `log_file_handle`, which can't be renamed.
+    RotationWithoutPath,
+    /// `rotation` has no size limit and doesn't rotate daily, so it never rotates.
+    RotationNeverDue,
+    /// `rotation` has a `time_of_day` of a day or more.
+    InvalidRotationTime,
+    /// `rotation` has a `file_names` template without `{seq}` once, or with a path separator.
+    InvalidRotationFileNames (String),
+    /// A module filter has a malformed module name.
+    InvalidModuleFilter (String),
+    /// A blocked target has a malformed module name.
+    InvalidBlockedTarget (String),
+    /// An allowed target has a malformed module name.
+    InvalidAllowedTarget (String),
+    /// A target colour has a malformed module name.
+    InvalidTargetColour (String),
+    /// A level name has a malformed module name, or is empty.
+    InvalidLevelName (String),
+    /// `async_output` is set, but `async_capacity` is zero.
+    ZeroAsyncCapacity,
+    /// `async_batching` has a zero `min_records`, or one above `max_records`.
+    InvalidAsyncBatching,
+    /// `load_shedding` has a threshold outside 0 to 1, or sheds a level before a lower one.
+    InvalidLoadShedding,
+    /// `heartbeat` is set with a zero interval.
+    ZeroHeartbeatInterval,
+}
+
+/*===============================================================================================*/
+/*------CONFIG ERROR TRAIT IMPLEMENTATIONS-------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl fmt::Display for ConfigError {
+
+    fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
+
+        match *self {
+
+            ConfigError::MissingOutputPath =>
+                write! (f, "file logging is enabled, but no output path is set"),
+
+            ConfigError::UnwritablePath {ref path, ref reason} =>
+                write! (f, "can't write to log file `{}`: {}", path, reason),
+
+            ConfigError::RotationWithoutPath =>
+                write! (f, "log rotation needs the file to be opened from `log_output_path`, not `log_file_handle`"),
+
+            ConfigError::RotationNeverDue =>
+                write! (f, "log rotation is set, but with no size limit and no daily rotation"),
+
+            ConfigError::InvalidRotationTime =>
+                write! (f, "the rotation time of day must be less than 24 hours"),
+
+            ConfigError::InvalidRotationFileNames (ref template) =>
+                write! (f, "the rotated file names `{}` must contain `{{seq}}` once, and no path separators", template),
+
+            ConfigError::InvalidModuleFilter (ref module) =>
+                write! (f, "invalid module name `{}` in module filter", module),
+
+            ConfigError::InvalidBlockedTarget (ref target) =>
+                write! (f, "invalid module name `{}` in blocked targets", target),
+
+            ConfigError::InvalidAllowedTarget (ref target) =>
+                write! (f, "invalid module name `{}` in allowed targets", target),
+
+            ConfigError::InvalidTargetColour (ref target) =>
+                write! (f, "invalid module name `{}` in target colours", target),
+
+            ConfigError::InvalidLevelName (ref target) =>
+                write! (f, "invalid module name or empty name for `{}` in level names", target),
+
+            ConfigError::ZeroAsyncCapacity =>
+                write! (f, "async output is enabled, but the queue capacity is zero"),
+
+            ConfigError::InvalidAsyncBatching =>
+                write! (f, "the async batch sizes must be at least 1, with the minimum no larger than the maximum"),
+
+            ConfigError::InvalidLoadShedding =>
+                write! (f, "the load shedding thresholds must be between 0 and 1, with trace <= debug <= info"),
+
+            ConfigError::ZeroHeartbeatInterval =>
+                write! (f, "the heartbeat interval is zero"),
+        }
+    }
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+impl Error for ConfigError {}
+
+/*===============================================================================================*/
+/*------PUBLIC FUNCTIONS-------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Returns every problem with the config, in field order.
+pub fn validate (config: &LogConfig) -> Vec<ConfigError> {
+
+    let mut errors = Vec::new ();
+
+    if config.log_to_file && config.log_file_handle.is_none () {
+
+        if config.log_output_path.is_empty () {
+            errors.push (ConfigError::MissingOutputPath);
+        }
+
+        else if let Err (reason) = check_writable (Path::new (&config.log_output_path)) {
+
+            errors.push (ConfigError::UnwritablePath {
+
+                path: config.log_output_path.clone (),
+                reason,
+            });
+        }
+    }
+
+    if let Some (ref rotation) = config.rotation {
+
+        if config.log_to_file && config.log_file_handle.is_some () {
+            errors.push (ConfigError::RotationWithoutPath);
+        }
+
+        if rotation.max_size == 0 && !rotation.daily {
+            errors.push (ConfigError::RotationNeverDue);
+        }
+
+        if rotation.time_of_day.is_some_and (|time_of_day| time_of_day.as_secs () >= 86_400) {
+            errors.push (ConfigError::InvalidRotationTime);
+        }
+
+        if let Some (ref template) = rotation.file_names.as_ref ().filter (|template| !rotation::is_valid_file_names (template)) {
+            errors.push (ConfigError::InvalidRotationFileNames (template.to_string ()));
+        }
+    }
+
+    if !config.audit_log_path.is_empty () {
+
+        if let Err (reason) = check_writable (Path::new (&config.audit_log_path)) {
+
+            errors.push (ConfigError::UnwritablePath {
+
+                path: config.audit_log_path.clone (),
+                reason,
+            });
+        }
+    }
+
+    for module_filter in &config.module_filters {
+
+        if !filter::is_valid_module (&module_filter.module) {
+            errors.push (ConfigError::InvalidModuleFilter (module_filter.module.clone ()));
+        }
+    }
+
+    for target in &config.blocked_targets {
+
+        if !filter::is_valid_module (target) {
+            errors.push (ConfigError::InvalidBlockedTarget (target.clone ()));
+        }
+    }
+
+    for target in &config.allowed_targets {
+
+        if !filter::is_valid_module (target) {
+            errors.push (ConfigError::InvalidAllowedTarget (target.clone ()));
+        }
+    }
+
+    for target_colour in &config.target_colours {
+
+        if !filter::is_valid_module (&target_colour.target) {
+            errors.push (ConfigError::InvalidTargetColour (target_colour.target.clone ()));
+        }
+    }
+
+    for level_name in &config.level_names {
+
+        if !filter::is_valid_module (&level_name.target) || level_name.name.trim ().is_empty () {
+            errors.push (ConfigError::InvalidLevelName (level_name.target.clone ()));
+        }
+    }
+
+    if config.async_output && config.async_capacity == 0 {
+        errors.push (ConfigError::ZeroAsyncCapacity);
+    }
+
+    let batching = config.async_batching;
+
+    if config.async_output && (batching.min_records == 0 || batching.min_records > batching.max_records) {
+        errors.push (ConfigError::InvalidAsyncBatching);
+    }
+
+    if let Some (shedding) = config.load_shedding {
+
+        let thresholds = [shedding.trace, shedding.debug, shedding.info];
+
+        if thresholds.iter ().any (|threshold| !(0.0..=1.0).contains (threshold)) ||
+           shedding.trace > shedding.debug || shedding.debug > shedding.info {
+
+            errors.push (ConfigError::InvalidLoadShedding);
+        }
+    }
+
+    if let Some (ref heartbeat) = config.heartbeat {
+
+        if heartbeat.interval.is_zero () {
+            errors.push (ConfigError::ZeroHeartbeatInterval);
+        }
+    }
+
+    errors
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Checks the log file could be created, without creating or truncating it.
+fn check_writable (path: &Path) -> Result<(), String> {
+
+    if path.is_dir () {
+        return Err ("path is a directory".to_string ());
+    }
+
+    if path.exists () {
+        return OpenOptions::new ().append (true).open (path).map (|_| ()).map_err (|e| e.to_string ());
+    }
+
+    let parent = match path.parent () {
+
+        Some (parent) if !parent.as_os_str ().is_empty () => parent,
+        _ => Path::new ("."),
+    };
+
+    match fs::metadata (parent) {
+
+        Ok (ref metadata) if !metadata.is_dir () => Err (format! ("`{}` is not a directory", parent.display ())),
+        Ok (ref metadata) if metadata.permissions ().readonly () => Err (format! ("`{}` is read only", parent.display ())),
+        Ok (_) => Ok (()),
+        Err (e) => Err (format! ("`{}`: {}", parent.display (), e)),
+    }
+}
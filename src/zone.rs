@@ -0,0 +1,498 @@
+/*===============================================================================================*/
+// Copyright 2016 Kyle Finlay
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+/*===============================================================================================*/
+
+/*===============================================================================================*/
+//! The local time zone, read from the tz database without a C library.
+/*===============================================================================================*/
+
+// Module imports
+use format;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// Where the tz database is looked for when `TZ` names a zone.
+const ZONEINFO: &str = "/usr/share/zoneinfo";
+
+/*===============================================================================================*/
+/*------ZONE STRUCT------------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// UTC offsets over time, from a TZif file and the POSIX rule in its footer, or from `TZ`.
+pub struct Zone {
+
+    // Private
+    initial: i64,
+    transitions: Vec<(i64, i64)>,
+    rule: Option<Rule>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// A POSIX `TZ` rule, as in `EST5EDT,M3.2.0,M11.1.0`. Offsets are seconds east of UTC.
+struct Rule {
+
+    // Private
+    standard: i64,
+    daylight: Option<DaylightRule>,
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+struct DaylightRule {
+
+    // Private
+    offset: i64,
+    start: (RuleDay, i64),
+    end: (RuleDay, i64),
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// The day of the year a daylight saving change falls on.
+#[derive (Copy, Clone)]
+enum RuleDay {
+
+    // `Jn`, from 1 to 365, never counting February 29.
+    Julian (i64),
+    // `n`, from 0 to 365, counting February 29 in leap years.
+    Ordinal (i64),
+    // `Mm.w.d`, day `d` (0 for Sunday) of week `w` of month `m`, where week 5 is the last.
+    Month (u32, i64, i64),
+}
+
+/*===============================================================================================*/
+/*------ZONE PUBLIC METHODS----------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Zone {
+
+    // Returns the local zone, from `TZ` or `/etc/localtime`. UTC is used if neither can be
+    // read, as on Windows.
+    pub fn local () -> Self {
+
+        match env::var ("TZ") {
+
+            Ok (tz) => Zone::from_tz (&tz),
+            Err (_) => Zone::from_file (Path::new ("/etc/localtime")).unwrap_or_else (Zone::utc),
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Returns the offset from UTC, in seconds east, at a time in seconds since the Unix epoch.
+    pub fn offset (&self, time: i64) -> i64 {
+
+        let index = self.transitions.partition_point (|&(at, _)| at <= time);
+
+        match self.rule {
+
+            Some (ref rule) if index == self.transitions.len () => rule.offset (time),
+            _ if index == 0 => self.initial,
+            _ => self.transitions[index - 1].1,
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Returns the first time the local clock reads the time of day, in seconds since
+    // midnight, after the time. A time skipped by a daylight saving change is moved forward
+    // by the change, and a repeated one is taken the first time it comes round.
+    pub fn next_time_of_day (&self, after: i64, time_of_day: i64) -> i64 {
+
+        let local_day = (after + self.offset (after)).div_euclid (86_400);
+
+        (local_day..local_day + 3).map (|day| self.wall_time (day * 86_400 + time_of_day))
+                                  .find (|&time| time > after)
+                                  .unwrap_or (after + 86_400)
+    }
+}
+
+/*===============================================================================================*/
+/*------ZONE PRIVATE METHODS---------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Zone {
+
+    fn utc () -> Self {
+        Zone {initial: 0, transitions: Vec::new (), rule: None}
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Reads `TZ`, as a path, a zone name or a POSIX rule.
+    fn from_tz (tz: &str) -> Self {
+
+        let tz = tz.strip_prefix (':').unwrap_or (tz);
+
+        if tz.is_empty () {
+            return Zone::utc ();
+        }
+
+        let path = if tz.starts_with ('/') {Path::new (tz).to_path_buf ()} else {Path::new (ZONEINFO).join (tz)};
+
+        match Zone::from_file (&path) {
+
+            Some (zone) => zone,
+            None => match Rule::parse (tz) {
+
+                Some (rule) => Zone {initial: rule.standard, transitions: Vec::new (), rule: Some (rule)},
+                None => Zone::utc (),
+            },
+        }
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn from_file (path: &Path) -> Option<Self> {
+        Zone::parse (&fs::read (path).ok ()?)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Parses a TZif file, as described in RFC 8536. Version 2 and later files have their
+    // transitions again with 64 bit times, and a POSIX rule for times after the last one.
+    fn parse (data: &[u8]) -> Option<Self> {
+
+        let (header, rest) = Header::parse (data)?;
+
+        if header.version < b'2' {
+            return header.read_block (rest, 4).map (|(zone, _)| zone);
+        }
+
+        let (_, rest) = header.read_block (rest, 4)?;
+        let (header, rest) = Header::parse (rest)?;
+        let (mut zone, rest) = header.read_block (rest, 8)?;
+
+        let footer = rest.strip_prefix (b"\n")?;
+        let footer = &footer[..footer.iter ().position (|&byte| byte == b'\n')?];
+
+        zone.rule = std::str::from_utf8 (footer).ok ().and_then (Rule::parse);
+        Some (zone)
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Returns when the local clock reads a time, given in seconds since the epoch as if it
+    // were UTC. The offsets a day either side of it are tried, as a change can only be
+    // between them.
+    fn wall_time (&self, local: i64) -> i64 {
+
+        let before = self.offset (local - 86_400);
+        let after = self.offset (local + 86_400);
+
+        let earlier = local - before;
+        let later = local - after;
+
+        match (self.offset (earlier) == before, self.offset (later) == after) {
+
+            (true, true)  => earlier.min (later),
+            (true, false) => earlier,
+            (false, true) => later,
+            // The time was skipped, so the clock passes it as the change's length later.
+            (false, false) => earlier,
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------HEADER STRUCT----------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// The counts at the start of each block of a TZif file.
+struct Header {
+
+    // Private
+    version: u8,
+    ut_indicators: usize,
+    standard_indicators: usize,
+    leap_seconds: usize,
+    transitions: usize,
+    types: usize,
+    abbreviation_bytes: usize,
+}
+
+/*===============================================================================================*/
+/*------HEADER PRIVATE METHODS-------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Header {
+
+    fn parse (data: &[u8]) -> Option<(Self, &[u8])> {
+
+        if data.len () < 44 || &data[..4] != b"TZif" {
+            return None;
+        }
+
+        let count = |index: usize| {
+
+            let start = 20 + index * 4;
+            u32::from_be_bytes ([data[start], data[start + 1], data[start + 2], data[start + 3]]) as usize
+        };
+
+        let header = Header {
+
+            version:             data[4],
+            ut_indicators:       count (0),
+            standard_indicators: count (1),
+            leap_seconds:        count (2),
+            transitions:         count (3),
+            types:               count (4),
+            abbreviation_bytes:  count (5),
+        };
+
+        Some ((header, &data[44..]))
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    // Reads the block after the header, with times of `time_size` bytes, and returns the
+    // zone with the rest of the data.
+    fn read_block<'a> (&self, data: &'a [u8], time_size: usize) -> Option<(Zone, &'a [u8])> {
+
+        let times_length = self.transitions * time_size;
+        let types_start = times_length + self.transitions;
+        let length = types_start +
+                     self.types * 6 +
+                     self.abbreviation_bytes +
+                     self.leap_seconds * (time_size + 4) +
+                     self.standard_indicators +
+                     self.ut_indicators;
+
+        if data.len () < length || self.types == 0 {
+            return None;
+        }
+
+        let offset = |index: usize| -> Option<i64> {
+
+            let start = types_start + index * 6;
+            let bytes = data.get (start..start + 4)?;
+
+            Some (i64::from (i32::from_be_bytes ([bytes[0], bytes[1], bytes[2], bytes[3]])))
+        };
+
+        let mut transitions = Vec::with_capacity (self.transitions);
+
+        for index in 0..self.transitions {
+
+            let bytes = &data[index * time_size..(index + 1) * time_size];
+            let time = if time_size == 8 {
+                i64::from_be_bytes ([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]])
+            }
+
+            else {
+                i64::from (i32::from_be_bytes ([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            };
+
+            transitions.push ((time, offset (usize::from (data[times_length + index]))?));
+        }
+
+        Some ((Zone {initial: offset (0)?, transitions, rule: None}, &data[length..]))
+    }
+}
+
+/*===============================================================================================*/
+/*------RULE PRIVATE METHODS---------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl Rule {
+
+    // Parses a POSIX `TZ` rule. A daylight saving zone without dates follows the US rules.
+    fn parse (text: &str) -> Option<Self> {
+
+        let mut rest = text;
+
+        parse_name (&mut rest)?;
+        let standard = -parse_offset (&mut rest)?;
+
+        if rest.is_empty () {
+            return Some (Rule {standard, daylight: None});
+        }
+
+        parse_name (&mut rest)?;
+
+        let offset = if rest.is_empty () || rest.starts_with (',') {standard + 3600} else {-parse_offset (&mut rest)?};
+        let rest = if rest.is_empty () {",M3.2.0,M11.1.0"} else {rest};
+
+        let mut dates = rest.strip_prefix (',')?.split (',');
+        let start = parse_change (dates.next ()?)?;
+        let end = parse_change (dates.next ()?)?;
+
+        if dates.next ().is_some () {
+            return None;
+        }
+
+        Some (Rule {standard, daylight: Some (DaylightRule {offset, start, end})})
+    }
+
+/*-----------------------------------------------------------------------------------------------*/
+
+    fn offset (&self, time: i64) -> i64 {
+
+        let daylight = match self.daylight {
+
+            Some (ref daylight) => daylight,
+            None => return self.standard,
+        };
+
+        let (year, _, _) = format::civil_from_days ((time + self.standard).div_euclid (86_400));
+
+        // The changes are given in local time, the start in standard time and the end in daylight time.
+        let start = daylight.start.0.day (year) * 86_400 + daylight.start.1 - self.standard;
+        let end = daylight.end.0.day (year) * 86_400 + daylight.end.1 - daylight.offset;
+
+        let in_daylight = if start < end {
+            time >= start && time < end
+        }
+
+        else {
+            time < end || time >= start
+        };
+
+        if in_daylight {daylight.offset} else {self.standard}
+    }
+}
+
+/*===============================================================================================*/
+/*------RULE DAY PRIVATE METHODS-----------------------------------------------------------------*/
+/*===============================================================================================*/
+
+impl RuleDay {
+
+    // Returns the day in the year, in days since the Unix epoch.
+    fn day (self, year: i64) -> i64 {
+
+        let new_year = format::days_from_civil (year, 1, 1);
+        let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+
+        match self {
+
+            RuleDay::Julian (day) => new_year + day - 1 + if leap && day >= 60 {1} else {0},
+            RuleDay::Ordinal (day) => new_year + day,
+            RuleDay::Month (month, week, weekday) => {
+
+                let first = format::days_from_civil (year, month, 1);
+                let next_month = if month == 12 {
+                    format::days_from_civil (year + 1, 1, 1)
+                }
+
+                else {
+                    format::days_from_civil (year, month + 1, 1)
+                };
+
+                // 1970-01-01 was a Thursday.
+                let first_weekday = (first + 4).rem_euclid (7);
+                let mut day = first + (weekday - first_weekday).rem_euclid (7) + (week - 1) * 7;
+
+                while day >= next_month {
+                    day -= 7;
+                }
+
+                day
+            },
+        }
+    }
+}
+
+/*===============================================================================================*/
+/*------PRIVATE FUNCTIONS------------------------------------------------------------------------*/
+/*===============================================================================================*/
+
+// Skips a zone abbreviation, as in `EST` or `<+0330>`.
+fn parse_name (rest: &mut &str) -> Option<()> {
+
+    let length = if rest.starts_with ('<') {
+        rest.find ('>')? + 1
+    }
+
+    else {
+        rest.bytes ().take_while (u8::is_ascii_alphabetic).count ()
+    };
+
+    if length < 3 {
+        return None;
+    }
+
+    *rest = &rest[length..];
+    Some (())
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Parses an offset or time as `[+|-]hh[:mm[:ss]]`, in seconds.
+fn parse_offset (rest: &mut &str) -> Option<i64> {
+
+    let sign = match rest.as_bytes ().first () {
+
+        Some (b'-') => -1,
+        _ => 1,
+    };
+
+    let text = rest.strip_prefix (['+', '-']).unwrap_or (rest);
+    let length = text.bytes ().take_while (|&byte| byte.is_ascii_digit () || byte == b':').count ();
+    let mut seconds = 0;
+
+    for (part, scale) in text[..length].split (':').zip ([3600, 60, 1]) {
+        seconds += part.parse::<i64> ().ok ()? * scale;
+    }
+
+    *rest = &text[length..];
+    Some (sign * seconds)
+}
+
+/*-----------------------------------------------------------------------------------------------*/
+
+// Parses a daylight saving change, as `date[/time]`, where the time defaults to 02:00.
+fn parse_change (text: &str) -> Option<(RuleDay, i64)> {
+
+    let (date, time) = match text.find ('/') {
+
+        Some (index) => (&text[..index], {
+
+            let mut time = &text[index + 1..];
+            let seconds = parse_offset (&mut time)?;
+
+            if !time.is_empty () {
+                return None;
+            }
+
+            seconds
+        }),
+
+        None => (text, 7200),
+    };
+
+    let day = if let Some (day) = date.strip_prefix ('J') {
+        RuleDay::Julian (day.parse ().ok ().filter (|day| (1..=365).contains (day))?)
+    }
+
+    else if let Some (month) = date.strip_prefix ('M') {
+
+        let mut parts = month.split ('.');
+        let month = parts.next ()?.parse ().ok ().filter (|month| (1..=12).contains (month))?;
+        let week = parts.next ()?.parse ().ok ().filter (|week| (1..=5).contains (week))?;
+        let weekday = parts.next ()?.parse ().ok ().filter (|weekday| (0..=6).contains (weekday))?;
+
+        RuleDay::Month (month, week, weekday)
+    }
+
+    else {
+        RuleDay::Ordinal (date.parse ().ok ().filter (|day| (0..=365).contains (day))?)
+    };
+
+    Some ((day, time))
+}